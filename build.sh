@@ -59,6 +59,10 @@ fi
 echo "📋 Copying public assets..."
 cp -r public/* dist/ 2>/dev/null || true
 
+# Bevy fetches runtime assets from assets/ next to the page
+echo "📋 Copying game assets..."
+cp -r game/assets dist/assets
+
 echo "✅ Build complete! Output in dist/ directory"
 echo "📊 Build size:"
 du -sh dist/
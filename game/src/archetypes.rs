@@ -0,0 +1,306 @@
+use bevy::asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::components::*;
+
+// ============= Enemy Archetypes =============
+//
+// Every enemy is an archetype from assets/enemies.archetypes.json: its class
+// and behaviour, health and damage curves over room number, ranges, sprite,
+// attack list with telegraph timings, and drops. Bosses and minibosses also
+// list phases, each with its own attack pattern and adds; see boss.rs.
+//
+// The file is embedded so enemies can spawn before the asset server has
+// loaded anything, and it is also loaded as an asset. With the `hot-reload`
+// feature edits to the file replace the archetypes while the game runs:
+// timings and attacks apply at once, stats and looks from the next spawn.
+
+const ARCHETYPES_PATH: &str = "enemies.archetypes.json";
+const ARCHETYPES_JSON: &str = include_str!("../assets/enemies.archetypes.json");
+
+/// `base + per_room * room_number`
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RoomScaling {
+    pub base: f32,
+    #[serde(default)]
+    pub per_room: f32,
+}
+
+impl RoomScaling {
+    pub fn at(&self, room_number: u32) -> f32 {
+        self.base + self.per_room * room_number as f32
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArchetypeSprite {
+    pub size: Vec2,
+    pub color: [f32; 3],
+}
+
+/// What peers draw while the enemy winds up; archetypes without one give no
+/// warning
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelegraphShape {
+    pub size: Vec2,
+    pub color: [f32; 4],
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind")]
+pub enum EnemyAttackKind {
+    /// A hitbox `reach` units toward the target
+    Melee {
+        size: Vec2,
+        reach: f32,
+        #[serde(default)]
+        knockback: f32,
+    },
+    /// `count` shots fanned across `spread` degrees
+    Projectile {
+        #[serde(default = "default_count")]
+        count: u32,
+        #[serde(default)]
+        spread: f32,
+        speed: f32,
+        range: f32,
+    },
+    /// Heals allies within `radius` by a fraction of their max HP and buffs
+    /// their damage and attack speed for `buff_seconds`
+    Pulse {
+        radius: f32,
+        heal_fraction: f32,
+        damage_buff: f32,
+        attack_speed_buff: f32,
+        buff_seconds: f32,
+    },
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnemyAttackDef {
+    #[serde(flatten)]
+    pub kind: EnemyAttackKind,
+    pub telegraph: f32, // Seconds
+    pub active: f32,
+    pub recovery: f32,
+    #[serde(default = "default_damage_multiplier")]
+    pub damage_multiplier: f32,
+    /// Put on players the attack hits, or on the allies a pulse reaches
+    #[serde(default)]
+    pub status: Option<OnHitStatus>,
+}
+
+fn default_damage_multiplier() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DropDef {
+    pub pickup: PickupType,
+    pub value: f32,
+    #[serde(default = "default_drop_chance")]
+    pub chance: f32,
+}
+
+fn default_drop_chance() -> f32 {
+    1.0
+}
+
+/// Enemies spawned around a boss as one of its phases begins
+#[derive(Debug, Clone, Deserialize)]
+pub struct AddSpawnDef {
+    pub archetype: String,
+    #[serde(default = "default_count")]
+    pub count: u32,
+}
+
+/// One stage of a boss fight. Phases run in file order, each starting once
+/// the boss drops to `health_threshold` of its max HP.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BossPhaseDef {
+    pub name: String,
+    pub health_threshold: f32,
+    /// Only part of the fight from this ascension level up
+    #[serde(default)]
+    pub min_ascension: u8,
+    /// Seconds the boss is invulnerable while it changes into this phase
+    #[serde(default)]
+    pub transition: f32,
+    /// Played in order and looped; an empty list keeps the archetype's own
+    pub attacks: Vec<EnemyAttackDef>,
+    #[serde(default)]
+    pub adds: Vec<AddSpawnDef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnemyArchetype {
+    pub name: String,
+    pub enemy_type: EnemyType,
+    pub behavior: EnemyBehavior,
+    pub health: RoomScaling,
+    pub damage: RoomScaling,
+    pub crit_chance: f32,
+    pub crit_damage: f32,
+    pub attack_speed: f32,
+    pub move_speed: f32,
+    pub aggro_range: f32,
+    pub attack_range: f32,
+    pub sprite: ArchetypeSprite,
+    #[serde(default)]
+    pub telegraph: Option<TelegraphShape>,
+    #[serde(default)]
+    pub attacks: Vec<EnemyAttackDef>,
+    #[serde(default)]
+    pub drops: Vec<DropDef>,
+    #[serde(default)]
+    pub phases: Vec<BossPhaseDef>,
+}
+
+impl EnemyArchetype {
+    pub fn is_boss(&self) -> bool {
+        !self.phases.is_empty()
+    }
+
+    /// The attack list in use during `phase`. Enemies without phases are
+    /// always in phase 0 and use their own list.
+    pub fn attacks_in(&self, phase: usize) -> &[EnemyAttackDef] {
+        match self.phases.get(phase) {
+            Some(phase) if !phase.attacks.is_empty() => &phase.attacks,
+            _ => &self.attacks,
+        }
+    }
+
+    /// The attack at `index` in `phase`, wrapping so a cycling index stays
+    /// valid after a phase change or a reload shortens the list
+    pub fn attack(&self, phase: usize, index: usize) -> Option<&EnemyAttackDef> {
+        let attacks = self.attacks_in(phase);
+        if attacks.is_empty() {
+            return None;
+        }
+        attacks.get(index % attacks.len())
+    }
+
+    /// How far this archetype's pulse reaches in `phase`, if it has one
+    pub fn pulse_radius(&self, phase: usize) -> Option<f32> {
+        self.attacks_in(phase).iter().find_map(|attack| match attack.kind {
+            EnemyAttackKind::Pulse { radius, .. } => Some(radius),
+            _ => None,
+        })
+    }
+}
+
+/// The archetype file as the asset server sees it
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct EnemyArchetypeSet {
+    pub archetypes: HashMap<String, EnemyArchetype>,
+}
+
+#[derive(Default)]
+pub struct EnemyArchetypeLoader;
+
+impl AssetLoader for EnemyArchetypeLoader {
+    type Asset = EnemyArchetypeSet;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["archetypes.json"]
+    }
+}
+
+/// Every archetype by id. Starts from the embedded copy and follows the asset
+/// once it loads or changes.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct EnemyArchetypes {
+    pub archetypes: HashMap<String, EnemyArchetype>,
+    handle: Handle<EnemyArchetypeSet>,
+}
+
+impl EnemyArchetypes {
+    pub fn load() -> Self {
+        match serde_json::from_str::<EnemyArchetypeSet>(ARCHETYPES_JSON) {
+            Ok(set) => Self {
+                archetypes: set.archetypes,
+                handle: Handle::default(),
+            },
+            Err(e) => {
+                error!("Failed to parse assets/{}: {}", ARCHETYPES_PATH, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&EnemyArchetype> {
+        self.archetypes.get(id)
+    }
+
+    /// Ids of every archetype of one type, sorted so a seeded pick comes out
+    /// the same on every peer
+    pub fn ids_of_type(&self, enemy_type: EnemyType) -> Vec<&str> {
+        let mut ids: Vec<&str> = self
+            .archetypes
+            .iter()
+            .filter(|(_, archetype)| archetype.enemy_type == enemy_type)
+            .map(|(id, _)| id.as_str())
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+pub struct ArchetypePlugin;
+
+impl Plugin for ArchetypePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<EnemyArchetypeSet>()
+            .init_asset_loader::<EnemyArchetypeLoader>()
+            .insert_resource(EnemyArchetypes::load())
+            .add_systems(Startup, watch_archetypes)
+            .add_systems(Update, reload_archetypes);
+    }
+}
+
+fn watch_archetypes(
+    asset_server: Res<AssetServer>,
+    mut archetypes: ResMut<EnemyArchetypes>,
+) {
+    archetypes.handle = asset_server.load(ARCHETYPES_PATH);
+}
+
+/// Swap in the file's archetypes whenever it finishes loading or changes
+/// on disk. A file that fails to parse leaves the last good set in place.
+fn reload_archetypes(
+    mut events: EventReader<AssetEvent<EnemyArchetypeSet>>,
+    sets: Res<Assets<EnemyArchetypeSet>>,
+    mut archetypes: ResMut<EnemyArchetypes>,
+) {
+    for event in events.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = event else {
+            continue;
+        };
+        if *id != archetypes.handle.id() {
+            continue;
+        }
+        if let Some(set) = sets.get(*id) {
+            archetypes.archetypes = set.archetypes.clone();
+            info!("Loaded {} enemy archetypes", archetypes.archetypes.len());
+        }
+    }
+}
@@ -0,0 +1,716 @@
+use bevy::prelude::*;
+use rand::Rng;
+use crate::components::*;
+use crate::resources::*;
+use crate::combat::Dead;
+use crate::damage::{DamageDealt, DamageInstance, DamageTag, DealDamage};
+use crate::flow::spawn_screen;
+use crate::movement::MovementSet;
+use crate::netcode::has_authority;
+use crate::network::GameMessage;
+use crate::peer_sync::{receive_from_peers, send_to_host, sync_local, PeerReported};
+use crate::room::PLAYER_BASE_HEALTH;
+
+// ============= Boons =============
+//
+// Clearing a room with a Boon reward inserts a `BoonSelectionState` for that
+// rarity and enters `BoonSelection`, where three choices are rolled from the
+// pool below. Picked boons live in `PlayerInventory` and are mirrored onto the
+// local player's `PlayerBoons`, which clients send to the host. Every player's
+// stat boons become their `StatModifier`s, and the host runs every player's
+// behaviour boons off `DamageDealt`.
+
+const BOON_CHOICES: usize = 3;
+const CHAIN_LIGHTNING_RANGE: f32 = 200.0;
+const CHAIN_LIGHTNING_DAMAGE: f32 = 0.5; // Fraction of the hit that procced it
+
+pub struct BoonPlugin;
+
+impl Plugin for BoonPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BoonSelectionState>()
+            .add_systems(OnEnter(CurrentGameState::BoonSelection), (roll_boon_selection, spawn_boon_screen).chain())
+            .add_systems(Update, (
+                handle_boon_input,
+                refresh_boon_screen.run_if(resource_changed::<BoonSelectionState>),
+            ).chain().run_if(in_state(CurrentGameState::BoonSelection)))
+            .add_systems(Update, (
+                sync_local::<PlayerBoons>,
+                send_to_host::<PlayerBoons>.run_if(not(has_authority)),
+                receive_from_peers::<PlayerBoons>.run_if(has_authority),
+                sync_boon_stats,
+            ).chain())
+            .add_systems(FixedUpdate, apply_boon_procs
+                .after(MovementSet)
+                .run_if(in_state(PauseState::Running))
+                .run_if(has_authority));
+    }
+}
+
+// ============= Pool =============
+
+struct BoonTemplate {
+    name: &'static str,
+    boon_type: BoonType, // Common-rarity values; scaled by the rarity's power multiplier
+    unique: bool,        // Never stacks
+}
+
+// DoubleJump has nothing to do in a top-down game, so it isn't offered
+const BOON_POOL: [BoonTemplate; 8] = [
+    BoonTemplate { name: "Sharpened Edge", boon_type: BoonType::DamageBoost(0.15), unique: false },
+    BoonTemplate { name: "Fleet Foot", boon_type: BoonType::SpeedBoost(0.10), unique: false },
+    BoonTemplate { name: "Keen Eye", boon_type: BoonType::CritBoost(0.05), unique: false },
+    BoonTemplate { name: "Vitality", boon_type: BoonType::HealthBoost(0.15), unique: false },
+    BoonTemplate { name: "Bloodthirst", boon_type: BoonType::Lifesteal(0.05), unique: false },
+    BoonTemplate { name: "Storm Caller", boon_type: BoonType::ChainLightning(2), unique: false },
+    BoonTemplate { name: "Thornmail", boon_type: BoonType::Thorns(0.3), unique: false },
+    BoonTemplate { name: "Second Wind", boon_type: BoonType::DashReset, unique: true },
+];
+
+// A run has fewer boon picks than this, so no honest stack is ever larger
+const MAX_CLAIMED_STACKS: u32 = 64;
+
+// Pairs shown as synergies when one half is already owned
+const BOON_SYNERGIES: [(&str, &str); 4] = [
+    ("Storm Caller", "Keen Eye"),
+    ("Bloodthirst", "Sharpened Edge"),
+    ("Second Wind", "Fleet Foot"),
+    ("Thornmail", "Vitality"),
+];
+
+#[derive(Debug, Clone, Copy)]
+pub struct RarityTuning {
+    pub power: f32,
+    pub proc_chance: f32,
+    pub max_stacks: u32,
+}
+
+pub fn rarity_tuning(rarity: BoonRarity) -> RarityTuning {
+    let tuning = |power, proc_chance, max_stacks| RarityTuning {
+        power,
+        proc_chance,
+        max_stacks,
+    };
+
+    // From the Boon Rarity Multipliers table in Tuning Tables
+    match rarity {
+        BoonRarity::Common => tuning(1.0, 0.10, 3),
+        BoonRarity::Rare => tuning(1.5, 0.15, 5),
+        BoonRarity::Epic => tuning(2.0, 0.20, 7),
+        BoonRarity::Legendary => tuning(3.0, 0.30, 10),
+        BoonRarity::Mythic => tuning(5.0, 0.50, u32::MAX),
+    }
+}
+
+/// Weights for Common..Mythic. The reward's rarity picks the row of the Drop
+/// Rates by Source table: common rooms roll like common enemies, and so on.
+fn rarity_weights(reward: BoonRarity) -> [u32; 5] {
+    match reward {
+        BoonRarity::Common => [70, 25, 5, 0, 0],
+        BoonRarity::Rare => [40, 40, 18, 2, 0],
+        BoonRarity::Epic => [20, 40, 35, 5, 0],
+        BoonRarity::Legendary | BoonRarity::Mythic => [0, 30, 50, 19, 1],
+    }
+}
+
+fn roll_rarity(reward: BoonRarity, rng: &mut impl Rng) -> BoonRarity {
+    const RARITIES: [BoonRarity; 5] = [
+        BoonRarity::Common,
+        BoonRarity::Rare,
+        BoonRarity::Epic,
+        BoonRarity::Legendary,
+        BoonRarity::Mythic,
+    ];
+
+    let weights = rarity_weights(reward);
+    let mut roll = rng.gen_range(0..weights.iter().sum::<u32>());
+    for (rarity, weight) in RARITIES.iter().zip(weights) {
+        if roll < weight {
+            return *rarity;
+        }
+        roll -= weight;
+    }
+    BoonRarity::Common
+}
+
+/// Reroll costs from Tuning Tables: 25g, 50g, 75g, 100g, then 150g
+pub fn reroll_cost(rerolls_used: u32) -> u32 {
+    match rerolls_used {
+        0..=3 => 25 * (rerolls_used + 1),
+        _ => 150,
+    }
+}
+
+fn scale_boon_type(boon_type: &BoonType, power: f32) -> BoonType {
+    match *boon_type {
+        BoonType::DamageBoost(value) => BoonType::DamageBoost(value * power),
+        BoonType::SpeedBoost(value) => BoonType::SpeedBoost(value * power),
+        BoonType::CritBoost(value) => BoonType::CritBoost(value * power),
+        BoonType::HealthBoost(value) => BoonType::HealthBoost(value * power),
+        BoonType::Lifesteal(value) => BoonType::Lifesteal(value * power),
+        BoonType::ChainLightning(targets) => BoonType::ChainLightning((targets as f32 * power).round() as u32),
+        BoonType::Thorns(value) => BoonType::Thorns(value * power),
+        BoonType::DashReset => BoonType::DashReset,
+        BoonType::DoubleJump => BoonType::DoubleJump,
+    }
+}
+
+/// Fold another stack's values into an owned boon
+fn combine_boon_types(owned: &BoonType, added: &BoonType) -> BoonType {
+    match (owned, added) {
+        (BoonType::DamageBoost(a), BoonType::DamageBoost(b)) => BoonType::DamageBoost(a + b),
+        (BoonType::SpeedBoost(a), BoonType::SpeedBoost(b)) => BoonType::SpeedBoost(a + b),
+        (BoonType::CritBoost(a), BoonType::CritBoost(b)) => BoonType::CritBoost(a + b),
+        (BoonType::HealthBoost(a), BoonType::HealthBoost(b)) => BoonType::HealthBoost(a + b),
+        (BoonType::Lifesteal(a), BoonType::Lifesteal(b)) => BoonType::Lifesteal(a + b),
+        (BoonType::ChainLightning(a), BoonType::ChainLightning(b)) => BoonType::ChainLightning(a + b),
+        (BoonType::Thorns(a), BoonType::Thorns(b)) => BoonType::Thorns(a + b),
+        _ => owned.clone(),
+    }
+}
+
+pub fn describe_boon_type(boon_type: &BoonType) -> String {
+    match boon_type {
+        BoonType::DamageBoost(value) => format!("+{:.0}% damage", value * 100.0),
+        BoonType::SpeedBoost(value) => format!("+{:.0}% move speed", value * 100.0),
+        BoonType::CritBoost(value) => format!("+{:.0}% crit chance", value * 100.0),
+        BoonType::HealthBoost(value) => format!("+{:.0}% max health", value * 100.0),
+        BoonType::Lifesteal(value) => format!("Heal {:.0}% of damage dealt", value * 100.0),
+        BoonType::ChainLightning(targets) => format!("Hits may arc lightning to {} nearby enemies", targets),
+        BoonType::Thorns(value) => format!("Reflect {:.0}% of damage taken", value * 100.0),
+        BoonType::DashReset => "Kills reset your dash cooldown".to_string(),
+        BoonType::DoubleJump => "Jump again in mid-air".to_string(),
+    }
+}
+
+/// Proc-driven boons take active slots; plain stat boons are passive
+pub fn is_active_boon(boon_type: &BoonType) -> bool {
+    matches!(
+        boon_type,
+        BoonType::Lifesteal(_) | BoonType::ChainLightning(_) | BoonType::Thorns(_) | BoonType::DashReset
+    )
+}
+
+fn owned_boons(inventory: &PlayerInventory) -> impl Iterator<Item = &Boon> {
+    inventory.active_boons.iter().chain(inventory.passive_boons.iter())
+}
+
+fn find_owned<'a>(inventory: &'a PlayerInventory, name: &str) -> Option<&'a Boon> {
+    owned_boons(inventory).find(|boon| boon.name == name)
+}
+
+/// Whether picking this template now would do anything: not stacked out, and
+/// a free slot if it's new
+fn can_take(inventory: &PlayerInventory, template: &BoonTemplate) -> bool {
+    if let Some(owned) = find_owned(inventory, template.name) {
+        return owned.stacks < owned.max_stacks;
+    }
+    if is_active_boon(&template.boon_type) {
+        inventory.active_boons.len() < inventory.max_active_boons
+    } else {
+        inventory.passive_boons.len() < inventory.max_passive_boons
+    }
+}
+
+fn make_boon(template: &BoonTemplate, rarity: BoonRarity) -> Boon {
+    let tuning = rarity_tuning(rarity);
+    let boon_type = scale_boon_type(&template.boon_type, tuning.power);
+
+    Boon {
+        name: template.name.to_string(),
+        description: describe_boon_type(&boon_type),
+        rarity,
+        boon_type,
+        stacks: 1,
+        max_stacks: if template.unique { 1 } else { tuning.max_stacks },
+    }
+}
+
+fn preview_boon(boon: &Boon) -> BoonPreview {
+    let mut preview = BoonPreview {
+        damage_change: None,
+        health_change: None,
+        speed_change: None,
+        special_effect: None,
+    };
+
+    match boon.boon_type {
+        BoonType::DamageBoost(value) => preview.damage_change = Some(value),
+        BoonType::HealthBoost(value) => preview.health_change = Some(PLAYER_BASE_HEALTH * value),
+        BoonType::SpeedBoost(value) => preview.speed_change = Some(value),
+        _ => preview.special_effect = Some(boon.description.clone()),
+    }
+    preview
+}
+
+fn boon_synergies(inventory: &PlayerInventory, name: &str) -> Vec<String> {
+    BOON_SYNERGIES
+        .iter()
+        .filter_map(|&(a, b)| {
+            if name == a {
+                Some(b)
+            } else if name == b {
+                Some(a)
+            } else {
+                None
+            }
+        })
+        .filter(|partner| find_owned(inventory, partner).is_some())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Roll up to three different boons the player can still take
+pub fn roll_boon_choices(reward: BoonRarity, inventory: &PlayerInventory, rng: &mut impl Rng) -> Vec<BoonChoice> {
+    let mut candidates: Vec<&BoonTemplate> = BOON_POOL.iter().filter(|template| can_take(inventory, template)).collect();
+    let mut choices = Vec::new();
+
+    while choices.len() < BOON_CHOICES && !candidates.is_empty() {
+        let template = candidates.swap_remove(rng.gen_range(0..candidates.len()));
+        let boon = make_boon(template, roll_rarity(reward, rng));
+
+        choices.push(BoonChoice {
+            synergies: boon_synergies(inventory, template.name),
+            preview_stats: preview_boon(&boon),
+            boon,
+        });
+    }
+    choices
+}
+
+/// Add a boon to the inventory, stacking onto an owned copy. Returns false if
+/// it's stacked out or there's no slot for it.
+pub fn add_boon(inventory: &mut PlayerInventory, boon: Boon) -> bool {
+    let active = is_active_boon(&boon.boon_type);
+    let (slots, max_slots) = if active {
+        (&mut inventory.active_boons, inventory.max_active_boons)
+    } else {
+        (&mut inventory.passive_boons, inventory.max_passive_boons)
+    };
+
+    if let Some(owned) = slots.iter_mut().find(|owned| owned.name == boon.name) {
+        if owned.stacks >= owned.max_stacks {
+            return false;
+        }
+        owned.stacks += 1;
+        owned.boon_type = combine_boon_types(&owned.boon_type, &boon.boon_type);
+        if boon.rarity > owned.rarity {
+            owned.rarity = boon.rarity;
+            owned.max_stacks = owned.max_stacks.max(boon.max_stacks);
+        }
+        owned.description = describe_boon_type(&owned.boon_type);
+        return true;
+    }
+
+    if slots.len() >= max_slots {
+        return false;
+    }
+    slots.push(boon);
+    true
+}
+
+// ============= Selection =============
+
+#[derive(Component)]
+struct BoonScreen;
+
+fn roll_boon_selection(
+    mut selection: ResMut<BoonSelectionState>,
+    inventory: Res<PlayerInventory>,
+    mut run_rng: ResMut<RunRng>,
+) {
+    if selection.available_boons.is_empty() {
+        selection.available_boons = roll_boon_choices(selection.rarity, &inventory, run_rng.stream(RngStream::Boons));
+    }
+}
+
+fn handle_boon_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut selection: ResMut<BoonSelectionState>,
+    mut inventory: ResMut<PlayerInventory>,
+    mut run_stats: ResMut<RunStats>,
+    mut run_rng: ResMut<RunRng>,
+    mut next_state: ResMut<NextState<CurrentGameState>>,
+) {
+    let picked = [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3]
+        .iter()
+        .position(|key| keyboard.just_pressed(*key));
+
+    if let Some(index) = picked {
+        let Some(choice) = selection.available_boons.get(index) else {
+            return;
+        };
+        if add_boon(&mut inventory, choice.boon.clone()) {
+            run_stats.boons_collected += 1;
+        }
+        *selection = BoonSelectionState::new(selection.rarity);
+        next_state.set(CurrentGameState::InRun);
+    } else if keyboard.just_pressed(KeyCode::KeyR) {
+        if inventory.gold < selection.reroll_cost {
+            return;
+        }
+        inventory.gold -= selection.reroll_cost;
+        selection.rerolls_used += 1;
+        selection.reroll_cost = reroll_cost(selection.rerolls_used);
+        selection.available_boons = roll_boon_choices(selection.rarity, &inventory, run_rng.stream(RngStream::Boons));
+    } else if keyboard.just_pressed(KeyCode::Enter) {
+        // Skip the reward
+        *selection = BoonSelectionState::new(selection.rarity);
+        next_state.set(CurrentGameState::InRun);
+    }
+}
+
+fn spawn_boon_screen(
+    mut commands: Commands,
+    selection: Res<BoonSelectionState>,
+    inventory: Res<PlayerInventory>,
+) {
+    let mut lines = Vec::new();
+
+    if selection.available_boons.is_empty() {
+        lines.push("Nothing left to offer".to_string());
+    }
+    for (i, choice) in selection.available_boons.iter().enumerate() {
+        let boon = &choice.boon;
+        let stacks = match find_owned(&inventory, &boon.name) {
+            Some(owned) => format!(" ({}/{})", owned.stacks + 1, owned.max_stacks),
+            None => String::new(),
+        };
+        lines.push(format!("[{}] {} - {:?}{}", i + 1, boon.name, boon.rarity, stacks));
+        lines.push(boon.description.clone());
+        if !choice.synergies.is_empty() {
+            lines.push(format!("Synergy: {}", choice.synergies.join(", ")));
+        }
+        lines.push(String::new());
+    }
+
+    lines.push(format!("[R] Reroll for {}g (you have {}g)", selection.reroll_cost, inventory.gold));
+    lines.push("[Enter] Skip".to_string());
+
+    let screen = spawn_screen(&mut commands, CurrentGameState::BoonSelection, "Choose a Boon", lines);
+    commands.entity(screen).insert(BoonScreen);
+}
+
+fn refresh_boon_screen(
+    mut commands: Commands,
+    screens: Query<Entity, With<BoonScreen>>,
+    selection: Res<BoonSelectionState>,
+    inventory: Res<PlayerInventory>,
+) {
+    // Picking resets the selection on the way out; nothing to redraw then
+    if screens.is_empty() || selection.available_boons.is_empty() {
+        return;
+    }
+    for screen in screens.iter() {
+        commands.entity(screen).despawn_recursive();
+    }
+    spawn_boon_screen(commands, selection, inventory);
+}
+
+// ============= Effects =============
+
+impl PeerReported for PlayerBoons {
+    type Claim = BoonClaim;
+
+    const LABEL: &'static str = "boons";
+
+    fn from_inventory(inventory: &PlayerInventory) -> Self {
+        PlayerBoons(owned_boons(inventory).cloned().collect())
+    }
+
+    fn claims(&self) -> Vec<BoonClaim> {
+        self.0
+            .iter()
+            .map(|boon| BoonClaim {
+                name: boon.name.clone(),
+                rarity: boon.rarity,
+                stacks: boon.stacks,
+            })
+            .collect()
+    }
+
+    fn message(player_id: String, boons: Vec<BoonClaim>) -> GameMessage {
+        GameMessage::PlayerBoons { player_id, boons }
+    }
+
+    fn read_message(message: &GameMessage) -> Option<(&String, &[BoonClaim])> {
+        match message {
+            GameMessage::PlayerBoons { player_id, boons } => Some((player_id, boons)),
+            _ => None,
+        }
+    }
+
+    /// Build the boons from the pool, as `add_boon` would have given them.
+    /// Unknown names are dropped; stacks and slots are clamped to what the
+    /// pool and a fresh inventory allow.
+    fn rebuild(claims: &[BoonClaim]) -> Self {
+        let mut inventory = PlayerInventory::default();
+        for claim in claims {
+            let Some(template) = BOON_POOL.iter().find(|template| template.name == claim.name) else {
+                continue;
+            };
+            for _ in 0..claim.stacks.min(MAX_CLAIMED_STACKS) {
+                if !add_boon(&mut inventory, make_boon(template, claim.rarity)) {
+                    break;
+                }
+            }
+        }
+        Self::from_inventory(&inventory)
+    }
+}
+
+/// Swap each player's boon modifiers for ones matching their boons
+fn sync_boon_stats(mut player_query: Query<(&PlayerBoons, &mut StatModifiers), Changed<PlayerBoons>>) {
+    for (boons, mut modifiers) in player_query.iter_mut() {
+        modifiers.retain(|modifier| !matches!(modifier.source, ModifierSource::Boon(_)));
+
+        for boon in boons.0.iter() {
+            let source = ModifierSource::Boon(boon.name.clone());
+            let modifier = match boon.boon_type {
+                BoonType::DamageBoost(value) => StatModifier::add_percent(StatKind::Damage, value, source),
+                BoonType::SpeedBoost(value) => StatModifier::add_percent(StatKind::MoveSpeed, value, source),
+                BoonType::CritBoost(value) => StatModifier::flat(StatKind::CritChance, value, source),
+                BoonType::HealthBoost(value) => StatModifier::add_percent(StatKind::MaxHealth, value, source),
+                _ => continue,
+            };
+            modifiers.add(modifier);
+        }
+    }
+}
+
+type EnemyTargetQuery<'w, 's> = Query<'w, 's, (Entity, &'static Transform), (With<Enemy>, Without<Dead>)>;
+
+/// Run the behaviour boons of whichever players dealt or took each hit
+fn apply_boon_procs(
+    mut dealt: EventReader<DamageDealt>,
+    mut damage_events: EventWriter<DealDamage>,
+    mut player_query: Query<(&PlayerBoons, &mut Health, &mut DashState)>,
+    enemy_query: EnemyTargetQuery,
+    mut run_rng: ResMut<RunRng>,
+) {
+    for event in dealt.read() {
+        if let Ok((boons, _, _)) = player_query.get(event.target) {
+            for boon in boons.0.iter() {
+                let BoonType::Thorns(fraction) = boon.boon_type else {
+                    continue;
+                };
+                if event.instance.has_tag(DamageTag::Reflected) {
+                    continue;
+                }
+                let Some(attacker) = event.instance.source else {
+                    continue;
+                };
+                damage_events.send(DealDamage {
+                    target: attacker,
+                    instance: DamageInstance::new((event.instance.amount + event.absorbed) * fraction, DamageType::Physical, Some(event.target))
+                        .with_tag(DamageTag::Reflected)
+                        .with_tag(DamageTag::NoCrit),
+                });
+            }
+        }
+
+        let Some(player) = event.instance.source else {
+            continue;
+        };
+        let Ok((boons, mut health, mut dash_state)) = player_query.get_mut(player) else {
+            continue;
+        };
+
+        for boon in boons.0.iter() {
+            match boon.boon_type {
+                BoonType::Lifesteal(fraction) => {
+                    health.heal(event.instance.amount * fraction);
+                }
+                BoonType::DashReset if event.killed => {
+                    let cooldown = dash_state.cooldown_timer.duration();
+                    dash_state.cooldown_timer.set_elapsed(cooldown);
+                }
+                BoonType::ChainLightning(targets) if !event.instance.has_tag(DamageTag::Chain) => {
+                    let proc_chance = rarity_tuning(boon.rarity).proc_chance;
+                    if run_rng.stream(RngStream::Combat).gen::<f32>() >= proc_chance {
+                        continue;
+                    }
+                    let Ok((_, origin)) = enemy_query.get(event.target) else {
+                        continue;
+                    };
+                    let origin = origin.translation.truncate();
+
+                    let mut nearby: Vec<(Entity, f32)> = enemy_query
+                        .iter()
+                        .filter(|(entity, _)| *entity != event.target)
+                        .map(|(entity, transform)| (entity, transform.translation.truncate().distance(origin)))
+                        .filter(|(_, distance)| *distance < CHAIN_LIGHTNING_RANGE)
+                        .collect();
+                    nearby.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+                    for (target, _) in nearby.into_iter().take(targets as usize) {
+                        damage_events.send(DealDamage {
+                            target,
+                            instance: DamageInstance::new(event.instance.amount * CHAIN_LIGHTNING_DAMAGE, DamageType::Lightning, Some(player))
+                                .with_tag(DamageTag::Chain)
+                                .with_tag(DamageTag::NoCrit),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn assert_close(actual: f32, expected: f32, case: &str) {
+        assert!((actual - expected).abs() < 1e-4, "{}: got {}, expected {}", case, actual, expected);
+    }
+
+    fn template(name: &str) -> &'static BoonTemplate {
+        BOON_POOL.iter().find(|template| template.name == name).unwrap()
+    }
+
+    fn claim(name: &str, rarity: BoonRarity, stacks: u32) -> BoonClaim {
+        BoonClaim { name: name.to_string(), rarity, stacks }
+    }
+
+    #[test]
+    fn reroll_costs_follow_the_tuning_table() {
+        let table = [(0, 25), (1, 50), (2, 75), (3, 100), (4, 150), (5, 150), (20, 150)];
+        for (rerolls_used, cost) in table {
+            assert_eq!(reroll_cost(rerolls_used), cost, "after {} rerolls", rerolls_used);
+        }
+    }
+
+    #[test]
+    fn stacks_stop_at_the_rarity_limit() {
+        // (rarity, picks, stacks kept, total damage bonus)
+        let table = [
+            (BoonRarity::Common, 1, 1, 0.15),
+            (BoonRarity::Common, 5, 3, 0.45),
+            (BoonRarity::Rare, 8, 5, 5.0 * 0.225),
+            (BoonRarity::Epic, 8, 7, 7.0 * 0.30),
+            (BoonRarity::Legendary, 12, 10, 10.0 * 0.45),
+            (BoonRarity::Mythic, 12, 12, 12.0 * 0.75),
+        ];
+
+        for (rarity, picks, stacks, bonus) in table {
+            let mut inventory = PlayerInventory::default();
+            let taken = (0..picks)
+                .filter(|_| add_boon(&mut inventory, make_boon(template("Sharpened Edge"), rarity)))
+                .count();
+            let owned = find_owned(&inventory, "Sharpened Edge").unwrap();
+            assert_eq!((taken, owned.stacks), (stacks as usize, stacks), "{:?} x{}", rarity, picks);
+            let BoonType::DamageBoost(value) = owned.boon_type else {
+                panic!("{:?} x{}: {:?}", rarity, picks, owned.boon_type);
+            };
+            assert_close(value, bonus, &format!("{:?} x{}", rarity, picks));
+        }
+    }
+
+    #[test]
+    fn stacking_a_rarer_copy_raises_the_limit_and_unique_boons_never_stack() {
+        let mut inventory = PlayerInventory::default();
+        for _ in 0..2 {
+            assert!(add_boon(&mut inventory, make_boon(template("Keen Eye"), BoonRarity::Common)));
+        }
+        assert!(add_boon(&mut inventory, make_boon(template("Keen Eye"), BoonRarity::Rare)));
+        let owned = find_owned(&inventory, "Keen Eye").unwrap();
+        assert_eq!((owned.rarity, owned.stacks, owned.max_stacks), (BoonRarity::Rare, 3, 5));
+        for _ in 0..2 {
+            assert!(add_boon(&mut inventory, make_boon(template("Keen Eye"), BoonRarity::Common)));
+        }
+        assert!(!add_boon(&mut inventory, make_boon(template("Keen Eye"), BoonRarity::Common)));
+
+        assert!(add_boon(&mut inventory, make_boon(template("Second Wind"), BoonRarity::Mythic)));
+        assert!(!add_boon(&mut inventory, make_boon(template("Second Wind"), BoonRarity::Mythic)));
+    }
+
+    #[test]
+    fn new_boons_need_a_free_slot_of_their_kind() {
+        let mut inventory = PlayerInventory::default();
+        for name in ["Bloodthirst", "Storm Caller", "Thornmail"] {
+            assert!(add_boon(&mut inventory, make_boon(template(name), BoonRarity::Common)), "{}", name);
+        }
+        // Active slots are full, passive ones aren't
+        assert!(!add_boon(&mut inventory, make_boon(template("Second Wind"), BoonRarity::Common)));
+        assert!(add_boon(&mut inventory, make_boon(template("Vitality"), BoonRarity::Common)));
+        assert_eq!((inventory.active_boons.len(), inventory.passive_boons.len()), (3, 1));
+    }
+
+    #[test]
+    fn rarity_rolls_follow_the_drop_rate_weights() {
+        const ROLLS: u32 = 20_000;
+        const RARITIES: [BoonRarity; 5] =
+            [BoonRarity::Common, BoonRarity::Rare, BoonRarity::Epic, BoonRarity::Legendary, BoonRarity::Mythic];
+
+        for reward in [BoonRarity::Common, BoonRarity::Rare, BoonRarity::Epic, BoonRarity::Legendary] {
+            let mut rng = StdRng::seed_from_u64(11);
+            let mut counts = [0u32; 5];
+            for _ in 0..ROLLS {
+                let rarity = roll_rarity(reward, &mut rng);
+                counts[RARITIES.iter().position(|r| *r == rarity).unwrap()] += 1;
+            }
+
+            for ((rarity, count), weight) in RARITIES.iter().zip(counts).zip(rarity_weights(reward)) {
+                let share = count as f32 / ROLLS as f32 * 100.0;
+                if weight == 0 {
+                    assert_eq!(count, 0, "{:?} reward rolled {:?}", reward, rarity);
+                } else {
+                    assert!((share - weight as f32).abs() < 1.5, "{:?} reward: {:?} at {}%, weight {}", reward, rarity, share, weight);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn choices_are_distinct_and_takeable() {
+        let mut inventory = PlayerInventory::default();
+        add_boon(&mut inventory, make_boon(template("Second Wind"), BoonRarity::Common));
+        for _ in 0..3 {
+            add_boon(&mut inventory, make_boon(template("Fleet Foot"), BoonRarity::Common));
+        }
+
+        for seed in 0..50 {
+            let choices = roll_boon_choices(BoonRarity::Epic, &inventory, &mut StdRng::seed_from_u64(seed));
+            let mut names: Vec<&str> = choices.iter().map(|choice| choice.boon.name.as_str()).collect();
+            assert_eq!(names.len(), BOON_CHOICES, "seed {}", seed);
+            assert!(!names.contains(&"Second Wind") && !names.contains(&"Fleet Foot"), "seed {}: {:?}", seed, names);
+            names.sort();
+            names.dedup();
+            assert_eq!(names.len(), BOON_CHOICES, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn host_rebuilds_claimed_boons_within_the_limits() {
+        // (claim, stacks kept)
+        let table = [
+            (claim("Sharpened Edge", BoonRarity::Common, 2), Some(2)),
+            (claim("Sharpened Edge", BoonRarity::Common, 50), Some(3)),
+            (claim("Sharpened Edge", BoonRarity::Mythic, 50), Some(50)),
+            (claim("Sharpened Edge", BoonRarity::Mythic, u32::MAX), Some(MAX_CLAIMED_STACKS)),
+            (claim("Second Wind", BoonRarity::Mythic, 9), Some(1)),
+            (claim("Sharpened Edge", BoonRarity::Common, 0), None),
+            (claim("Infinite Damage", BoonRarity::Mythic, 1), None),
+        ];
+
+        for (claim, stacks) in table {
+            let rebuilt = PlayerBoons::rebuild(std::slice::from_ref(&claim));
+            assert_eq!(rebuilt.0.first().map(|boon| boon.stacks), stacks, "{:?}", claim);
+        }
+
+        // Values come from the pool, not the client
+        let rebuilt = PlayerBoons::rebuild(&[claim("Sharpened Edge", BoonRarity::Mythic, u32::MAX)]);
+        let BoonType::DamageBoost(value) = rebuilt.0[0].boon_type else {
+            panic!("{:?}", rebuilt.0[0].boon_type);
+        };
+        assert_close(value, MAX_CLAIMED_STACKS as f32 * 0.75, "clamped mythic stacks");
+
+        // One slot's worth of claims over the limit is dropped
+        let actives = ["Bloodthirst", "Storm Caller", "Thornmail", "Second Wind"].map(|name| claim(name, BoonRarity::Common, 1));
+        assert_eq!(PlayerBoons::rebuild(&actives).0.len(), 3);
+    }
+}
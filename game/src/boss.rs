@@ -0,0 +1,259 @@
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+use crate::components::*;
+use crate::resources::*;
+use crate::archetypes::{EnemyArchetype, EnemyArchetypes};
+use crate::combat::Dead;
+use crate::damage::resolve_damage;
+use crate::enemy::spawn_enemy;
+use crate::movement::{ARENA_HALF_HEIGHT, ARENA_HALF_WIDTH};
+use crate::netcode::has_authority;
+
+// ============= Bosses =============
+//
+// Bosses and minibosses are archetypes with phases. A phase starts once the
+// boss drops to its health threshold: the boss goes invulnerable for the
+// phase's transition, switches to the phase's attack pattern and calls in
+// its adds. Damage can't carry a boss past a phase it hasn't played, so big
+// hits don't skip them. Phases can require an ascension level, which is how
+// ascension 4 gets its extra boss phase.
+
+const ADD_SPAWN_RADIUS: f32 = 100.0;
+const ADD_WALL_MARGIN: f32 = 40.0;
+const TRANSITION_ALPHA: f32 = 0.5;
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, advance_boss_phases
+            .after(resolve_damage)
+            .run_if(in_state(PauseState::Running))
+            .run_if(has_authority))
+            // Every peer shows the bar, from replicated health and phase
+            .add_systems(Update, (
+                spawn_boss_health_bars,
+                update_boss_health_bars,
+            ).chain());
+    }
+}
+
+/// The archetype's phases that apply at `ascension`, in file order
+fn make_boss(archetype: &EnemyArchetype, ascension: u8) -> Boss {
+    let phases = archetype
+        .phases
+        .iter()
+        .enumerate()
+        .filter(|(_, phase)| phase.min_ascension <= ascension)
+        .map(|(index, phase)| (index, phase.health_threshold))
+        .collect();
+
+    Boss {
+        phases,
+        current: 0,
+        transition: FrameTimer::new(0),
+    }
+}
+
+/// Spawn a boss or miniboss archetype with the phases `ascension` gives it
+pub fn spawn_boss(
+    commands: &mut Commands,
+    archetypes: &EnemyArchetypes,
+    archetype_id: &str,
+    position: Vec2,
+    room_number: u32,
+    ascension: u8,
+) -> Option<Entity> {
+    let entity = spawn_enemy(commands, archetypes, archetype_id, position, room_number, None)?;
+    let archetype = archetypes.get(archetype_id)?;
+    commands.entity(entity).insert(make_boss(archetype, ascension));
+    Some(entity)
+}
+
+type BossPhaseQuery<'w, 's> = Query<'w, 's, (
+    &'static mut Boss,
+    &'static mut EnemyAI,
+    &'static mut Hurtbox,
+    &'static mut Sprite,
+    &'static Health,
+    &'static Enemy,
+    &'static Transform,
+    Option<&'static Parent>,
+), Without<Dead>>;
+
+fn advance_boss_phases(
+    mut commands: Commands,
+    mut boss_query: BossPhaseQuery,
+    archetypes: Res<EnemyArchetypes>,
+    game_state: Res<GameState>,
+) {
+    for (mut boss, mut ai, mut hurtbox, mut sprite, health, enemy, transform, parent) in boss_query.iter_mut() {
+        if boss.is_transitioning() {
+            boss.transition.tick();
+            if !boss.is_transitioning() {
+                hurtbox.invulnerable = false;
+                sprite.color = sprite.color.with_alpha(1.0);
+            }
+            continue;
+        }
+
+        // Also picks up the first phase on spawn
+        if ai.phase != boss.phase() {
+            ai.phase = boss.phase();
+            ai.attack_index = 0;
+        }
+
+        let Some(threshold) = boss.next_threshold() else {
+            continue;
+        };
+        if health.percentage() > threshold {
+            continue;
+        }
+
+        boss.current += 1;
+        let Some(archetype) = archetypes.get(&enemy.archetype) else {
+            continue;
+        };
+        let Some(phase) = archetype.phases.get(boss.phase()) else {
+            continue;
+        };
+        info!("{} enters phase: {}", archetype.name, phase.name);
+
+        // Start the new pattern from the top once the transition is over
+        ai.phase = boss.phase();
+        ai.attack_index = 0;
+        ai.state = AIState::Recovering;
+        ai.state_timer = FrameTimer::from_seconds(phase.transition);
+        boss.transition = FrameTimer::from_seconds(phase.transition);
+        if boss.is_transitioning() {
+            hurtbox.invulnerable = true;
+            sprite.color = sprite.color.with_alpha(TRANSITION_ALPHA);
+        }
+
+        // Adds ring the boss and join its room, so the room isn't cleared
+        // until they're dead too
+        let center = transform.translation.truncate();
+        let limit = Vec2::new(ARENA_HALF_WIDTH, ARENA_HALF_HEIGHT) - Vec2::splat(ADD_WALL_MARGIN);
+        let total: u32 = phase.adds.iter().map(|add| add.count).sum();
+        let mut slot = 0;
+        for add in phase.adds.iter() {
+            for _ in 0..add.count {
+                let angle = slot as f32 / total as f32 * TAU;
+                slot += 1;
+                let position = (center + Vec2::from_angle(angle) * ADD_SPAWN_RADIUS).clamp(-limit, limit);
+                let Some(entity) = spawn_enemy(&mut commands, &archetypes, &add.archetype, position, game_state.room_number, None) else {
+                    continue;
+                };
+                if let Some(parent) = parent {
+                    commands.entity(entity).set_parent(parent.get());
+                }
+            }
+        }
+    }
+}
+
+// ============= Health Bar =============
+
+/// Top-of-screen bar for one boss; goes away with the boss
+#[derive(Component)]
+struct BossHealthBar {
+    boss: Entity,
+    fill: Entity,
+    label: Entity,
+}
+
+fn boss_label(archetype: Option<&EnemyArchetype>, phase: usize) -> String {
+    let Some(archetype) = archetype else {
+        return String::new();
+    };
+    match archetype.phases.get(phase) {
+        Some(phase) => format!("{} - {}", archetype.name, phase.name),
+        None => archetype.name.clone(),
+    }
+}
+
+fn spawn_boss_health_bars(
+    mut commands: Commands,
+    boss_query: Query<(Entity, &Enemy), Added<Boss>>,
+    archetypes: Res<EnemyArchetypes>,
+) {
+    for (boss, enemy) in boss_query.iter() {
+        let label = commands
+            .spawn(TextBundle::from_section(
+                boss_label(archetypes.get(&enemy.archetype), 0),
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ))
+            .id();
+        let fill = commands
+            .spawn(NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                background_color: Color::srgb(0.8, 0.1, 0.1).into(),
+                ..default()
+            })
+            .id();
+        let track = commands
+            .spawn(NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Px(16.0),
+                    ..default()
+                },
+                background_color: Color::srgb(0.15, 0.15, 0.15).into(),
+                ..default()
+            })
+            .add_child(fill)
+            .id();
+
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        top: Val::Px(50.0),
+                        left: Val::Percent(25.0),
+                        width: Val::Percent(50.0),
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                },
+                BossHealthBar { boss, fill, label },
+            ))
+            .push_children(&[label, track]);
+    }
+}
+
+fn update_boss_health_bars(
+    mut commands: Commands,
+    bar_query: Query<(Entity, &BossHealthBar)>,
+    boss_query: Query<(&Enemy, &EnemyAI, &Health), With<Boss>>,
+    mut styles: Query<&mut Style>,
+    mut texts: Query<&mut Text>,
+    archetypes: Res<EnemyArchetypes>,
+) {
+    for (bar, health_bar) in bar_query.iter() {
+        let Ok((enemy, ai, health)) = boss_query.get(health_bar.boss) else {
+            commands.entity(bar).despawn_recursive();
+            continue;
+        };
+
+        if let Ok(mut style) = styles.get_mut(health_bar.fill) {
+            style.width = Val::Percent(health.percentage().clamp(0.0, 1.0) * 100.0);
+        }
+        if let Ok(mut text) = texts.get_mut(health_bar.label) {
+            let label = boss_label(archetypes.get(&enemy.archetype), ai.phase);
+            if text.sections[0].value != label {
+                text.sections[0].value = label;
+            }
+        }
+    }
+}
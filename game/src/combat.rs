@@ -2,47 +2,66 @@ use bevy::prelude::*;
 use rand::Rng;
 use crate::components::*;
 use crate::resources::*;
-use crate::movement::buffer_input;
+use crate::damage::*;
+use crate::archetypes::EnemyArchetypes;
+use crate::enemy::{spawn_elite_death_effects, spawn_enemy_drops};
+use crate::movement::MovementSet;
+use crate::movesets::{Move, MoveSets};
+use crate::projectiles::{check_projectile_hits, move_projectiles, spawn_projectile_spread, SpreadPattern};
+use crate::weapons::{weapon_projectile, MELEE_REFERENCE_RANGE};
+use crate::netcode::has_authority;
+use crate::status::ApplyStatus;
 
 pub struct CombatPlugin;
 
 impl Plugin for CombatPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
-            buffer_input,
-            handle_attack_input,
-            update_attack_state,
-            spawn_hitboxes,
-            check_hit_detection,
-            apply_damage,
-            spawn_damage_numbers,
-            update_damage_numbers,
-            handle_death,
-            apply_knockback,
-        ).chain().run_if(in_combat));
+        app.insert_resource(MoveSets::load())
+            .add_event::<DealDamage>()
+            .add_event::<DamageDealt>()
+            .add_systems(FixedUpdate, (
+                handle_attack_input,
+                update_attack_state,
+                spawn_hitboxes,
+                // Clients predict their own swings but only the host decides what they hit
+                (
+                    move_projectiles,
+                    check_hit_detection,
+                    check_projectile_hits,
+                    resolve_damage,
+                ).chain().run_if(has_authority),
+                handle_death,
+                handle_player_death,
+                apply_knockback,
+                cleanup_hitboxes,
+            ).chain().after(MovementSet).run_if(in_state(PauseState::Running)))
+            // Damage numbers are cosmetic and animate at render rate
+            .add_systems(Update, (
+                spawn_damage_numbers,
+                update_damage_numbers,
+            ).chain().run_if(in_state(PauseState::Running)));
     }
 }
 
-fn in_combat(game_state: Res<GameState>) -> bool {
-    matches!(game_state.current_state, CurrentGameState::InRun | CurrentGameState::BossFight)
-        && !game_state.paused
-}
-
+type AttackInputQuery<'w, 's> = Query<'w, 's, (
+    &'static mut AttackState,
+    &'static mut InputBuffer,
+    &'static CombatStats,
+    &'static DashState,
+    Option<&'static StatusEffects>,
+), (With<Player>, Without<Incapacitated>, Without<Dead>)>;
+
+/// Attacks come from the input buffer, which `simulate_players` fills from
+/// each player's input frames (local or received by the host). Which move a
+/// press starts comes from the player's move-set: an open chain out of the
+/// current move, otherwise the input's starter.
 fn handle_attack_input(
-    keyboard: Res<ButtonInput<KeyCode>>,
-    gamepads: Res<Gamepads>,
-    button_inputs: Res<ButtonInput<GamepadButton>>,
-    time: Res<Time>,
-    mut query: Query<(
-        &mut AttackState,
-        &mut InputBuffer,
-        &CombatStats,
-        &DashState,
-    ), With<LocalPlayer>>,
+    mut query: AttackInputQuery,
+    move_sets: Res<MoveSets>,
 ) {
-    for (mut attack_state, mut input_buffer, stats, dash_state) in query.iter_mut() {
-        // Can't attack while dashing (unless it's a dash attack)
-        if dash_state.is_dashing && attack_state.attack_type != AttackType::DashAttack {
+    for (mut attack_state, mut input_buffer, stats, dash_state, status_effects) in query.iter_mut() {
+        // Can't attack again during a dash attack
+        if dash_state.is_dashing && attack_state.is_attacking {
             continue;
         }
 
@@ -51,196 +70,247 @@ fn handle_attack_input(
             continue;
         }
 
-        let mut new_attack = None;
+        let Some(move_set) = move_sets.get(&attack_state.move_set) else {
+            continue;
+        };
 
-        // Check keyboard input
-        if keyboard.just_pressed(KeyCode::KeyJ) {
-            new_attack = Some(AttackType::LightAttack);
-        } else if keyboard.just_pressed(KeyCode::KeyK) {
-            new_attack = Some(AttackType::HeavyAttack);
-        }
+        // Find the oldest buffered press that leads somewhere. Presses that
+        // don't stay buffered in case their window opens later.
+        let max_buffer_frames = input_buffer.max_buffer_frames;
+        let next = input_buffer.buffer.iter().enumerate().find_map(|(index, buffered)| {
+            if buffered.age >= max_buffer_frames {
+                return None;
+            }
+            let input = match buffered.action {
+                InputAction::LightAttack => AttackType::LightAttack,
+                InputAction::HeavyAttack => AttackType::HeavyAttack,
+                _ => return None,
+            };
 
-        // Check gamepad input
-        for gamepad in gamepads.iter() {
-            if button_inputs.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::West)) {
-                new_attack = Some(AttackType::LightAttack);
-            } else if button_inputs.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::North)) {
-                new_attack = Some(AttackType::HeavyAttack);
+            if dash_state.is_dashing {
+                return move_set.starter(AttackType::DashAttack).map(|next| (index, AttackType::DashAttack, next, false));
             }
-        }
 
-        // Check input buffer
-        let current_time = time.elapsed_seconds();
-        input_buffer.buffer.retain(|buffered| {
-            if new_attack.is_none() && current_time - buffered.timestamp < input_buffer.max_buffer_time {
-                match buffered.action {
-                    InputAction::LightAttack => {
-                        new_attack = Some(AttackType::LightAttack);
-                        false
-                    }
-                    InputAction::HeavyAttack => {
-                        new_attack = Some(AttackType::HeavyAttack);
-                        false
-                    }
-                    _ => true,
-                }
-            } else {
-                true
+            let frame = attack_state.is_attacking.then_some(attack_state.move_frame);
+            let chained = attack_state
+                .current_move
+                .and_then(|current| move_set.chain(current, input, frame, attack_state.speed_multiplier));
+
+            match chained {
+                Some(next) => Some((index, input, next, true)),
+                // Mid-move, only an open chain can interrupt
+                None if attack_state.is_attacking => None,
+                None => move_set.starter(input).map(|next| (index, input, next, false)),
             }
         });
 
-        if let Some(attack_type) = new_attack {
-            start_attack(&mut attack_state, attack_type, stats);
+        if let Some((index, attack_type, next, chained)) = next {
+            input_buffer.buffer.remove(index);
+            let attack_speed = stats.attack_speed * status_effects.map_or(1.0, StatusEffects::attack_speed_multiplier);
+            start_attack(&mut attack_state, attack_type, next, &move_set.moves[next], chained, attack_speed);
         }
     }
 }
 
-fn start_attack(attack_state: &mut AttackState, attack_type: AttackType, stats: &CombatStats) {
+fn start_attack(
+    attack_state: &mut AttackState,
+    attack_type: AttackType,
+    move_index: usize,
+    next_move: &Move,
+    chained: bool,
+    attack_speed: f32,
+) {
     attack_state.is_attacking = true;
     attack_state.attack_type = attack_type;
+    attack_state.current_move = Some(move_index);
+    attack_state.move_frame = 0;
+    attack_state.can_cancel = false;
 
-    // Set timers based on attack type and attack speed stat
-    // Using frame data from Tuning Tables
-    let speed_multiplier = 1.0 / stats.attack_speed.max(0.1);
-    
-    match attack_type {
-        AttackType::LightAttack => {
-            // Light attack: 8f startup, 4f active, 6f recovery (0.3s total)
-            attack_state.startup_timer = Timer::from_seconds(0.133 * speed_multiplier, TimerMode::Once);
-            attack_state.active_timer = Timer::from_seconds(0.067 * speed_multiplier, TimerMode::Once);
-            attack_state.recovery_timer = Timer::from_seconds(0.1 * speed_multiplier, TimerMode::Once);
-            attack_state.can_cancel = false; // Can cancel after frame 12
-            
-            // Combo system
-            if attack_state.combo_count < 3 {
-                attack_state.combo_count += 1;
-            } else {
-                attack_state.combo_count = 1;
-            }
-        }
-        AttackType::HeavyAttack => {
-            // Heavy attack: 20f startup, 8f active, 20f recovery (0.8s total)
-            attack_state.startup_timer = Timer::from_seconds(0.333 * speed_multiplier, TimerMode::Once);
-            attack_state.active_timer = Timer::from_seconds(0.133 * speed_multiplier, TimerMode::Once);
-            attack_state.recovery_timer = Timer::from_seconds(0.333 * speed_multiplier, TimerMode::Once);
-            attack_state.can_cancel = false; // Can cancel after frame 28
-            attack_state.combo_count = 0; // Heavy attack resets combo
-        }
-        AttackType::DashAttack => {
-            // Dash attack: Quick strike during dash
-            attack_state.startup_timer = Timer::from_seconds(0.05 * speed_multiplier, TimerMode::Once);
-            attack_state.active_timer = Timer::from_seconds(0.1 * speed_multiplier, TimerMode::Once);
-            attack_state.recovery_timer = Timer::from_seconds(0.05 * speed_multiplier, TimerMode::Once);
-            attack_state.can_cancel = true;
-            attack_state.combo_count = 0;
-        }
-        AttackType::Special => {
-            // Special attack: Skill cast timing
-            attack_state.startup_timer = Timer::from_seconds(0.167 * speed_multiplier, TimerMode::Once);
-            attack_state.active_timer = Timer::from_seconds(0.083 * speed_multiplier, TimerMode::Once);
-            attack_state.recovery_timer = Timer::from_seconds(0.25 * speed_multiplier, TimerMode::Once);
-            attack_state.can_cancel = false;
-            attack_state.combo_count = 0;
-        }
-    }
+    // Chained moves extend the string; anything else starts a new one
+    attack_state.combo_count = if chained { attack_state.combo_count + 1 } else { 1 };
+
+    // Frame data comes from the move-set, stretched or squashed by attack speed
+    let speed_multiplier = 1.0 / attack_speed.max(0.1);
+    attack_state.speed_multiplier = speed_multiplier;
+
+    let frames = |base: u32| ((base as f32 * speed_multiplier).round() as u32).max(1);
 
-    // Reset all timers
-    attack_state.startup_timer.reset();
-    attack_state.active_timer.reset();
-    attack_state.recovery_timer.reset();
+    attack_state.startup_timer = FrameTimer::new(frames(next_move.startup));
+    attack_state.active_timer = FrameTimer::new(frames(next_move.active));
+    attack_state.recovery_timer = FrameTimer::new(frames(next_move.recovery));
 }
 
 fn update_attack_state(
-    time: Res<Time>,
-    mut query: Query<&mut AttackState>,
+    mut query: Query<(&mut AttackState, Option<&mut DashState>)>,
+    move_sets: Res<MoveSets>,
 ) {
-    for mut attack_state in query.iter_mut() {
+    for (mut attack_state, dash_state) in query.iter_mut() {
+        let current = attack_state
+            .current_move
+            .and_then(|index| move_sets.get(&attack_state.move_set)?.moves.get(index));
+
         if !attack_state.is_attacking {
+            // Chains stay open for the combo reset window, then the string drops
+            if attack_state.current_move.is_some() {
+                attack_state.combo_timer.tick();
+                if attack_state.combo_timer.finished() {
+                    attack_state.current_move = None;
+                    attack_state.combo_count = 0;
+                }
+            }
+            if let Some(mut dash_state) = dash_state {
+                dash_state.attack_locked = false;
+            }
             continue;
         }
 
+        // A dash that got through the dash-cancel window ends the attack
+        if let Some(dash_state) = dash_state.as_ref() {
+            if dash_state.is_dashing && attack_state.attack_type != AttackType::DashAttack {
+                attack_state.is_attacking = false;
+                attack_state.can_cancel = false;
+                attack_state.current_move = None;
+                attack_state.combo_count = 0;
+                continue;
+            }
+        }
+
         // Progress through attack phases
+        attack_state.move_frame += 1;
         if !attack_state.startup_timer.finished() {
-            attack_state.startup_timer.tick(time.delta());
-            
-            // Check for cancel window (80% of animations can be cancelled)
-            if attack_state.startup_timer.fraction() > 0.8 {
-                attack_state.can_cancel = true;
-            }
+            attack_state.startup_timer.tick();
         } else if !attack_state.active_timer.finished() {
-            attack_state.active_timer.tick(time.delta());
+            attack_state.active_timer.tick();
         } else if !attack_state.recovery_timer.finished() {
-            attack_state.recovery_timer.tick(time.delta());
-            attack_state.can_cancel = true; // Can always cancel during recovery
+            attack_state.recovery_timer.tick();
         } else {
-            // Attack complete
+            // Attack complete; the combo reset window starts now
             attack_state.is_attacking = false;
             attack_state.can_cancel = false;
+            let combo_reset_frames = move_sets.get(&attack_state.move_set).map_or(0, |set| set.combo_reset_frames);
+            attack_state.combo_timer = FrameTimer::new(combo_reset_frames);
+            if let Some(mut dash_state) = dash_state {
+                dash_state.attack_locked = false;
+            }
+            continue;
+        }
+
+        // Cancel windows from the move's data
+        let (frame, speed_multiplier) = (attack_state.move_frame, attack_state.speed_multiplier);
+        let can_cancel = current.is_some_and(|current| {
+            current.cancels.iter().any(|rule| rule.window.contains(frame, speed_multiplier))
+        });
+        let can_dash_cancel = current.is_none_or(|current| {
+            current.dash_cancel.is_some_and(|window| window.contains(frame, speed_multiplier))
+        });
+
+        if attack_state.can_cancel != can_cancel {
+            attack_state.can_cancel = can_cancel;
+        }
+        if let Some(mut dash_state) = dash_state {
+            dash_state.attack_locked = !can_dash_cancel;
         }
     }
 }
 
+type HitboxSourceQuery<'w, 's> = Query<'w, 's, (Entity, &'static AttackState, &'static Transform, &'static CombatStats, &'static Velocity, &'static Team, Option<&'static Weapon>), Changed<AttackState>>;
+
 fn spawn_hitboxes(
     mut commands: Commands,
-    query: Query<(Entity, &AttackState, &Transform, &CombatStats), Changed<AttackState>>,
+    query: HitboxSourceQuery,
+    move_sets: Res<MoveSets>,
 ) {
-    for (entity, attack_state, transform, stats) in query.iter() {
-        if attack_state.is_attacking && attack_state.startup_timer.finished() && !attack_state.active_timer.finished() {
-            // Spawn hitbox based on attack type
-            let (size, offset, damage_mult, knockback) = match attack_state.attack_type {
-                AttackType::LightAttack => {
-                    let combo_mult = 1.0 + (attack_state.combo_count as f32 * 0.2);
-                    (Vec2::new(60.0, 40.0), Vec2::new(40.0, 0.0), combo_mult, 50.0)
-                }
-                AttackType::HeavyAttack => {
-                    (Vec2::new(80.0, 60.0), Vec2::new(50.0, 0.0), 2.0, 150.0)
-                }
-                AttackType::DashAttack => {
-                    (Vec2::new(50.0, 30.0), Vec2::new(30.0, 0.0), 1.5, 100.0)
-                }
-                AttackType::Special => {
-                    (Vec2::new(100.0, 100.0), Vec2::new(0.0, 0.0), 3.0, 200.0)
-                }
+    for (entity, attack_state, transform, stats, velocity, team, weapon) in query.iter() {
+        // One hitbox or projectile per move, on its first active frame
+        if !attack_state.is_attacking || !attack_state.startup_timer.finished() || attack_state.active_timer.elapsed() != 0 {
+            continue;
+        }
+        let Some(current) = attack_state
+            .current_move
+            .and_then(|index| move_sets.get(&attack_state.move_set)?.moves.get(index))
+        else {
+            continue;
+        };
+        let damage = stats.damage * current.damage_multiplier;
+
+        if let Some(weapon) = weapon.filter(|weapon| weapon.attack == WeaponAttack::Projectile) {
+            // Fire the way we're moving, or to the right when standing still
+            let direction = velocity.linear.try_normalize().unwrap_or(Vec2::X);
+            let pattern = SpreadPattern {
+                count: current.projectile.count,
+                arc_degrees: current.projectile.spread,
             };
-
-            commands.spawn((
-                Hitbox {
-                    size,
-                    offset,
-                    damage: stats.damage * damage_mult,
-                    knockback,
-                    active: true,
-                    hit_entities: Vec::new(),
-                },
-                TransformBundle::from_transform(
-                    Transform::from_translation(transform.translation + offset.extend(0.0))
-                ),
-                AttackHitbox { owner: entity },
-            ));
+            let spec = weapon_projectile(weapon, damage, current);
+            spawn_projectile_spread(&mut commands, &spec, pattern, transform.translation, direction, entity, *team);
+            continue;
         }
+
+        // Melee hitboxes reach as far as the weapon does
+        let reach = weapon.map_or(1.0, |weapon| weapon.range / MELEE_REFERENCE_RANGE);
+        let size = Vec2::new(current.hitbox.size.x * reach, current.hitbox.size.y);
+        let offset = Vec2::new(current.hitbox.offset.x * reach, current.hitbox.offset.y);
+
+        commands.spawn((
+            Hitbox {
+                size,
+                offset,
+                damage,
+                damage_type: DamageType::Physical,
+                knockback: current.knockback,
+                status: current.status,
+                active: true,
+                hit_entities: Vec::new(),
+            },
+            TransformBundle::from_transform(
+                Transform::from_translation(transform.translation + offset.extend(0.0))
+            ),
+            AttackHitbox { owner: entity },
+            *team,
+        ));
     }
 }
 
+/// Links a hitbox to the combatant whose attack it is. Environment hitboxes
+/// have no owner.
 #[derive(Component)]
-struct AttackHitbox {
-    owner: Entity,
+pub struct AttackHitbox {
+    pub owner: Entity,
 }
 
+/// Whether an attack from `attacker` may hurt `target`. The environment hurts
+/// everyone; otherwise teams don't hurt themselves, except players when
+/// friendly fire is on.
+pub fn can_hit(attacker: Team, target: Team, settings: &CombatSettings) -> bool {
+    match attacker {
+        Team::Environment => true,
+        Team::Player if target == Team::Player => settings.friendly_fire,
+        _ => attacker != target,
+    }
+}
+
+/// Every hitbox against every hurtbox, whichever side either is on
 fn check_hit_detection(
-    mut hitbox_query: Query<(&mut Hitbox, &Transform, &AttackHitbox)>,
-    hurtbox_query: Query<(Entity, &Hurtbox, &Transform, Option<&Enemy>), Without<AttackHitbox>>,
-    player_query: Query<Entity, With<LocalPlayer>>,
+    mut hitbox_query: Query<(&mut Hitbox, &Transform, &Team, Option<&AttackHitbox>)>,
+    hurtbox_query: Query<(Entity, &Hurtbox, &Transform, &Team), Without<Hitbox>>,
+    settings: Res<CombatSettings>,
+    mut damage_events: EventWriter<DealDamage>,
+    mut status_events: EventWriter<ApplyStatus>,
 ) {
-    for (mut hitbox, hitbox_transform, attack_hitbox) in hitbox_query.iter_mut() {
+    for (mut hitbox, hitbox_transform, hitbox_team, attack_hitbox) in hitbox_query.iter_mut() {
         if !hitbox.active {
             continue;
         }
 
         let hitbox_pos = hitbox_transform.translation.truncate();
+        let owner = attack_hitbox.map(|attack_hitbox| attack_hitbox.owner);
 
-        for (target_entity, hurtbox, hurtbox_transform, maybe_enemy) in hurtbox_query.iter() {
+        for (target_entity, hurtbox, hurtbox_transform, target_team) in hurtbox_query.iter() {
             // Don't hit yourself
-            if target_entity == attack_hitbox.owner {
+            if Some(target_entity) == owner {
+                continue;
+            }
+
+            if !can_hit(*hitbox_team, *target_team, &settings) {
                 continue;
             }
 
@@ -265,12 +335,27 @@ fn check_hit_detection(
             ) {
                 // Register hit
                 hitbox.hit_entities.push(target_entity);
+                if let Some(status) = hitbox.status {
+                    status_events.send(ApplyStatus::on_hit(target_entity, status));
+                }
+                // Status-only hitboxes, like poison pools, do no damage themselves
+                if hitbox.damage <= 0.0 {
+                    continue;
+                }
+                let mut instance = DamageInstance::new(hitbox.damage, hitbox.damage_type, owner);
+                if owner.is_some() {
+                    instance = instance.with_tag(DamageTag::Melee);
+                }
+                damage_events.send(DealDamage {
+                    target: target_entity,
+                    instance,
+                });
             }
         }
     }
 }
 
-fn check_aabb_collision(pos1: Vec2, size1: Vec2, pos2: Vec2, size2: Vec2) -> bool {
+pub fn check_aabb_collision(pos1: Vec2, size1: Vec2, pos2: Vec2, size2: Vec2) -> bool {
     let half_size1 = size1 / 2.0;
     let half_size2 = size2 / 2.0;
 
@@ -280,91 +365,56 @@ fn check_aabb_collision(pos1: Vec2, size1: Vec2, pos2: Vec2, size2: Vec2) -> boo
         && (pos1.y + half_size1.y > pos2.y - half_size2.y)
 }
 
-fn apply_damage(
+#[derive(Component)]
+pub struct Dead;
+
+fn spawn_damage_numbers(
     mut commands: Commands,
-    hitbox_query: Query<(&Hitbox, &AttackHitbox)>,
-    mut target_query: Query<(Entity, &mut Health, &Transform, &CombatStats, Option<&Enemy>)>,
-    attacker_query: Query<&CombatStats>,
-    mut combat_log: ResMut<CombatLog>,
-    mut run_stats: ResMut<RunStats>,
-    time: Res<Time>,
+    mut dealt: EventReader<DamageDealt>,
+    target_query: Query<&Transform>,
 ) {
-    for (hitbox, attack_hitbox) in hitbox_query.iter() {
-        // Safely get attacker stats, skip if attacker entity doesn't exist
-        let Ok(attacker_stats) = attacker_query.get(attack_hitbox.owner) else {
+    // Cosmetic jitter stays off the seeded streams
+    let mut rng = rand::thread_rng();
+
+    for event in dealt.read() {
+        let Ok(transform) = target_query.get(event.target) else {
             continue;
         };
-
-        for &hit_entity in hitbox.hit_entities.iter() {
-            if let Ok((entity, mut health, transform, defender_stats, maybe_enemy)) = target_query.get_mut(hit_entity) {
-                // Calculate damage
-                let mut damage = hitbox.damage;
-
-                // Critical hit calculation
-                let mut rng = rand::thread_rng();
-                let is_crit = rng.gen::<f32>() < attacker_stats.crit_chance;
-                if is_crit {
-                    damage *= attacker_stats.crit_damage;
-                }
-
-                // Apply armor and damage reduction
-                damage = (damage - defender_stats.armor).max(1.0);
-                damage *= 1.0 - defender_stats.damage_reduction;
-
-                // Apply damage
-                let died = health.take_damage(damage);
-
-                // Update stats
-                run_stats.damage_dealt += damage;
-
-                // Log damage event
-                combat_log.recent_damage.push(DamageEvent {
-                    source: "Player".to_string(),
-                    target: if maybe_enemy.is_some() { "Enemy".to_string() } else { "Unknown".to_string() },
-                    amount: damage,
-                    is_crit,
-                    damage_type: DamageType::Physical,
-                    timestamp: time.elapsed_seconds(),
-                });
-
-                // Spawn damage number
-                commands.spawn((
-                    DamageNumber {
-                        amount: damage,
-                        is_crit,
-                        lifetime: Timer::from_seconds(1.0, TimerMode::Once),
-                        velocity: Vec2::new(rng.gen_range(-50.0..50.0), 100.0),
-                    },
-                    Text2dBundle {
-                        text: Text::from_section(
-                            format!("{}", damage as i32),
-                            TextStyle {
-                                font_size: if is_crit { 32.0 } else { 24.0 },
-                                color: if is_crit { Color::YELLOW } else { Color::WHITE },
-                                ..default()
-                            },
-                        ),
-                        transform: Transform::from_translation(transform.translation + Vec3::new(0.0, 20.0, 10.0)),
+        let is_crit = event.instance.is_crit;
+
+        commands.spawn((
+            DamageNumber {
+                amount: event.instance.amount,
+                is_crit,
+                lifetime: Timer::from_seconds(1.0, TimerMode::Once),
+                velocity: Vec2::new(rng.gen_range(-50.0..50.0), 100.0),
+            },
+            Text2dBundle {
+                text: Text::from_section(
+                    format!("{}", event.instance.amount as i32),
+                    TextStyle {
+                        font_size: if is_crit { 32.0 } else { 24.0 },
+                        color: if is_crit { Color::srgb(1.0, 1.0, 0.0) } else { damage_type_color(event.instance.damage_type) },
                         ..default()
                     },
-                ));
-
-                if died {
-                    // Handle death in separate system
-                    commands.entity(entity).insert(Dead);
-                }
-            }
-        }
+                ),
+                transform: Transform::from_translation(transform.translation + Vec3::new(0.0, 20.0, 10.0)),
+                ..default()
+            },
+        ));
     }
 }
 
-#[derive(Component)]
-struct Dead;
-
-fn spawn_damage_numbers(
-    // Implementation handled in apply_damage for now
-) {
-    // This could be expanded to handle different types of damage numbers
+fn damage_type_color(damage_type: DamageType) -> Color {
+    match damage_type {
+        DamageType::Physical => Color::WHITE,
+        DamageType::Fire => Color::srgb(1.0, 0.5, 0.1),
+        DamageType::Ice => Color::srgb(0.5, 0.8, 1.0),
+        DamageType::Lightning => Color::srgb(0.8, 0.7, 1.0),
+        DamageType::Poison => Color::srgb(0.4, 0.9, 0.3),
+        DamageType::Bleed => Color::srgb(0.8, 0.1, 0.1),
+        DamageType::True => Color::srgb(0.9, 0.9, 0.9),
+    }
 }
 
 fn update_damage_numbers(
@@ -385,7 +435,7 @@ fn update_damage_numbers(
         // Fade out
         let alpha = 1.0 - damage_num.lifetime.fraction();
         if let Some(section) = text.sections.first_mut() {
-            section.style.color.set_a(alpha);
+            section.style.color.set_alpha(alpha);
         }
 
         // Remove when expired
@@ -395,41 +445,72 @@ fn update_damage_numbers(
     }
 }
 
+type DeathQuery<'w, 's> = Query<'w, 's, (Entity, &'static Health, &'static Transform, &'static Enemy, Option<&'static Elite>), With<Dead>>;
+
 fn handle_death(
     mut commands: Commands,
-    query: Query<(Entity, &Health, &Transform, Option<&Enemy>, Option<&LocalPlayer>), With<Dead>>,
-    mut game_state: ResMut<GameState>,
+    query: DeathQuery,
     mut run_stats: ResMut<RunStats>,
+    archetypes: Res<EnemyArchetypes>,
+    mut run_rng: ResMut<RunRng>,
 ) {
-    for (entity, health, transform, maybe_enemy, maybe_player) in query.iter() {
-        if let Some(_enemy) = maybe_enemy {
-            // Enemy died
-            run_stats.enemies_killed += 1;
-            
-            // Spawn death effect - simplified for now
-            // In a full implementation, these would spawn actual particle and loot entities
-            
-            // Despawn enemy
-            commands.entity(entity).despawn_recursive();
-        } else if let Some(_player) = maybe_player {
-            // Player died
-            game_state.current_state = CurrentGameState::Death;
-            run_stats.deaths += 1;
-            
-            // Don't despawn player, show death screen instead
+    for (entity, health, transform, enemy, maybe_elite) in query.iter() {
+        run_stats.enemies_killed += 1;
+
+        if let Some(archetype) = archetypes.get(&enemy.archetype) {
+            let reward_multiplier = maybe_elite.map_or(1.0, |elite| elite.reward_multiplier);
+            spawn_enemy_drops(
+                &mut commands,
+                archetype,
+                transform.translation.truncate(),
+                reward_multiplier,
+                run_rng.stream(RngStream::Loot),
+            );
+        }
+        
+        // Elites may go out with a bang
+        if let Some(elite) = maybe_elite {
+            spawn_elite_death_effects(&mut commands, entity, elite, transform.translation, health);
+        }
+        
+        // Despawn enemy
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// How visible a downed player's body stays while their friends play on
+const DOWNED_ALPHA: f32 = 0.3;
+
+type PlayerDeathQuery<'w, 's> = Query<'w, 's, (&'static mut Sprite, &'static mut Velocity, Has<LocalPlayer>), (With<Player>, Added<Dead>)>;
+
+/// Players stay in the world when they die. `Dead` is decided by the host and
+/// replicated, so every peer sees each death exactly once: our own player goes
+/// to the death screen, anyone else is left downed and spectates.
+fn handle_player_death(
+    mut query: PlayerDeathQuery,
+    mut next_state: ResMut<NextState<CurrentGameState>>,
+    mut run_stats: ResMut<RunStats>,
+) {
+    for (mut sprite, mut velocity, is_local) in query.iter_mut() {
+        run_stats.deaths += 1;
+        velocity.linear = Vec2::ZERO;
+        sprite.color.set_alpha(DOWNED_ALPHA);
+
+        if is_local {
+            next_state.set(CurrentGameState::Death);
         }
     }
 }
 
 fn apply_knockback(
-    hitbox_query: Query<(&Hitbox, &Transform, &AttackHitbox)>,
-    mut target_query: Query<(&mut Velocity, &Transform), Without<AttackHitbox>>,
+    hitbox_query: Query<(&Hitbox, &Transform)>,
+    mut target_query: Query<(&mut Velocity, &Transform), Without<Hitbox>>,
 ) {
-    for (hitbox, hitbox_transform, _) in hitbox_query.iter() {
+    for (hitbox, hitbox_transform) in hitbox_query.iter() {
         for &hit_entity in hitbox.hit_entities.iter() {
             if let Ok((mut velocity, target_transform)) = target_query.get_mut(hit_entity) {
                 // Calculate knockback direction
-                let direction = (target_transform.translation.truncate() - hitbox_transform.translation.truncate()).normalize();
+                let direction = (target_transform.translation.truncate() - hitbox_transform.translation.truncate()).normalize_or_zero();
                 
                 // Apply knockback
                 velocity.linear += direction * hitbox.knockback;
@@ -438,15 +519,32 @@ fn apply_knockback(
     }
 }
 
+type HitboxCleanupQuery<'w, 's> = Query<'w, 's, (Entity, Option<&'static AttackHitbox>, Option<&'static mut HitboxLifetime>), With<Hitbox>>;
+
 // Cleanup system for hitboxes
 pub fn cleanup_hitboxes(
     mut commands: Commands,
-    query: Query<(Entity, &AttackHitbox)>,
+    mut query: HitboxCleanupQuery,
     attack_query: Query<&AttackState>,
 ) {
-    for (entity, attack_hitbox) in query.iter() {
+    for (entity, attack_hitbox, lifetime) in query.iter_mut() {
+        // Blasts, enemy swings and the like last a set time
+        if let Some(mut lifetime) = lifetime {
+            lifetime.0.tick();
+            if lifetime.0.finished() {
+                commands.entity(entity).despawn();
+            }
+            continue;
+        }
+
+        // Ownerless hitboxes are part of the room and stay with it
+        let Some(attack_hitbox) = attack_hitbox else {
+            continue;
+        };
+
         if let Ok(attack_state) = attack_query.get(attack_hitbox.owner) {
-            if !attack_state.is_attacking || attack_state.active_timer.finished() {
+            // Gone once the move ends, or the owner has cancelled into the next one
+            if !attack_state.is_attacking || attack_state.active_timer.finished() || !attack_state.startup_timer.finished() {
                 commands.entity(entity).despawn();
             }
         } else {
@@ -454,4 +552,4 @@ pub fn cleanup_hitboxes(
             commands.entity(entity).despawn();
         }
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,8 @@
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use crate::resources::{Curse, DamageType, EliteModifier};
+use crate::movesets::DEFAULT_MOVE_SET;
 
 // ============= Player Components =============
 
@@ -12,6 +15,71 @@ pub struct Player {
 #[derive(Component)]
 pub struct LocalPlayer;
 
+// ============= Simulation Timing =============
+
+/// Gameplay runs in `FixedUpdate` at this rate; frame data in the tuning tables assumes it
+pub const SIMULATION_HZ: f64 = 60.0;
+pub const FRAME_SECONDS: f32 = (1.0 / SIMULATION_HZ) as f32;
+
+pub fn frames_from_seconds(seconds: f32) -> u32 {
+    (seconds * SIMULATION_HZ as f32).round().max(0.0) as u32
+}
+
+/// One-shot timer counted in simulation ticks. Frame data stays exact and
+/// replays come out identical no matter the render frame rate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameTimer {
+    duration: u32,
+    elapsed: u32,
+}
+
+impl FrameTimer {
+    pub fn new(frames: u32) -> Self {
+        Self { duration: frames, elapsed: 0 }
+    }
+
+    pub fn from_seconds(seconds: f32) -> Self {
+        Self::new(frames_from_seconds(seconds))
+    }
+
+    /// Advance by one simulation tick
+    pub fn tick(&mut self) {
+        self.elapsed = (self.elapsed + 1).min(self.duration);
+    }
+
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    pub fn reset(&mut self) {
+        self.elapsed = 0;
+    }
+
+    pub fn duration(&self) -> u32 {
+        self.duration
+    }
+
+    pub fn elapsed(&self) -> u32 {
+        self.elapsed
+    }
+
+    pub fn set_elapsed(&mut self, frames: u32) {
+        self.elapsed = frames.min(self.duration);
+    }
+
+    pub fn remaining(&self) -> u32 {
+        self.duration - self.elapsed
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.duration == 0 {
+            1.0
+        } else {
+            self.elapsed as f32 / self.duration as f32
+        }
+    }
+}
+
 // ============= Movement Components =============
 
 #[derive(Component, Debug, Clone, Default)]
@@ -21,8 +89,8 @@ pub struct Velocity {
 
 #[derive(Component, Debug, Clone)]
 pub struct MovementStats {
-    pub base_speed: f32,
-    pub current_speed: f32,
+    pub base_speed: f32,    // Move speed after stat modifiers
+    pub current_speed: f32, // `base_speed` after status slows; what movement uses
     pub dash_speed: f32,
     pub dash_duration: f32,
     pub dash_cooldown: f32,
@@ -43,20 +111,24 @@ impl Default for MovementStats {
 #[derive(Component, Debug, Clone)]
 pub struct DashState {
     pub is_dashing: bool,
-    pub dash_timer: Timer,
-    pub cooldown_timer: Timer,
+    pub dash_timer: FrameTimer,
+    pub cooldown_timer: FrameTimer,
     pub dash_direction: Vec2,
     pub has_iframes: bool,
+    pub buffered_dash: u32, // Frames left on a dash pressed during cooldown
+    pub attack_locked: bool, // Set by combat while the current move can't be dash-cancelled
 }
 
 impl Default for DashState {
     fn default() -> Self {
         Self {
             is_dashing: false,
-            dash_timer: Timer::from_seconds(0.2, TimerMode::Once),
-            cooldown_timer: Timer::from_seconds(0.4, TimerMode::Once),
+            dash_timer: FrameTimer::new(12),     // 0.2s
+            cooldown_timer: FrameTimer::new(24), // 0.4s
             dash_direction: Vec2::ZERO,
             has_iframes: true,
+            buffered_dash: 0,
+            attack_locked: false,
         }
     }
 }
@@ -96,6 +168,8 @@ pub struct CombatStats {
     pub attack_speed: f32,
     pub armor: f32,
     pub damage_reduction: f32,
+    pub damage_bonus: DamageTypeValues, // Outgoing multiplier per type, 0.2 = +20%
+    pub resistances: DamageTypeValues,  // Incoming reduction per type, negative = weakness
 }
 
 impl Default for CombatStats {
@@ -107,22 +181,153 @@ impl Default for CombatStats {
             attack_speed: 1.0,     // 1.0 = normal speed
             armor: 0.0,
             damage_reduction: 0.0,
+            damage_bonus: DamageTypeValues::default(),
+            resistances: DamageTypeValues::default(),
         }
     }
 }
 
+/// One value per damage type. True damage has no slot and always reads 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DamageTypeValues {
+    pub physical: f32,
+    pub fire: f32,
+    pub ice: f32,
+    pub lightning: f32,
+    pub poison: f32,
+    pub bleed: f32,
+}
+
+impl DamageTypeValues {
+    pub fn get(&self, damage_type: DamageType) -> f32 {
+        match damage_type {
+            DamageType::Physical => self.physical,
+            DamageType::Fire => self.fire,
+            DamageType::Ice => self.ice,
+            DamageType::Lightning => self.lightning,
+            DamageType::Poison => self.poison,
+            DamageType::Bleed => self.bleed,
+            DamageType::True => 0.0,
+        }
+    }
+
+    pub fn get_mut(&mut self, damage_type: DamageType) -> Option<&mut f32> {
+        match damage_type {
+            DamageType::Physical => Some(&mut self.physical),
+            DamageType::Fire => Some(&mut self.fire),
+            DamageType::Ice => Some(&mut self.ice),
+            DamageType::Lightning => Some(&mut self.lightning),
+            DamageType::Poison => Some(&mut self.poison),
+            DamageType::Bleed => Some(&mut self.bleed),
+            DamageType::True => None,
+        }
+    }
+}
+
+// ============= Stat Modifiers =============
+
+/// Stats that modifiers can target. `CombatStats`, `MovementStats` and
+/// `Health::max` are derived from `BaseStats` plus every `StatModifier`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatKind {
+    Damage,
+    CritChance,
+    CritDamage,
+    AttackSpeed,
+    Armor,
+    DamageReduction,
+    DamageBonus(DamageType),
+    Resistance(DamageType),
+    MoveSpeed,
+    MaxHealth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModifierOp {
+    Flat,       // Added to the base value
+    AddPercent, // Summed with the other percentages, then applied once
+    Multiply,   // Applied last, one after another
+}
+
+/// Who owns a modifier, so it can be taken away again
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModifierSource {
+    Boon(String),
+    Curse(String),
+    Elite(EliteModifier),
+    Support, // A Support enemy's pulse, until its `SupportBuff` runs out
+}
+
+#[derive(Debug, Clone)]
+pub struct StatModifier {
+    pub stat: StatKind,
+    pub op: ModifierOp,
+    pub value: f32,
+    pub source: ModifierSource,
+}
+
+impl StatModifier {
+    pub fn flat(stat: StatKind, value: f32, source: ModifierSource) -> Self {
+        Self { stat, op: ModifierOp::Flat, value, source }
+    }
+
+    pub fn add_percent(stat: StatKind, value: f32, source: ModifierSource) -> Self {
+        Self { stat, op: ModifierOp::AddPercent, value, source }
+    }
+
+    pub fn multiply(stat: StatKind, value: f32, source: ModifierSource) -> Self {
+        Self { stat, op: ModifierOp::Multiply, value, source }
+    }
+}
+
+#[derive(Component, Debug, Clone, Default)]
+pub struct StatModifiers {
+    modifiers: Vec<StatModifier>,
+}
+
+impl StatModifiers {
+    pub fn add(&mut self, modifier: StatModifier) {
+        self.modifiers.push(modifier);
+    }
+
+    pub fn remove_source(&mut self, source: &ModifierSource) {
+        self.modifiers.retain(|modifier| &modifier.source != source);
+    }
+
+    pub fn retain(&mut self, keep: impl FnMut(&StatModifier) -> bool) {
+        self.modifiers.retain(keep);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &StatModifier> {
+        self.modifiers.iter()
+    }
+}
+
+/// Unmodified stats that the derived components are rebuilt from
+#[derive(Component, Debug, Clone)]
+pub struct BaseStats {
+    pub combat: CombatStats,
+    pub move_speed: f32,
+    pub max_health: f32,
+}
+
 #[derive(Component, Debug, Clone)]
 pub struct AttackState {
     pub is_attacking: bool,
     pub attack_type: AttackType,
-    pub startup_timer: Timer,
-    pub active_timer: Timer,
-    pub recovery_timer: Timer,
-    pub can_cancel: bool,
+    pub startup_timer: FrameTimer,
+    pub active_timer: FrameTimer,
+    pub recovery_timer: FrameTimer,
+    pub can_cancel: bool, // Some chain out of the current move is open
     pub combo_count: u32,
+    pub move_set: String,
+    pub current_move: Option<usize>, // Index into the move-set; kept through the combo reset window
+    pub move_frame: u32,             // Frames since the current move started
+    pub speed_multiplier: f32,       // Frame data stretch from attack speed when the move started
+    pub combo_timer: FrameTimer,     // Combo reset window after the move finishes
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
 pub enum AttackType {
     LightAttack,
     HeavyAttack,
@@ -136,11 +341,16 @@ impl Default for AttackState {
         Self {
             is_attacking: false,
             attack_type: AttackType::LightAttack,
-            startup_timer: Timer::from_seconds(0.133, TimerMode::Once),  // 8 frames
-            active_timer: Timer::from_seconds(0.067, TimerMode::Once),   // 4 frames
-            recovery_timer: Timer::from_seconds(0.1, TimerMode::Once),   // 6 frames
+            startup_timer: FrameTimer::new(8),
+            active_timer: FrameTimer::new(4),
+            recovery_timer: FrameTimer::new(6),
             can_cancel: false,
             combo_count: 0,
+            move_set: DEFAULT_MOVE_SET.to_string(),
+            current_move: None,
+            move_frame: 0,
+            speed_multiplier: 1.0,
+            combo_timer: FrameTimer::new(0),
         }
     }
 }
@@ -150,28 +360,63 @@ pub struct Hitbox {
     pub size: Vec2,
     pub offset: Vec2,
     pub damage: f32,
+    pub damage_type: DamageType,
     pub knockback: f32,
+    pub status: Option<OnHitStatus>,
     pub active: bool,
     pub hit_entities: Vec<Entity>,
 }
 
+/// Which side a combatant or attack volume is on; see `combat::can_hit`
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Team {
+    Player,
+    Enemy,
+    Environment, // Hazards; hurts everyone
+}
+
+/// Despawns a hitbox that isn't tied to its owner's attack, e.g. an enemy
+/// swing or a blast
+#[derive(Component)]
+pub struct HitboxLifetime(pub FrameTimer);
+
 #[derive(Component)]
 pub struct Hurtbox {
     pub size: Vec2,
     pub invulnerable: bool,
 }
 
+/// Any damage that reaches health kills outright (the Doomed curse)
+#[derive(Component)]
+pub struct OneHitDeath;
+
+/// Enemies pick this player over others (the Marked curse)
+#[derive(Component)]
+pub struct Marked;
+
+/// Every curse one player carries. The local player's mirrors
+/// `PlayerInventory`; clients send theirs to the host, which applies them.
+#[derive(Component, Debug, Clone, Default)]
+pub struct PlayerCurses(pub Vec<Curse>);
+
+/// Interact to take on `curse`; spawned in Secret rooms and now and then elsewhere
+#[derive(Component, Debug, Clone)]
+pub struct CurseShrine {
+    pub curse: Curse,
+}
+
 // ============= Enemy Components =============
 
 #[derive(Component, Debug, Clone)]
 pub struct Enemy {
+    pub archetype: String, // Id in `EnemyArchetypes`
     pub enemy_type: EnemyType,
     pub behavior: EnemyBehavior,
     pub aggro_range: f32,
     pub attack_range: f32,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum EnemyType {
     CommonMelee,
     CommonRanged,
@@ -181,7 +426,7 @@ pub enum EnemyType {
     Boss,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum EnemyBehavior {
     Aggressive,  // Always moves toward player
     Defensive,   // Maintains distance
@@ -189,15 +434,101 @@ pub enum EnemyBehavior {
     Patrol,      // Follows waypoints
 }
 
+/// An elite's rolled modifier and whatever it still has left to break
+#[derive(Component, Debug, Clone)]
+pub struct Elite {
+    pub modifier: EliteModifier,
+    pub reward_multiplier: f32,
+    pub barrier: f32, // Armor or shield HP left before it breaks; 0 for other modifiers
+    pub regen_timer: FrameTimer,
+}
+
+impl Elite {
+    pub fn is_shielded(&self) -> bool {
+        self.modifier == EliteModifier::Shielded && self.barrier > 0.0
+    }
+
+    pub fn is_armored(&self) -> bool {
+        self.modifier == EliteModifier::Armored && self.barrier > 0.0
+    }
+
+    /// Shielded elites take no health damage until the shield breaks.
+    /// Returns what gets through, which is nothing while the shield holds.
+    pub fn absorb(&mut self, damage: f32) -> f32 {
+        if !self.is_shielded() {
+            return damage;
+        }
+        self.barrier = (self.barrier - damage).max(0.0);
+        0.0
+    }
+}
+
 #[derive(Component, Debug)]
 pub struct EnemyAI {
     pub state: AIState,
     pub target: Option<Entity>,
     pub last_known_position: Option<Vec2>,
-    pub state_timer: Timer,
+    pub state_timer: FrameTimer,
+    pub strafe_sign: f32, // Which way a kiting enemy circles its target; flips after each attack
+    pub attack_index: usize, // Into the archetype's attack list; moves on after each attack
+    pub phase: usize, // Into the archetype's boss phases; always 0 for everyone else
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// Waypoints a Patrol enemy walks in a loop until something comes into range
+#[derive(Component, Debug, Clone)]
+pub struct PatrolRoute {
+    pub waypoints: Vec<Vec2>,
+    pub next: usize,
+}
+
+impl PatrolRoute {
+    pub fn new(waypoints: Vec<Vec2>) -> Self {
+        Self { waypoints, next: 0 }
+    }
+
+    pub fn current(&self) -> Option<Vec2> {
+        self.waypoints.get(self.next).copied()
+    }
+
+    pub fn advance(&mut self) {
+        if !self.waypoints.is_empty() {
+            self.next = (self.next + 1) % self.waypoints.len();
+        }
+    }
+}
+
+/// A boss or miniboss working through its phases. `phases` holds the
+/// archetype's phase indices that apply at this ascension, each with the
+/// health fraction it starts at.
+#[derive(Component, Debug, Clone)]
+pub struct Boss {
+    pub phases: Vec<(usize, f32)>,
+    pub current: usize, // Into `phases`
+    pub transition: FrameTimer,
+}
+
+impl Boss {
+    /// Archetype phase index the fight is in
+    pub fn phase(&self) -> usize {
+        self.phases.get(self.current).map_or(0, |(phase, _)| *phase)
+    }
+
+    pub fn next_threshold(&self) -> Option<f32> {
+        self.phases.get(self.current + 1).map(|(_, threshold)| *threshold)
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        !self.transition.finished()
+    }
+}
+
+/// Time left on the stat boost from a Support enemy's pulse
+#[derive(Component, Debug)]
+pub struct SupportBuff {
+    pub timer: FrameTimer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum AIState {
     Idle,
     Patrolling,
@@ -222,10 +553,19 @@ pub enum TelegraphType {
     RangedShot,
     AreaOfEffect,
     ChargeAttack,
+    EnemySpawn,
 }
 
 // ============= Room Components =============
 
+/// Something enemies path around: blocking obstacles close their nav cells,
+/// the rest only make them expensive to cross
+#[derive(Component, Debug, Clone, Copy)]
+pub struct NavObstacle {
+    pub size: Vec2,
+    pub blocks: bool,
+}
+
 #[derive(Component)]
 pub struct Room {
     pub room_type: RoomType,
@@ -262,7 +602,7 @@ pub enum RewardType {
     Item(String),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum BoonRarity {
     Common,
     Rare,
@@ -271,6 +611,14 @@ pub enum BoonRarity {
     Mythic,
 }
 
+/// Shop ware bought by pressing interact next to it; turns into a pickup
+#[derive(Component, Debug, Clone)]
+pub struct ShopItem {
+    pub pickup_type: PickupType,
+    pub value: f32,
+    pub price: u32,
+}
+
 #[derive(Component)]
 pub struct Door {
     pub direction: Direction,
@@ -280,7 +628,7 @@ pub struct Door {
 
 // ============= Boon/Power-up Components =============
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Boon {
     pub name: String,
     pub description: String,
@@ -290,7 +638,21 @@ pub struct Boon {
     pub max_stacks: u32,
 }
 
-#[derive(Debug, Clone)]
+/// Every boon one player owns. The local player's mirrors `PlayerInventory`;
+/// clients send theirs to the host, which runs everyone's procs.
+#[derive(Component, Debug, Clone, Default)]
+pub struct PlayerBoons(pub Vec<Boon>);
+
+/// What a client tells the host about one of its boons. The host rebuilds the
+/// boon from the pool rather than taking the client's numbers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoonClaim {
+    pub name: String,
+    pub rarity: BoonRarity,
+    pub stacks: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BoonType {
     DamageBoost(f32),
     SpeedBoost(f32),
@@ -321,19 +683,28 @@ pub struct GameInfoText;
 
 // ============= Input Components =============
 
-#[derive(Component, Default)]
+#[derive(Component)]
 pub struct InputBuffer {
     pub buffer: Vec<BufferedInput>,
-    pub max_buffer_time: f32,
+    pub max_buffer_frames: u32,
+}
+
+impl Default for InputBuffer {
+    fn default() -> Self {
+        Self {
+            buffer: Vec::new(),
+            max_buffer_frames: 6, // 100ms window for chained actions per Design Bible
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct BufferedInput {
     pub action: InputAction,
-    pub timestamp: f32,
+    pub age: u32, // Simulation frames since it was pressed
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum InputAction {
     Move(Vec2),
     LightAttack,
@@ -343,22 +714,102 @@ pub enum InputAction {
     Pause,
 }
 
+/// One simulation tick of a player's input, numbered so the host can acknowledge it
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputFrame {
+    pub sequence: u32,
+    pub actions: Vec<InputAction>,
+}
+
+impl InputFrame {
+    pub fn movement(&self) -> Vec2 {
+        self.actions
+            .iter()
+            .find_map(|action| match action {
+                InputAction::Move(direction) => Some(*direction),
+                _ => None,
+            })
+            .unwrap_or(Vec2::ZERO)
+    }
+
+    pub fn pressed(&self, action: &InputAction) -> bool {
+        self.actions.contains(action)
+    }
+}
+
+/// Input frames waiting to be simulated for this player, oldest first
+#[derive(Component, Default)]
+pub struct PendingInputs {
+    pub frames: VecDeque<InputFrame>,
+    pub last_sequence: u32, // Last frame simulated, acknowledged back to the sender
+}
+
 // ============= Status Effect Components =============
 
-#[derive(Component)]
+#[derive(Component, Debug, Clone, Default)]
 pub struct StatusEffects {
     pub effects: Vec<StatusEffect>,
+    pub stun_immunity: FrameTimer, // Runs after a stun wears off; no new stuns until it finishes
+}
+
+impl StatusEffects {
+    pub fn get(&self, effect_type: StatusEffectType) -> Option<&StatusEffect> {
+        self.effects.iter().find(|effect| effect.effect_type == effect_type)
+    }
+
+    pub fn stacks(&self, effect_type: StatusEffectType) -> u32 {
+        self.get(effect_type).map_or(0, |effect| effect.stacks)
+    }
+
+    /// Stunned and frozen targets can't move, attack or think
+    pub fn is_incapacitated(&self) -> bool {
+        self.get(StatusEffectType::Stun).is_some() || self.get(StatusEffectType::Freeze).is_some()
+    }
+
+    pub fn move_speed_multiplier(&self) -> f32 {
+        if self.is_incapacitated() {
+            return 0.0;
+        }
+        self.slow_multiplier()
+    }
+
+    pub fn attack_speed_multiplier(&self) -> f32 {
+        self.slow_multiplier()
+    }
+
+    fn slow_multiplier(&self) -> f32 {
+        self.get(StatusEffectType::Slow)
+            .map_or(1.0, |slow| (1.0 - slow.value * slow.stacks as f32).max(0.1))
+    }
+
+    /// Let an active shield soak up damage, returning what gets through
+    pub fn absorb(&mut self, damage: f32) -> f32 {
+        let Some(shield) = self.effects.iter_mut().find(|effect| effect.effect_type == StatusEffectType::Shield) else {
+            return damage;
+        };
+        let absorbed = damage.min(shield.value);
+        shield.value -= absorbed;
+        if shield.value <= 0.0 {
+            self.effects.retain(|effect| effect.effect_type != StatusEffectType::Shield);
+        }
+        damage - absorbed
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct StatusEffect {
     pub effect_type: StatusEffectType,
-    pub duration: Timer,
+    pub duration: FrameTimer,
     pub stacks: u32,
-    pub value: f32,
+    pub value: f32, // Per stack: DoT/HoT per second, slow fraction, or shield HP
+    pub tick_timer: FrameTimer, // Paces DoT and HoT ticks
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// Marker kept in sync with Stun/Freeze so movement, combat and AI can filter on it
+#[derive(Component)]
+pub struct Incapacitated;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
 pub enum StatusEffectType {
     Burn,
     Poison,
@@ -370,15 +821,53 @@ pub enum StatusEffectType {
     Regen,
 }
 
+/// An effect an attack puts on whatever it lands on, e.g.
+/// `{ "effect": "Burn", "value": 4.0 }` in move-set or archetype data
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct OnHitStatus {
+    pub effect: StatusEffectType,
+    pub value: f32, // Per stack, as in `status::ApplyStatus`
+}
+
+// ============= Weapon Components =============
+
+/// How a weapon's moves reach their target
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum WeaponAttack {
+    Melee,      // Moves spawn their `Hitbox`, stretched to the weapon's range
+    Projectile, // Moves fire a `Projectile` that travels the weapon's range
+}
+
+/// The weapon a player swings. Damage and attack speed go into `BaseStats`
+/// and the move-set into `AttackState` when it's equipped.
+#[derive(Component, Debug, Clone)]
+pub struct Weapon {
+    pub id: String,
+    pub range: f32,
+    pub attack: WeaponAttack,
+    pub projectile_speed: f32,
+}
+
 // ============= Projectile Components =============
 
 #[derive(Component)]
 pub struct Projectile {
     pub damage: f32,
+    pub damage_type: DamageType,
     pub speed: f32,
-    pub lifetime: Timer,
-    pub piercing: u32,
+    pub size: Vec2,
+    pub lifetime: FrameTimer,
+    pub piercing: u32, // Further targets it passes through; 0 stops at the first
+    pub status: Option<OnHitStatus>,
     pub owner: Entity,
+    pub hit_entities: Vec<Entity>,
+}
+
+/// Steers a projectile toward the nearest target on the other team
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Homing {
+    pub turn_rate: f32, // Radians per second
+    pub range: f32,
 }
 
 // ============= Pickup Components =============
@@ -390,7 +879,7 @@ pub struct Pickup {
     pub value: f32,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum PickupType {
     Gold,
     Health,
@@ -0,0 +1,419 @@
+use bevy::prelude::*;
+use rand::Rng;
+use crate::components::*;
+use crate::resources::*;
+use crate::damage::{DamageInstance, DamageTag, DealDamage};
+use crate::movement::MovementSet;
+use crate::netcode::has_authority;
+use crate::network::GameMessage;
+use crate::peer_sync::{receive_from_peers, send_to_host, sync_local, PeerReported};
+
+// ============= Curses =============
+//
+// Curses are the push-your-luck choice: a penalty paired with a bonus from
+// the Curse Effects table. They're taken at shrines and count down one per
+// room entered. The inventory's curses are mirrored onto the local player's
+// `PlayerCurses`, which clients send to the host; each player's curses turn
+// into their `StatModifier`s (plus a few marker components) whenever they
+// change, so lifting one undoes it.
+
+const HEALTH_DRAIN_TICK_FRAMES: u32 = 60;
+const BASE_VISION_RANGE: f32 = 600.0;
+const SHRINE_INTERACT_RANGE: f32 = 50.0;
+const SHRINE_CHANCE: f64 = 0.1; // Combat and Elite rooms; Secret rooms always have one
+
+pub struct CursePlugin;
+
+impl Plugin for CursePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(CurrentGameState::RoomTransition), tick_curse_durations)
+            .add_systems(Update, (
+                use_curse_shrines,
+                apply_blindness,
+            ).run_if(in_state(PauseState::Running)))
+            .add_systems(Update, (
+                sync_local::<PlayerCurses>,
+                send_to_host::<PlayerCurses>.run_if(not(has_authority)),
+                receive_from_peers::<PlayerCurses>.run_if(has_authority),
+                sync_curse_modifiers,
+            ).chain())
+            .add_systems(FixedUpdate, apply_health_drain
+                .before(MovementSet)
+                .run_if(in_state(PauseState::Running))
+                .run_if(has_authority));
+    }
+}
+
+/// From the Curse Effects table in Tuning Tables
+pub fn make_curse(kind: CurseKind) -> Curse {
+    let curse = |name: &str, description: &str, effect, bonus, duration| Curse {
+        kind,
+        name: name.to_string(),
+        description: description.to_string(),
+        effect,
+        bonus,
+        duration,
+    };
+
+    match kind {
+        CurseKind::Fragile => curse("Fragile", "-30% max HP, +40% damage", CurseEffect::ReducedMaxHealth(0.3), CurseBonus::Damage(0.4), None),
+        CurseKind::Slow => curse("Slow", "-25% move speed, +30% attack speed", CurseEffect::ReducedSpeed(0.25), CurseBonus::AttackSpeed(0.3), Some(5)),
+        CurseKind::Blind => curse("Blind", "-50% vision range, +50% crit chance", CurseEffect::ReducedVision(0.5), CurseBonus::CritChance(0.5), Some(3)),
+        CurseKind::Cursed => curse("Cursed", "-1 HP/sec until healed, +100% gold find", CurseEffect::HealthDrain(1.0), CurseBonus::GoldFind(1.0), None),
+        CurseKind::Marked => curse("Marked", "Enemies target you first, +25% all stats", CurseEffect::Marked, CurseBonus::AllStats(0.25), Some(10)),
+        CurseKind::Doomed => curse("Doomed", "Die in one hit, +200% damage", CurseEffect::OneHitDeath, CurseBonus::Damage(2.0), Some(1)),
+    }
+}
+
+pub fn roll_curse(rng: &mut impl Rng) -> Curse {
+    const KINDS: [CurseKind; 6] = [
+        CurseKind::Fragile,
+        CurseKind::Slow,
+        CurseKind::Blind,
+        CurseKind::Cursed,
+        CurseKind::Marked,
+        CurseKind::Doomed,
+    ];
+    make_curse(KINDS[rng.gen_range(0..KINDS.len())])
+}
+
+/// Whether this room gets a shrine. Rolled from the room-scoped spawn stream
+/// so every peer agrees.
+pub fn should_spawn_shrine(room_type: RoomType, rng: &mut impl Rng) -> bool {
+    match room_type {
+        RoomType::Secret => true,
+        RoomType::Combat | RoomType::Elite => rng.gen_bool(SHRINE_CHANCE),
+        _ => false,
+    }
+}
+
+pub fn spawn_curse_shrine(commands: &mut Commands, room_entity: Entity, position: Vec2, curse: Curse) {
+    let label = format!("{}\n{}\n[E] Accept", curse.name, curse.description);
+
+    commands
+        .spawn((
+            CurseShrine { curse },
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgb(0.5, 0.2, 0.7),
+                    custom_size: Some(Vec2::new(36.0, 36.0)),
+                    ..default()
+                },
+                transform: Transform::from_translation(position.extend(0.2)),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(Text2dBundle {
+                text: Text::from_section(
+                    label,
+                    TextStyle {
+                        font_size: 14.0,
+                        color: Color::srgb(0.8, 0.6, 1.0),
+                        ..default()
+                    },
+                )
+                .with_justify(JustifyText::Center),
+                transform: Transform::from_translation(Vec3::new(0.0, 50.0, 0.1)),
+                ..default()
+            });
+        })
+        .set_parent(room_entity);
+}
+
+/// Shrines and doors share the interact key; a shrine in reach takes the press
+pub fn in_shrine_range(position: Vec2, shrine_transform: &GlobalTransform) -> bool {
+    position.distance(shrine_transform.translation().truncate()) <= SHRINE_INTERACT_RANGE
+}
+
+fn use_curse_shrines(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    shrine_query: Query<(Entity, &CurseShrine, &GlobalTransform)>,
+    player_query: Query<&Transform, With<LocalPlayer>>,
+    mut inventory: ResMut<PlayerInventory>,
+) {
+    if !keyboard.just_pressed(KeyCode::KeyE) {
+        return;
+    }
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
+
+    for (entity, shrine, shrine_transform) in shrine_query.iter() {
+        if !in_shrine_range(player_pos, shrine_transform) {
+            continue;
+        }
+        // One curse at a time, per the design bible's slot limits
+        if inventory.curses.len() >= inventory.max_curses {
+            return;
+        }
+
+        inventory.curses.push(shrine.curse.clone());
+        commands.entity(entity).despawn_recursive();
+        return;
+    }
+}
+
+/// Count room-limited curses down as each new room is entered
+fn tick_curse_durations(mut inventory: ResMut<PlayerInventory>) {
+    for curse in inventory.curses.iter_mut() {
+        if let Some(rooms) = curse.duration.as_mut() {
+            *rooms = rooms.saturating_sub(1);
+        }
+    }
+    inventory.curses.retain(|curse| curse.duration != Some(0));
+}
+
+impl PeerReported for PlayerCurses {
+    type Claim = CurseClaim;
+
+    const LABEL: &'static str = "curses";
+
+    fn from_inventory(inventory: &PlayerInventory) -> Self {
+        PlayerCurses(inventory.curses.clone())
+    }
+
+    fn claims(&self) -> Vec<CurseClaim> {
+        self.0
+            .iter()
+            .map(|curse| CurseClaim {
+                kind: curse.kind,
+                rooms_remaining: curse.duration,
+            })
+            .collect()
+    }
+
+    fn message(player_id: String, curses: Vec<CurseClaim>) -> GameMessage {
+        GameMessage::PlayerCurses { player_id, curses }
+    }
+
+    fn read_message(message: &GameMessage) -> Option<(&String, &[CurseClaim])> {
+        match message {
+            GameMessage::PlayerCurses { player_id, curses } => Some((player_id, curses)),
+            _ => None,
+        }
+    }
+
+    /// Build the curses from the Curse Effects table, no more than a fresh
+    /// inventory can carry. A timed curse keeps the client's count of rooms
+    /// left, but never more than it starts with.
+    fn rebuild(claims: &[CurseClaim]) -> Self {
+        let max_curses = PlayerInventory::default().max_curses;
+        let curses = claims
+            .iter()
+            .take(max_curses)
+            .map(|claim| {
+                let mut curse = make_curse(claim.kind);
+                curse.duration = curse
+                    .duration
+                    .map(|rooms| claim.rooms_remaining.map_or(rooms, |remaining| remaining.clamp(1, rooms)));
+                curse
+            })
+            .collect();
+        PlayerCurses(curses)
+    }
+}
+
+/// Swap each player's curse modifiers and markers for ones matching their
+/// curses
+fn sync_curse_modifiers(
+    mut commands: Commands,
+    mut player_query: Query<(Entity, &PlayerCurses, &mut StatModifiers), Changed<PlayerCurses>>,
+) {
+    for (player, curses, mut modifiers) in player_query.iter_mut() {
+        modifiers.retain(|modifier| !matches!(modifier.source, ModifierSource::Curse(_)));
+        let mut one_hit_death = false;
+        let mut marked = false;
+
+        for curse in curses.0.iter() {
+            let source = ModifierSource::Curse(curse.name.clone());
+
+            match curse.effect {
+                CurseEffect::ReducedMaxHealth(amount) => {
+                    modifiers.add(StatModifier::add_percent(StatKind::MaxHealth, -amount, source.clone()));
+                }
+                CurseEffect::ReducedSpeed(amount) => {
+                    modifiers.add(StatModifier::add_percent(StatKind::MoveSpeed, -amount, source.clone()));
+                }
+                CurseEffect::IncreasedEnemyDamage(amount) => {
+                    modifiers.add(StatModifier::flat(StatKind::DamageReduction, -amount, source.clone()));
+                }
+                CurseEffect::OneHitDeath => one_hit_death = true,
+                CurseEffect::Marked => marked = true,
+                // Handled by their own systems
+                CurseEffect::ReducedVision(_) | CurseEffect::HealthDrain(_) => {}
+            }
+
+            match curse.bonus {
+                CurseBonus::Damage(bonus) => {
+                    modifiers.add(StatModifier::add_percent(StatKind::Damage, bonus, source));
+                }
+                CurseBonus::AttackSpeed(bonus) => {
+                    modifiers.add(StatModifier::add_percent(StatKind::AttackSpeed, bonus, source));
+                }
+                CurseBonus::CritChance(bonus) => {
+                    modifiers.add(StatModifier::flat(StatKind::CritChance, bonus, source));
+                }
+                CurseBonus::AllStats(bonus) => {
+                    for stat in [StatKind::Damage, StatKind::AttackSpeed, StatKind::MoveSpeed, StatKind::MaxHealth] {
+                        modifiers.add(StatModifier::add_percent(stat, bonus, source.clone()));
+                    }
+                }
+                // Read from the player's curses when they pick up gold
+                CurseBonus::GoldFind(_) => {}
+            }
+        }
+
+        let mut player = commands.entity(player);
+        if one_hit_death {
+            player.insert(OneHitDeath);
+        } else {
+            player.remove::<OneHitDeath>();
+        }
+        if marked {
+            player.insert(Marked);
+        } else {
+            player.remove::<Marked>();
+        }
+    }
+}
+
+/// Drain every player carrying a drain curse, once a second
+fn apply_health_drain(
+    player_query: Query<(Entity, &PlayerCurses)>,
+    mut damage_events: EventWriter<DealDamage>,
+    mut frames: Local<u32>,
+) {
+    *frames += 1;
+    if *frames < HEALTH_DRAIN_TICK_FRAMES {
+        return;
+    }
+    *frames = 0;
+
+    for (player, curses) in player_query.iter() {
+        let drain: f32 = curses
+            .0
+            .iter()
+            .map(|curse| match curse.effect {
+                CurseEffect::HealthDrain(per_second) => per_second,
+                _ => 0.0,
+            })
+            .sum();
+        if drain <= 0.0 {
+            continue;
+        }
+
+        damage_events.send(DealDamage {
+            target: player,
+            instance: DamageInstance::new(drain * HEALTH_DRAIN_TICK_FRAMES as f32 * FRAME_SECONDS, DamageType::True, None)
+                .with_tag(DamageTag::DamageOverTime),
+        });
+    }
+}
+
+/// Blind hides enemies beyond the shortened vision range
+fn apply_blindness(
+    inventory: Res<PlayerInventory>,
+    player_query: Query<&Transform, With<LocalPlayer>>,
+    mut enemy_query: Query<(&Transform, &mut Visibility), With<Enemy>>,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
+
+    let vision = inventory.curses.iter().fold(BASE_VISION_RANGE, |range, curse| match curse.effect {
+        CurseEffect::ReducedVision(amount) => range * (1.0 - amount),
+        _ => range,
+    });
+    let blind = vision < BASE_VISION_RANGE;
+
+    for (transform, mut visibility) in enemy_query.iter_mut() {
+        let visible = !blind || transform.translation.truncate().distance(player_pos) <= vision;
+        visibility.set_if_neq(if visible { Visibility::Inherited } else { Visibility::Hidden });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    const KINDS: [CurseKind; 6] =
+        [CurseKind::Fragile, CurseKind::Slow, CurseKind::Blind, CurseKind::Cursed, CurseKind::Marked, CurseKind::Doomed];
+
+    fn claim(kind: CurseKind, rooms_remaining: Option<u32>) -> CurseClaim {
+        CurseClaim { kind, rooms_remaining }
+    }
+
+    #[test]
+    fn curses_follow_the_curse_effects_table() {
+        // (kind, penalty, bonus, rooms, lifts on heal)
+        let table = [
+            (CurseKind::Fragile, CurseEffect::ReducedMaxHealth(0.3), CurseBonus::Damage(0.4), None, false),
+            (CurseKind::Slow, CurseEffect::ReducedSpeed(0.25), CurseBonus::AttackSpeed(0.3), Some(5), false),
+            (CurseKind::Blind, CurseEffect::ReducedVision(0.5), CurseBonus::CritChance(0.5), Some(3), false),
+            (CurseKind::Cursed, CurseEffect::HealthDrain(1.0), CurseBonus::GoldFind(1.0), None, true),
+            (CurseKind::Marked, CurseEffect::Marked, CurseBonus::AllStats(0.25), Some(10), false),
+            (CurseKind::Doomed, CurseEffect::OneHitDeath, CurseBonus::Damage(2.0), Some(1), false),
+        ];
+        assert_eq!(table.len(), KINDS.len());
+
+        for (kind, effect, bonus, duration, lifts_on_heal) in table {
+            let curse = make_curse(kind);
+            assert_eq!(curse.kind, kind);
+            assert_eq!((curse.effect.clone(), curse.bonus, curse.duration), (effect, bonus, duration), "{:?}", kind);
+            assert_eq!(curse.lifts_on_heal(), lifts_on_heal, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn host_rebuilds_claimed_curses_from_their_kind() {
+        // (claim, rooms left after the rebuild)
+        let table = [
+            (claim(CurseKind::Slow, Some(3)), Some(3)),
+            (claim(CurseKind::Slow, Some(99)), Some(5)),
+            (claim(CurseKind::Slow, Some(0)), Some(1)),
+            (claim(CurseKind::Slow, None), Some(5)),
+            (claim(CurseKind::Doomed, Some(4)), Some(1)),
+            // Untimed curses stay untimed whatever the client says
+            (claim(CurseKind::Fragile, Some(2)), None),
+            (claim(CurseKind::Cursed, None), None),
+        ];
+
+        for (claim, duration) in table {
+            let rebuilt = PlayerCurses::rebuild(std::slice::from_ref(&claim));
+            let [curse] = rebuilt.0.as_slice() else {
+                panic!("{:?}: {:?}", claim, rebuilt.0);
+            };
+            let table_curse = make_curse(claim.kind);
+            assert_eq!((curse.kind, curse.effect.clone(), curse.bonus), (table_curse.kind, table_curse.effect, table_curse.bonus));
+            assert_eq!(curse.duration, duration, "{:?}", claim);
+        }
+
+        // No more curses than an inventory holds
+        let claims = KINDS.map(|kind| claim(kind, None));
+        assert_eq!(PlayerCurses::rebuild(&claims).0.len(), PlayerInventory::default().max_curses);
+    }
+
+    #[test]
+    fn timed_curses_lift_after_their_rooms() {
+        let mut world = World::new();
+        world.insert_resource(PlayerInventory {
+            curses: vec![make_curse(CurseKind::Blind), make_curse(CurseKind::Fragile)],
+            max_curses: 2,
+            ..default()
+        });
+
+        let rooms_left = |world: &World| -> Vec<Option<u32>> {
+            world.resource::<PlayerInventory>().curses.iter().map(|curse| curse.duration).collect()
+        };
+        world.run_system_once(tick_curse_durations);
+        assert_eq!(rooms_left(&world), vec![Some(2), None]);
+        world.run_system_once(tick_curse_durations);
+        world.run_system_once(tick_curse_durations);
+        assert_eq!(rooms_left(&world), vec![None]);
+    }
+}
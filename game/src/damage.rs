@@ -0,0 +1,360 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use rand::Rng;
+use crate::components::*;
+use crate::resources::*;
+use crate::archetypes::EnemyArchetypes;
+use crate::combat::Dead;
+
+// ============= Damage Pipeline =============
+//
+// Every hit and DoT tick becomes a `DamageInstance` sent with
+// `DealDamage`. `resolve_damage` runs it through the stages in order:
+//
+//   attacker modifiers -> crit -> defender resistances (True skips these)
+//   -> shields -> health
+//
+// and reports what landed with `DamageDealt` so cosmetics and boons can react
+// without re-deriving the numbers. Like hit detection, this only runs on the
+// host.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DamageTag {
+    Melee,
+    Projectile,
+    DamageOverTime,
+    NoCrit,    // Skips the crit roll even when the source can crit
+    Chain,     // Spawned by another hit (chain lightning); never chains again
+    Reflected, // Thorns and the like; never reflected back
+}
+
+#[derive(Debug, Clone)]
+pub struct DamageInstance {
+    pub amount: f32,
+    pub damage_type: DamageType,
+    pub source: Option<Entity>,
+    pub is_crit: bool,
+    pub tags: Vec<DamageTag>,
+}
+
+impl DamageInstance {
+    pub fn new(amount: f32, damage_type: DamageType, source: Option<Entity>) -> Self {
+        Self {
+            amount,
+            damage_type,
+            source,
+            is_crit: false,
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn with_tag(mut self, tag: DamageTag) -> Self {
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+        self
+    }
+
+    pub fn has_tag(&self, tag: DamageTag) -> bool {
+        self.tags.contains(&tag)
+    }
+}
+
+/// Ask the pipeline to hurt `target`
+#[derive(Event, Debug, Clone)]
+pub struct DealDamage {
+    pub target: Entity,
+    pub instance: DamageInstance,
+}
+
+/// What actually reached `target` after every stage, shields included
+#[derive(Event, Debug, Clone)]
+pub struct DamageDealt {
+    pub target: Entity,
+    pub instance: DamageInstance,
+    pub absorbed: f32,
+    pub killed: bool,
+}
+
+// ============= Stages =============
+
+/// Stage 1: the attacker's per-type damage bonus
+pub fn apply_attacker_modifiers(instance: &mut DamageInstance, attacker: &CombatStats) {
+    instance.amount *= 1.0 + attacker.damage_bonus.get(instance.damage_type);
+}
+
+/// Stage 2: crits. DoTs and anything tagged NoCrit never crit.
+pub fn roll_crit(instance: &mut DamageInstance, attacker: &CombatStats, rng: &mut impl Rng) {
+    if instance.is_crit {
+        return;
+    }
+    if instance.has_tag(DamageTag::DamageOverTime) || instance.has_tag(DamageTag::NoCrit) {
+        return;
+    }
+
+    if rng.gen::<f32>() < attacker.crit_chance {
+        instance.is_crit = true;
+        instance.amount *= attacker.crit_damage;
+    }
+}
+
+/// Stage 3: armor (physical only), the defender's resistance to this type,
+/// then flat damage reduction. True damage bypasses the whole stage.
+pub fn apply_resistances(instance: &mut DamageInstance, defender: &CombatStats) {
+    if instance.damage_type == DamageType::True {
+        return;
+    }
+
+    let mut damage = instance.amount;
+    if instance.damage_type == DamageType::Physical {
+        damage -= defender.armor;
+    }
+    damage *= 1.0 - defender.resistances.get(instance.damage_type).min(1.0);
+    damage *= 1.0 - defender.damage_reduction;
+
+    // Always do something
+    instance.amount = damage.max(1.0);
+}
+
+/// Stages 1-3 for one instance. Shields and health are applied by the caller.
+pub fn mitigate(
+    mut instance: DamageInstance,
+    attacker: Option<&CombatStats>,
+    defender: Option<&CombatStats>,
+    rng: &mut impl Rng,
+) -> DamageInstance {
+    if let Some(attacker) = attacker {
+        apply_attacker_modifiers(&mut instance, attacker);
+        roll_crit(&mut instance, attacker, rng);
+    }
+    if let Some(defender) = defender {
+        apply_resistances(&mut instance, defender);
+    }
+    instance
+}
+
+// ============= Resolution =============
+
+/// Run statistics and the combat log that `resolve_damage` writes each hit into
+#[derive(SystemParam)]
+pub struct DamageRecords<'w, 's> {
+    players: Query<'w, 's, (), With<Player>>,
+    enemies: Query<'w, 's, &'static Enemy>,
+    archetypes: Res<'w, EnemyArchetypes>,
+    combat_log: ResMut<'w, CombatLog>,
+    run_stats: ResMut<'w, RunStats>,
+    time: Res<'w, Time>,
+}
+
+impl DamageRecords<'_, '_> {
+    fn source_name(&self, source: Option<Entity>) -> String {
+        match source {
+            Some(source) if self.players.contains(source) => "Player".to_string(),
+            Some(source) => self
+                .enemies
+                .get(source)
+                .ok()
+                .and_then(|enemy| self.archetypes.get(&enemy.archetype))
+                .map_or("Enemy", |archetype| archetype.name.as_str())
+                .to_string(),
+            None => "Environment".to_string(),
+        }
+    }
+}
+
+type DamageTargetQuery<'w, 's> = Query<'w, 's, (&'static mut Health, Option<&'static CombatStats>, Option<&'static mut StatusEffects>, Option<&'static mut Elite>, Option<&'static Boss>, Has<Enemy>, Has<OneHitDeath>, Option<&'static Team>), Without<Dead>>;
+
+pub fn resolve_damage(
+    mut commands: Commands,
+    mut requests: EventReader<DealDamage>,
+    mut dealt: EventWriter<DamageDealt>,
+    attacker_query: Query<&CombatStats>,
+    mut target_query: DamageTargetQuery,
+    mut records: DamageRecords,
+    mut run_rng: ResMut<RunRng>,
+) {
+    for request in requests.read() {
+        let Ok((mut health, defender_stats, status_effects, elite, boss, is_enemy, one_hit_death, team)) = target_query.get_mut(request.target) else {
+            continue;
+        };
+        // Already killed earlier this tick
+        if health.current <= 0.0 {
+            continue;
+        }
+
+        let attacker_stats = request.instance.source.and_then(|source| attacker_query.get(source).ok());
+        let mut instance = mitigate(
+            request.instance.clone(),
+            attacker_stats,
+            defender_stats,
+            run_rng.stream(RngStream::Combat),
+        );
+
+        // Stage 4: shields soak damage before health does
+        let incoming = instance.amount;
+        if let Some(mut elite) = elite {
+            instance.amount = elite.absorb(instance.amount);
+        }
+        if let Some(mut status_effects) = status_effects {
+            instance.amount = status_effects.absorb(instance.amount);
+        }
+        let absorbed = incoming - instance.amount;
+
+        // Stage 5: health
+        if one_hit_death && instance.amount > 0.0 {
+            instance.amount = health.current;
+        }
+        // Bosses can't be hurt while changing phase or pushed past one they
+        // haven't played yet
+        if let Some(boss) = boss {
+            let floor = if boss.is_transitioning() {
+                health.current
+            } else {
+                boss.next_threshold().map_or(0.0, |threshold| health.max * threshold)
+            };
+            instance.amount = instance.amount.min((health.current - floor).max(0.0));
+        }
+        let killed = health.take_damage(instance.amount);
+        if killed {
+            commands.entity(request.target).insert(Dead);
+        }
+
+        // Only damage done to enemies counts; hazards and friendly fire don't
+        if team == Some(&Team::Enemy) {
+            records.run_stats.damage_dealt += instance.amount;
+        }
+
+        let event = DamageEvent {
+            source: records.source_name(request.instance.source),
+            target: if is_enemy { "Enemy".to_string() } else { "Player".to_string() },
+            amount: instance.amount,
+            is_crit: instance.is_crit,
+            damage_type: instance.damage_type,
+            timestamp: records.time.elapsed_seconds(),
+        };
+        records.combat_log.recent_damage.push(event);
+
+        dealt.send(DamageDealt {
+            target: request.target,
+            instance,
+            absorbed,
+            killed,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn assert_close(actual: f32, expected: f32, case: &str) {
+        assert!((actual - expected).abs() < 1e-4, "{}: got {}, expected {}", case, actual, expected);
+    }
+
+    fn defender(armor: f32, resistance: (DamageType, f32), damage_reduction: f32) -> CombatStats {
+        let mut stats = CombatStats { armor, damage_reduction, ..default() };
+        if let Some(value) = stats.resistances.get_mut(resistance.0) {
+            *value = resistance.1;
+        }
+        stats
+    }
+
+    #[test]
+    fn resistances_apply_armor_then_resistance_then_reduction() {
+        // (type, amount, armor, resistance, damage reduction, expected)
+        let table = [
+            // Armor comes off physical hits first
+            (DamageType::Physical, 50.0, 10.0, 0.5, 0.2, 16.0),
+            (DamageType::Physical, 50.0, 10.0, 0.0, 0.0, 40.0),
+            // and nothing else
+            (DamageType::Fire, 50.0, 10.0, 0.5, 0.2, 20.0),
+            (DamageType::Poison, 50.0, 10.0, 0.0, 0.0, 50.0),
+            // Resistance stops at 100%; weaknesses add damage
+            (DamageType::Ice, 50.0, 0.0, 1.0, 0.0, 1.0),
+            (DamageType::Ice, 50.0, 0.0, 2.5, 0.0, 1.0),
+            (DamageType::Lightning, 50.0, 0.0, -0.5, 0.0, 75.0),
+            // Every hit does at least 1
+            (DamageType::Physical, 5.0, 10.0, 0.0, 0.0, 1.0),
+            (DamageType::Bleed, 0.5, 0.0, 0.0, 0.5, 1.0),
+            // True damage skips the stage, floor included
+            (DamageType::True, 50.0, 10.0, 0.0, 0.75, 50.0),
+            (DamageType::True, 0.5, 10.0, 0.0, 0.75, 0.5),
+        ];
+
+        for (damage_type, amount, armor, resistance, damage_reduction, expected) in table {
+            let mut instance = DamageInstance::new(amount, damage_type, None);
+            apply_resistances(&mut instance, &defender(armor, (damage_type, resistance), damage_reduction));
+            assert_close(
+                instance.amount,
+                expected,
+                &format!("{:?} {} vs armor {}, resistance {}, reduction {}", damage_type, amount, armor, resistance, damage_reduction),
+            );
+        }
+    }
+
+    #[test]
+    fn dots_and_no_crit_hits_never_crit() {
+        let attacker = CombatStats { crit_chance: 1.0, crit_damage: 2.0, ..default() };
+        let mut rng = StdRng::seed_from_u64(7);
+
+        // (tag, crits)
+        let table = [
+            (None, true),
+            (Some(DamageTag::Melee), true),
+            (Some(DamageTag::Projectile), true),
+            (Some(DamageTag::DamageOverTime), false),
+            (Some(DamageTag::NoCrit), false),
+        ];
+        for (tag, crits) in table {
+            let mut instance = DamageInstance::new(10.0, DamageType::Fire, None);
+            if let Some(tag) = tag {
+                instance = instance.with_tag(tag);
+            }
+            roll_crit(&mut instance, &attacker, &mut rng);
+            assert_eq!(instance.is_crit, crits, "{:?}", tag);
+            assert_close(instance.amount, if crits { 20.0 } else { 10.0 }, &format!("{:?}", tag));
+        }
+    }
+
+    #[test]
+    fn crit_rolls_follow_the_seed() {
+        let attacker = CombatStats { crit_chance: 0.5, ..default() };
+        let rolls = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..32)
+                .map(|_| {
+                    let mut instance = DamageInstance::new(10.0, DamageType::Physical, None);
+                    roll_crit(&mut instance, &attacker, &mut rng);
+                    instance.is_crit
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(rolls(42), rolls(42));
+        assert!(rolls(42).contains(&true) && rolls(42).contains(&false));
+    }
+
+    #[test]
+    fn mitigation_runs_the_stages_in_order() {
+        let mut attacker = CombatStats { crit_chance: 1.0, crit_damage: 2.0, ..default() };
+        attacker.damage_bonus.physical = 0.5;
+        let defender = defender(10.0, (DamageType::Physical, 0.5), 0.5);
+        let mut rng = StdRng::seed_from_u64(7);
+
+        // 10 * 1.5 bonus * 2 crit = 30, - 10 armor, * 0.5 resistance, * 0.5 reduction
+        let instance = mitigate(DamageInstance::new(10.0, DamageType::Physical, None), Some(&attacker), Some(&defender), &mut rng);
+        assert!(instance.is_crit);
+        assert_close(instance.amount, 5.0, "physical");
+
+        // True damage keeps the attacker's stages but skips the defender's
+        let instance = mitigate(DamageInstance::new(10.0, DamageType::True, None), Some(&attacker), Some(&defender), &mut rng);
+        assert_close(instance.amount, 20.0, "true");
+
+        // No stats on either side leaves the hit alone
+        let instance = mitigate(DamageInstance::new(10.0, DamageType::Fire, None), None, None, &mut rng);
+        assert!(!instance.is_crit);
+        assert_close(instance.amount, 10.0, "no stats");
+    }
+}
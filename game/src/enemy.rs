@@ -2,84 +2,191 @@ use bevy::prelude::*;
 use rand::Rng;
 use crate::components::*;
 use crate::resources::*;
+use crate::archetypes::{EnemyArchetype, EnemyArchetypes, EnemyAttackKind};
+use crate::combat::{AttackHitbox, Dead};
+use crate::damage::DamageDealt;
+use crate::movement::{ARENA_HALF_HEIGHT, ARENA_HALF_WIDTH};
+use crate::navigation::{Navigation, NavigationSet};
+use crate::netcode::has_authority;
+use crate::projectiles::{spawn_projectile_spread, ProjectileSpec, SpreadPattern};
+use crate::room::spawn_pickup;
+use crate::status::ApplyStatus;
 
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
+        // Clients only see replicas of the host's enemies, so only the host thinks for them
+        app.add_systems(FixedUpdate, (
             update_enemy_ai,
             enemy_movement,
             enemy_attack_system,
-            update_telegraphs,
-            spawn_enemy_projectiles,
+            cast_support_pulses,
+            expire_support_buffs,
             apply_elite_modifiers,
-        ).chain().run_if(in_combat));
+            react_to_elite_damage,
+        ).chain().after(NavigationSet).run_if(in_state(PauseState::Running)).run_if(has_authority))
+            // Telegraphs follow replicated AI state, so every peer draws them
+            .add_systems(Update, update_telegraphs.run_if(in_state(PauseState::Running)));
     }
 }
 
-fn in_combat(game_state: Res<GameState>) -> bool {
-    matches!(game_state.current_state, CurrentGameState::InRun | CurrentGameState::BossFight)
-        && !game_state.paused
+// ============= Behaviours =============
+//
+// Aggressive enemies walk straight in. Defensive ones kite: they hold a
+// preferred range, strafe around their target and back off after attacking.
+// Support enemies hang back near their allies and use their Pulse attack to
+// heal and buff them. Patrol enemies walk the `PatrolRoute` the room
+// gave them until something comes into range, and go back to it when they
+// lose their target. Ranged enemies of any behaviour only fire with a clear
+// line to their target and strafe to find one. Chasing and fleeing follow
+// the room's flow fields (see navigation.rs), and everyone keeps a little
+// space from everyone else.
+
+const KITE_RANGE: f32 = 0.8;            // Preferred distance as a fraction of attack range
+const KITE_RETREAT_RANGE: f32 = 0.5;    // Back off inside this fraction of attack range
+const STRAFE_SPEED: f32 = 0.6;          // Fraction of move speed
+const PATROL_SPEED: f32 = 0.5;          // Fraction of move speed
+const PATROL_WAYPOINT_RADIUS: f32 = 10.0;
+const SEPARATION_SPACING: f32 = 1.2;    // Personal space as a multiple of touching distance
+const SEPARATION_STRENGTH: f32 = 0.8;   // Fraction of move speed at full overlap
+
+fn is_ranged(archetype: &EnemyArchetype, phase: usize) -> bool {
+    archetype
+        .attacks_in(phase)
+        .iter()
+        .any(|attack| matches!(attack.kind, EnemyAttackKind::Projectile { .. }))
+}
+
+/// Marked players draw every enemy's attention; otherwise go for the nearest
+fn choose_target(enemy_pos: Vec2, players: impl Iterator<Item = (Entity, Vec2, bool)>) -> Option<(Entity, Vec2)> {
+    players
+        .min_by(|a, b| b.2.cmp(&a.2).then(a.1.distance(enemy_pos).total_cmp(&b.1.distance(enemy_pos))))
+        .map(|(entity, position, _)| (entity, position))
 }
 
+/// Whether the straight line from `from` to `to` passes clear of every
+/// blocker, given as (centre, size) boxes
+fn line_of_sight(from: Vec2, to: Vec2, mut blockers: impl Iterator<Item = (Vec2, Vec2)>) -> bool {
+    blockers.all(|(center, size)| !segment_hits_box(from, to, center, size / 2.0))
+}
+
+fn segment_hits_box(from: Vec2, to: Vec2, center: Vec2, half_size: Vec2) -> bool {
+    let delta = to - from;
+    let (mut t_min, mut t_max) = (0.0_f32, 1.0_f32);
+
+    for axis in 0..2 {
+        let low = center[axis] - half_size[axis];
+        let high = center[axis] + half_size[axis];
+        if delta[axis].abs() < f32::EPSILON {
+            if from[axis] < low || from[axis] > high {
+                return false;
+            }
+            continue;
+        }
+
+        let t0 = (low - from[axis]) / delta[axis];
+        let t1 = (high - from[axis]) / delta[axis];
+        t_min = t_min.max(t0.min(t1));
+        t_max = t_max.min(t0.max(t1));
+        if t_min > t_max {
+            return false;
+        }
+    }
+    true
+}
+
+type EnemyAiQuery<'w, 's> = Query<'w, 's, (
+    &'static mut EnemyAI,
+    &'static Enemy,
+    &'static Transform,
+    &'static Hurtbox,
+    &'static Health,
+    &'static CombatStats,
+    Entity,
+), Without<Incapacitated>>;
+
+type TargetPlayerQuery<'w, 's> = Query<'w, 's, (Entity, &'static Transform, Has<Marked>), (With<Player>, Without<Dead>)>;
+
 fn update_enemy_ai(
-    mut enemy_query: Query<(
-        &mut EnemyAI,
-        &mut Enemy,
-        &Transform,
-        &Health,
-        Entity,
-    ), Without<LocalPlayer>>,
-    player_query: Query<(Entity, &Transform), With<LocalPlayer>>,
-    time: Res<Time>,
+    mut enemy_query: EnemyAiQuery,
+    player_query: TargetPlayerQuery,
+    archetypes: Res<EnemyArchetypes>,
 ) {
-    let Ok((player_entity, player_transform)) = player_query.get_single() else {
-        return; // No player to target
-    };
-    let player_pos = player_transform.translation.truncate();
+    if player_query.is_empty() {
+        return; // No player left standing to target
+    }
 
-    for (mut ai, enemy, transform, health, entity) in enemy_query.iter_mut() {
+    // Allies standing in the way block a ranged enemy's shot
+    let bodies: Vec<(Entity, Vec2, Vec2)> = enemy_query
+        .iter()
+        .map(|(_, _, transform, hurtbox, _, _, entity)| (entity, transform.translation.truncate(), hurtbox.size))
+        .collect();
+
+    for (mut ai, enemy, transform, _, health, stats, entity) in enemy_query.iter_mut() {
+        let Some(archetype) = archetypes.get(&enemy.archetype) else {
+            continue;
+        };
         let enemy_pos = transform.translation.truncate();
-        let distance_to_player = enemy_pos.distance(player_pos);
+        let players = player_query
+            .iter()
+            .map(|(player, player_transform, marked)| (player, player_transform.translation.truncate(), marked));
+        let Some((target, target_pos)) = choose_target(enemy_pos, players) else {
+            continue;
+        };
+        let distance_to_player = enemy_pos.distance(target_pos);
+        let has_line_of_sight = line_of_sight(
+            enemy_pos,
+            target_pos,
+            bodies.iter().filter(|(other, ..)| *other != entity).map(|(_, position, size)| (*position, *size)),
+        );
+        let support_radius = archetype.pulse_radius(ai.phase).unwrap_or(0.0);
+        let allies_nearby = bodies
+            .iter()
+            .any(|(other, position, _)| *other != entity && position.distance(enemy_pos) < support_radius);
+
+        // Once engaged, keep tracking whoever is the best target right now
+        if ai.target.is_some() {
+            ai.target = Some(target);
+            ai.last_known_position = Some(target_pos);
+        }
 
         // Update state timer
-        ai.state_timer.tick(time.delta());
+        ai.state_timer.tick();
 
         // State machine logic
         match ai.state {
-            AIState::Idle => {
+            AIState::Idle | AIState::Patrolling => {
                 // Check if player is in aggro range
                 if distance_to_player < enemy.aggro_range {
                     ai.state = AIState::Chasing;
-                    ai.target = Some(player_entity);
-                    ai.state_timer = Timer::from_seconds(0.5, TimerMode::Once);
-                }
-            }
-            
-            AIState::Patrolling => {
-                // Patrol logic (simplified for now)
-                if distance_to_player < enemy.aggro_range {
-                    ai.state = AIState::Chasing;
-                    ai.target = Some(player_entity);
+                    ai.target = Some(target);
+                    ai.last_known_position = Some(target_pos);
+                    ai.state_timer = FrameTimer::from_seconds(0.5);
                 }
             }
             
             AIState::Chasing => {
-                // Update last known position
-                ai.last_known_position = Some(player_pos);
-                
-                // Check if in attack range
-                if distance_to_player < enemy.attack_range {
+                let ready = match enemy.behavior {
+                    // Supports "attack" by pulsing whenever there's someone to pulse for
+                    EnemyBehavior::Support => allies_nearby,
+                    _ => {
+                        distance_to_player < enemy.attack_range
+                            && (!is_ranged(archetype, ai.phase) || has_line_of_sight)
+                    }
+                };
+
+                if let Some(attack) = archetype.attack(ai.phase, ai.attack_index).filter(|_| ready) {
                     // Start telegraph before attack
                     ai.state = AIState::Telegraphing;
-                    ai.state_timer = Timer::from_seconds(
-                        get_telegraph_duration(enemy.enemy_type),
-                        TimerMode::Once
-                    );
+                    ai.state_timer = FrameTimer::from_seconds(attack.telegraph);
                 } else if distance_to_player > enemy.aggro_range * 1.5 {
-                    // Lost player, return to idle
-                    ai.state = AIState::Idle;
+                    // Lost player, go back to what it was doing
+                    ai.state = if enemy.behavior == EnemyBehavior::Patrol {
+                        AIState::Patrolling
+                    } else {
+                        AIState::Idle
+                    };
                     ai.target = None;
                 }
             }
@@ -87,19 +194,20 @@ fn update_enemy_ai(
             AIState::Telegraphing => {
                 // Telegraph attack
                 if ai.state_timer.finished() {
+                    let active = archetype.attack(ai.phase, ai.attack_index).map_or(0.0, |attack| attack.active);
                     ai.state = AIState::Attacking;
-                    ai.state_timer = Timer::from_seconds(0.3, TimerMode::Once);
+                    ai.state_timer = FrameTimer::from_seconds(active);
                 }
             }
             
             AIState::Attacking => {
                 // Execute attack
                 if ai.state_timer.finished() {
+                    let recovery = archetype.attack(ai.phase, ai.attack_index).map_or(0.0, |attack| attack.recovery);
                     ai.state = AIState::Recovering;
-                    ai.state_timer = Timer::from_seconds(
-                        get_recovery_duration(enemy.enemy_type),
-                        TimerMode::Once
-                    );
+                    ai.state_timer = FrameTimer::from_seconds(recovery / stats.attack_speed.max(0.1));
+                    // Work through the attack list in order
+                    ai.attack_index = (ai.attack_index + 1) % archetype.attacks_in(ai.phase).len().max(1);
                 }
             }
             
@@ -108,18 +216,17 @@ fn update_enemy_ai(
                 if ai.state_timer.finished() {
                     // Decide next action based on enemy behavior
                     match enemy.behavior {
-                        EnemyBehavior::Aggressive => {
-                            ai.state = AIState::Chasing;
-                        }
-                        EnemyBehavior::Defensive => {
+                        EnemyBehavior::Defensive | EnemyBehavior::Support => {
+                            // Circle the other way next time
+                            ai.strafe_sign = -ai.strafe_sign;
                             if distance_to_player < enemy.attack_range * 0.7 {
                                 ai.state = AIState::Fleeing;
-                                ai.state_timer = Timer::from_seconds(1.0, TimerMode::Once);
+                                ai.state_timer = FrameTimer::from_seconds(1.0);
                             } else {
                                 ai.state = AIState::Chasing;
                             }
                         }
-                        _ => {
+                        EnemyBehavior::Aggressive | EnemyBehavior::Patrol => {
                             ai.state = AIState::Chasing;
                         }
                     }
@@ -138,99 +245,133 @@ fn update_enemy_ai(
         if health.percentage() < 0.3 && enemy.behavior != EnemyBehavior::Support {
             // Enemies become more aggressive when low on health
             if ai.state == AIState::Chasing {
-                ai.state_timer = Timer::from_seconds(
-                    ai.state_timer.duration().as_secs_f32() * 0.7,
-                    TimerMode::Once
+                ai.state_timer = FrameTimer::new(
+                    (ai.state_timer.duration() as f32 * 0.7).round() as u32
                 );
             }
         }
     }
 }
 
-fn get_telegraph_duration(enemy_type: EnemyType) -> f32 {
-    // From Tuning Tables
-    match enemy_type {
-        EnemyType::CommonMelee => 0.5,
-        EnemyType::CommonRanged => 0.7,
-        EnemyType::EliteMelee => 0.8,
-        EnemyType::EliteRanged => 1.0,
-        EnemyType::MiniBoss => 1.5,
-        EnemyType::Boss => 2.0,
-    }
-}
-
-fn get_recovery_duration(enemy_type: EnemyType) -> f32 {
-    // From Tuning Tables
-    match enemy_type {
-        EnemyType::CommonMelee => 0.3,
-        EnemyType::CommonRanged => 0.5,
-        EnemyType::EliteMelee => 0.4,
-        EnemyType::EliteRanged => 0.6,
-        EnemyType::MiniBoss => 0.5,
-        EnemyType::Boss => 1.0,
-    }
-}
+type EnemyMovementQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static mut Transform,
+    &'static mut Velocity,
+    &'static Enemy,
+    &'static EnemyAI,
+    &'static Hurtbox,
+    Option<&'static mut PatrolRoute>,
+    Option<&'static StatusEffects>,
+), Without<LocalPlayer>>;
 
 fn enemy_movement(
-    mut enemy_query: Query<(
-        &mut Transform,
-        &mut Velocity,
-        &Enemy,
-        &EnemyAI,
-    ), Without<LocalPlayer>>,
-    player_query: Query<&Transform, With<LocalPlayer>>,
+    mut enemy_query: EnemyMovementQuery,
+    archetypes: Res<EnemyArchetypes>,
+    navigation: Res<Navigation>,
     time: Res<Time>,
 ) {
-    let player_transform = player_query.get_single();
-    if player_transform.is_err() {
-        return;
-    }
-    let player_pos = player_transform.translation.truncate();
+    let bodies: Vec<(Entity, Vec2, Vec2)> = enemy_query
+        .iter()
+        .map(|(entity, transform, _, _, _, hurtbox, _, _)| (entity, transform.translation.truncate(), hurtbox.size))
+        .collect();
 
-    for (mut transform, mut velocity, enemy, ai) in enemy_query.iter_mut() {
+    for (entity, mut transform, mut velocity, enemy, ai, hurtbox, patrol_route, status_effects) in enemy_query.iter_mut() {
+        let Some(archetype) = archetypes.get(&enemy.archetype) else {
+            continue;
+        };
         let enemy_pos = transform.translation.truncate();
-        let to_player = player_pos - enemy_pos;
+        let to_player = ai.last_known_position.map_or(Vec2::ZERO, |target| target - enemy_pos);
         let distance = to_player.length();
+        // Chasing and fleeing go around walls and hazards; strafing circles
+        // the target itself
+        let (toward, away) = match (ai.target, ai.last_known_position) {
+            (Some(target), Some(target_pos)) => (
+                navigation.toward(enemy_pos, target, target_pos),
+                navigation.away(enemy_pos, target, target_pos),
+            ),
+            _ => (to_player.normalize_or_zero(), -to_player.normalize_or_zero()),
+        };
+        let strafe = to_player.normalize_or_zero().perp() * ai.strafe_sign;
 
         // Movement based on AI state
-        let movement_speed = match enemy.enemy_type {
-            EnemyType::CommonMelee => 150.0,
-            EnemyType::CommonRanged => 100.0,
-            EnemyType::EliteMelee => 200.0,
-            EnemyType::EliteRanged => 120.0,
-            EnemyType::MiniBoss => 180.0,
-            EnemyType::Boss => 100.0,
-        };
+        let movement_speed = archetype.move_speed
+            * status_effects.map_or(1.0, StatusEffects::move_speed_multiplier);
 
         match ai.state {
             AIState::Chasing => {
-                // Move toward player
-                if distance > enemy.attack_range * 0.9 {
-                    let direction = to_player.normalize();
-                    velocity.linear = direction * movement_speed;
-                } else {
-                    // Stop when in range
-                    velocity.linear = Vec2::ZERO;
+                velocity.linear = match enemy.behavior {
+                    EnemyBehavior::Defensive => {
+                        if distance > enemy.attack_range {
+                            toward * movement_speed
+                        } else if distance < enemy.attack_range * KITE_RETREAT_RANGE {
+                            away * movement_speed
+                        } else {
+                            // Circle while drifting back to the preferred range
+                            let correction = (distance - enemy.attack_range * KITE_RANGE) / enemy.attack_range;
+                            (strafe * STRAFE_SPEED + toward * correction).clamp_length_max(1.0) * movement_speed
+                        }
+                    }
+
+                    EnemyBehavior::Support => {
+                        let nearest_ally = bodies
+                            .iter()
+                            .filter(|(other, ..)| *other != entity)
+                            .map(|(_, position, _)| *position)
+                            .min_by(|a, b| a.distance(enemy_pos).total_cmp(&b.distance(enemy_pos)));
+
+                        if distance < enemy.attack_range * KITE_RETREAT_RANGE {
+                            away * movement_speed
+                        } else if let Some(ally) = nearest_ally.filter(|ally| ally.distance(enemy_pos) > archetype.pulse_radius(ai.phase).unwrap_or(0.0) * 0.5) {
+                            // Stay close enough that the pulse reaches someone
+                            (ally - enemy_pos).normalize_or_zero() * movement_speed
+                        } else {
+                            strafe * movement_speed * STRAFE_SPEED
+                        }
+                    }
+
+                    EnemyBehavior::Aggressive | EnemyBehavior::Patrol => {
+                        // Move toward player, stopping when in range
+                        if distance > enemy.attack_range * 0.9 {
+                            toward * movement_speed
+                        } else {
+                            Vec2::ZERO
+                        }
+                    }
+                };
+
+                // In range but an ally is in the way: sidestep for a clear shot
+                let in_range = distance <= enemy.attack_range;
+                if is_ranged(archetype, ai.phase) && enemy.behavior != EnemyBehavior::Support && in_range {
+                    let blockers = bodies
+                        .iter()
+                        .filter(|(other, ..)| *other != entity)
+                        .map(|(_, position, size)| (*position, *size));
+                    if !line_of_sight(enemy_pos, enemy_pos + to_player, blockers) {
+                        velocity.linear = strafe * movement_speed;
+                    }
                 }
             }
             
             AIState::Fleeing => {
                 // Move away from player
                 if distance < enemy.attack_range * 1.5 {
-                    let direction = -to_player.normalize();
-                    velocity.linear = direction * movement_speed * 1.2; // Flee faster
+                    velocity.linear = away * movement_speed * 1.2; // Flee faster
                 } else {
                     velocity.linear = Vec2::ZERO;
                 }
             }
             
             AIState::Patrolling => {
-                // Simple patrol pattern
-                let time_factor = time.elapsed_seconds();
-                velocity.linear = Vec2::new(
-                    (time_factor * 0.5).sin() * 50.0,
-                    (time_factor * 0.3).cos() * 50.0,
-                );
+                velocity.linear = Vec2::ZERO;
+                if let Some(mut route) = patrol_route {
+                    if let Some(waypoint) = route.current() {
+                        if waypoint.distance(enemy_pos) < PATROL_WAYPOINT_RADIUS {
+                            route.advance();
+                        } else {
+                            velocity.linear = (waypoint - enemy_pos).normalize_or_zero() * movement_speed * PATROL_SPEED;
+                        }
+                    }
+                }
             }
             
             AIState::Telegraphing | AIState::Attacking | AIState::Recovering => {
@@ -243,9 +384,32 @@ fn enemy_movement(
             }
         }
 
-        // Apply velocity
-        transform.translation.x += velocity.linear.x * time.delta_seconds();
-        transform.translation.y += velocity.linear.y * time.delta_seconds();
+        // Keep clear of each other rather than stacking on one spot
+        let separation = bodies
+            .iter()
+            .filter(|(other, ..)| *other != entity)
+            .fold(Vec2::ZERO, |push, (other, position, size)| {
+                let offset = enemy_pos - *position;
+                let min_distance = (hurtbox.size.x + size.x) * 0.5 * SEPARATION_SPACING;
+                let distance = offset.length();
+                if distance >= min_distance {
+                    push
+                } else if distance < f32::EPSILON {
+                    // Exactly on top of each other: split by id so each goes its own way
+                    push + Vec2::from_angle(entity.index().wrapping_sub(other.index()) as f32)
+                } else {
+                    push + offset / distance * (1.0 - distance / min_distance)
+                }
+            });
+        velocity.linear += separation.clamp_length_max(1.0) * movement_speed * SEPARATION_STRENGTH;
+
+        // Apply velocity, staying inside the walls and out of blocked cells
+        let from = transform.translation.truncate();
+        let limit = Vec2::new(ARENA_HALF_WIDTH, ARENA_HALF_HEIGHT) - hurtbox.size / 2.0;
+        let to = (from + velocity.linear * time.delta_seconds()).clamp(-limit, limit);
+        let moved = navigation.resolve_move(from, to);
+        transform.translation.x = moved.x;
+        transform.translation.y = moved.y;
     }
 }
 
@@ -257,82 +421,139 @@ fn enemy_attack_system(
         &Enemy,
         &EnemyAI,
         &CombatStats,
-    )>,
-    player_query: Query<&Transform, With<LocalPlayer>>,
+    ), Without<Incapacitated>>,
+    archetypes: Res<EnemyArchetypes>,
 ) {
-    let player_transform = player_query.get_single();
-    if player_transform.is_err() {
-        return;
-    }
-    let player_pos = player_transform.translation.truncate();
-
     for (entity, transform, enemy, ai, stats) in enemy_query.iter() {
-        if ai.state != AIState::Attacking {
+        // Attacks land once, on the first frame of the Attacking state
+        if ai.state != AIState::Attacking || ai.state_timer.elapsed() != 0 {
             continue;
         }
+        let Some(attack) = archetypes.get(&enemy.archetype).and_then(|archetype| archetype.attack(ai.phase, ai.attack_index)) else {
+            continue;
+        };
+        let Some(target_pos) = ai.last_known_position else {
+            continue;
+        };
 
         let enemy_pos = transform.translation.truncate();
-        let to_player = player_pos - enemy_pos;
+        let to_player = target_pos - enemy_pos;
+        let damage = stats.damage * attack.damage_multiplier;
 
-        match enemy.enemy_type {
-            EnemyType::CommonMelee | EnemyType::EliteMelee => {
+        match attack.kind {
+            EnemyAttackKind::Melee { size, reach, knockback } => {
                 // Spawn melee hitbox
+                let offset = to_player.normalize_or_zero() * reach;
                 commands.spawn((
                     Hitbox {
-                        size: Vec2::new(40.0, 40.0),
-                        offset: to_player.normalize() * 30.0,
-                        damage: stats.damage,
-                        knockback: 30.0,
+                        size,
+                        offset,
+                        damage,
+                        damage_type: DamageType::Physical,
+                        knockback,
+                        status: attack.status,
                         active: true,
                         hit_entities: Vec::new(),
                     },
                     TransformBundle::from_transform(
-                        Transform::from_translation(transform.translation)
+                        Transform::from_translation(transform.translation + offset.extend(0.0))
                     ),
-                    EnemyAttack { owner: entity },
+                    AttackHitbox { owner: entity },
+                    Team::Enemy,
+                    HitboxLifetime(FrameTimer::from_seconds(attack.active)),
                 ));
             }
             
-            EnemyType::CommonRanged | EnemyType::EliteRanged => {
-                // Spawn projectile
-                let direction = to_player.normalize();
-                commands.spawn((
-                    Projectile {
-                        damage: stats.damage,
-                        speed: 300.0,
-                        lifetime: Timer::from_seconds(3.0, TimerMode::Once),
-                        piercing: 0,
-                        owner: entity,
+            EnemyAttackKind::Projectile { count, spread, speed, range } => {
+                spawn_projectile_spread(
+                    &mut commands,
+                    &ProjectileSpec {
+                        status: attack.status,
+                        ..enemy_projectile(damage, speed, range)
                     },
-                    Velocity {
-                        linear: direction * 300.0,
-                    },
-                    SpriteBundle {
-                        sprite: Sprite {
-                            color: Color::rgb(1.0, 0.5, 0.0),
-                            custom_size: Some(Vec2::new(10.0, 10.0)),
-                            ..default()
-                        },
-                        transform: Transform::from_translation(transform.translation),
-                        ..default()
-                    },
-                ));
+                    SpreadPattern { count, arc_degrees: spread },
+                    transform.translation,
+                    to_player,
+                    entity,
+                    Team::Enemy,
+                );
             }
             
-            _ => {}
+            // See `cast_support_pulses`
+            EnemyAttackKind::Pulse { .. } => {}
         }
     }
 }
 
-#[derive(Component)]
-struct EnemyAttack {
-    owner: Entity,
+type SupportAllyQuery<'w, 's> = Query<'w, 's, (Entity, &'static Transform, &'static mut Health, &'static mut StatModifiers, Option<&'static mut SupportBuff>), With<Enemy>>;
+
+/// Pulse attacks heal and buff every ally around the caster on the first
+/// frame of its Attacking state, when anyone else would strike, and give
+/// them the attack's status if it has one
+fn cast_support_pulses(
+    mut commands: Commands,
+    caster_query: Query<(Entity, &Transform, &Enemy, &EnemyAI), Without<Incapacitated>>,
+    mut ally_query: SupportAllyQuery,
+    archetypes: Res<EnemyArchetypes>,
+    mut status_events: EventWriter<ApplyStatus>,
+) {
+    for (caster, caster_transform, enemy, ai) in caster_query.iter() {
+        if ai.state != AIState::Attacking || ai.state_timer.elapsed() != 0 {
+            continue;
+        }
+        let Some(attack) = archetypes.get(&enemy.archetype).and_then(|archetype| archetype.attack(ai.phase, ai.attack_index)) else {
+            continue;
+        };
+        let EnemyAttackKind::Pulse { radius, heal_fraction, damage_buff, attack_speed_buff, buff_seconds } = attack.kind else {
+            continue;
+        };
+        let center = caster_transform.translation.truncate();
+
+        for (ally, transform, mut health, mut modifiers, buff) in ally_query.iter_mut() {
+            if ally == caster || transform.translation.truncate().distance(center) > radius {
+                continue;
+            }
+
+            let max_health = health.max;
+            health.heal(max_health * heal_fraction);
+            if let Some(status) = attack.status {
+                status_events.send(ApplyStatus::on_hit(ally, status));
+            }
+
+            match buff {
+                Some(mut buff) => buff.timer = FrameTimer::from_seconds(buff_seconds),
+                None => {
+                    // Two pulses in one frame must not stack
+                    modifiers.remove_source(&ModifierSource::Support);
+                    modifiers.add(StatModifier::add_percent(StatKind::Damage, damage_buff, ModifierSource::Support));
+                    modifiers.add(StatModifier::add_percent(StatKind::AttackSpeed, attack_speed_buff, ModifierSource::Support));
+                    commands.entity(ally).insert(SupportBuff {
+                        timer: FrameTimer::from_seconds(buff_seconds),
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn expire_support_buffs(
+    mut commands: Commands,
+    mut buff_query: Query<(Entity, &mut SupportBuff, &mut StatModifiers)>,
+) {
+    for (entity, mut buff, mut modifiers) in buff_query.iter_mut() {
+        buff.timer.tick();
+        if buff.timer.finished() {
+            modifiers.remove_source(&ModifierSource::Support);
+            commands.entity(entity).remove::<SupportBuff>();
+        }
+    }
 }
 
 fn update_telegraphs(
     mut commands: Commands,
     mut telegraph_query: Query<(Entity, &mut Telegraph, &mut Sprite)>,
     enemy_query: Query<(&Transform, &Enemy, &EnemyAI), Changed<EnemyAI>>,
+    archetypes: Res<EnemyArchetypes>,
     time: Res<Time>,
 ) {
     // Update existing telegraphs
@@ -356,7 +577,7 @@ fn update_telegraphs(
             1.0
         };
         
-        sprite.color.set_a(alpha);
+        sprite.color.set_alpha(alpha);
         
         if telegraph.duration.finished() {
             commands.entity(entity).despawn();
@@ -366,17 +587,25 @@ fn update_telegraphs(
     // Spawn new telegraphs
     for (transform, enemy, ai) in enemy_query.iter() {
         if ai.state == AIState::Telegraphing {
-            let (size, color, telegraph_type) = match enemy.enemy_type {
-                EnemyType::CommonMelee => (Vec2::new(60.0, 60.0), Color::rgba(1.0, 0.0, 0.0, 0.5), TelegraphType::MeleeSwing),
-                EnemyType::CommonRanged => (Vec2::new(20.0, 200.0), Color::rgba(1.0, 1.0, 0.0, 0.5), TelegraphType::RangedShot),
-                EnemyType::EliteMelee => (Vec2::new(80.0, 80.0), Color::rgba(1.0, 0.0, 0.0, 0.5), TelegraphType::MeleeSwing),
-                EnemyType::EliteRanged => (Vec2::new(30.0, 300.0), Color::rgba(1.0, 0.5, 0.0, 0.5), TelegraphType::RangedShot),
-                _ => continue,
+            let Some(archetype) = archetypes.get(&enemy.archetype) else {
+                continue;
+            };
+            // Clients don't run the AI, so they see the first attack's timing
+            let (Some(shape), Some(attack)) = (&archetype.telegraph, archetype.attack(ai.phase, ai.attack_index)) else {
+                continue;
+            };
+            let size = shape.size;
+            let [r, g, b, a] = shape.color;
+            let color = Color::srgba(r, g, b, a);
+            let telegraph_type = match attack.kind {
+                EnemyAttackKind::Melee { .. } => TelegraphType::MeleeSwing,
+                EnemyAttackKind::Projectile { .. } => TelegraphType::RangedShot,
+                EnemyAttackKind::Pulse { .. } => TelegraphType::AreaOfEffect,
             };
 
             commands.spawn((
                 Telegraph {
-                    duration: Timer::from_seconds(get_telegraph_duration(enemy.enemy_type), TimerMode::Once),
+                    duration: Timer::from_seconds(attack.telegraph, TimerMode::Once),
                     telegraph_type,
                     damage_area: size,
                     color,
@@ -395,151 +624,312 @@ fn update_telegraphs(
     }
 }
 
-fn spawn_enemy_projectiles(
-    mut projectile_query: Query<(&mut Transform, &mut Velocity, &Projectile)>,
-    time: Res<Time>,
-) {
-    for (mut transform, velocity, projectile) in projectile_query.iter_mut() {
-        // Move projectile
-        transform.translation.x += velocity.linear.x * time.delta_seconds();
-        transform.translation.y += velocity.linear.y * time.delta_seconds();
+// ============= Elite Modifiers =============
+
+const ELITE_REGEN_TICK_FRAMES: u32 = 30;
+const ELITE_REGEN_PER_SECOND: f32 = 0.05;  // Fraction of max HP
+const ELITE_BARRIER_FRACTION: f32 = 0.3;   // Armor/shield HP as a fraction of max HP
+const ELITE_ARMOR_REDUCTION: f32 = 0.5;
+const ELITE_VAMPIRIC_HEAL: f32 = 0.2;      // Fraction of damage dealt
+const ELITE_EXPLOSION_SIZE: f32 = 120.0;
+const ELITE_EXPLOSION_DAMAGE: f32 = 2.0;   // Fraction of max HP
+const ELITE_EXPLOSION_BURN: f32 = 5.0;     // Burn per second left on anyone caught
+
+#[derive(Debug, Clone, Copy)]
+pub struct EliteTuning {
+    pub health_multiplier: f32,
+    pub damage_multiplier: f32,
+    pub reward_multiplier: f32,
+}
+
+pub fn elite_tuning(modifier: EliteModifier) -> EliteTuning {
+    let tuning = |health_multiplier, damage_multiplier, reward_multiplier| EliteTuning {
+        health_multiplier,
+        damage_multiplier,
+        reward_multiplier,
+    };
+
+    // From the Elite Modifiers table in Tuning Tables
+    match modifier {
+        EliteModifier::Armored => tuning(1.5, 1.0, 1.5),
+        EliteModifier::Berserker => tuning(1.0, 2.0, 1.5),
+        EliteModifier::Regenerating => tuning(2.0, 1.0, 2.0),
+        EliteModifier::Shielded => tuning(1.0, 1.0, 1.5),
+        EliteModifier::Vampiric => tuning(1.2, 1.2, 1.8),
+        EliteModifier::Explosive => tuning(0.8, 1.5, 2.0),
+    }
+}
+
+pub fn roll_elite_modifier(rng: &mut impl Rng) -> EliteModifier {
+    match rng.gen_range(0..6) {
+        0 => EliteModifier::Armored,
+        1 => EliteModifier::Berserker,
+        2 => EliteModifier::Regenerating,
+        3 => EliteModifier::Shielded,
+        4 => EliteModifier::Vampiric,
+        _ => EliteModifier::Explosive,
     }
 }
 
+/// The elite's stat modifiers and the barrier it starts with
+fn make_elite(modifier: EliteModifier, base_health: f32) -> (Elite, StatModifiers) {
+    let tuning = elite_tuning(modifier);
+    let source = ModifierSource::Elite(modifier);
+    let max_health = base_health * tuning.health_multiplier;
+
+    let mut modifiers = StatModifiers::default();
+    modifiers.add(StatModifier::multiply(StatKind::MaxHealth, tuning.health_multiplier, source.clone()));
+    modifiers.add(StatModifier::multiply(StatKind::Damage, tuning.damage_multiplier, source.clone()));
+
+    let mut barrier = 0.0;
+    match modifier {
+        EliteModifier::Armored => {
+            modifiers.add(StatModifier::flat(StatKind::DamageReduction, ELITE_ARMOR_REDUCTION, source));
+            barrier = max_health * ELITE_BARRIER_FRACTION;
+        }
+        EliteModifier::Berserker => {
+            modifiers.add(StatModifier::add_percent(StatKind::AttackSpeed, 0.5, source));
+        }
+        EliteModifier::Shielded => {
+            barrier = max_health * ELITE_BARRIER_FRACTION;
+        }
+        _ => {}
+    }
+
+    let elite = Elite {
+        modifier,
+        reward_multiplier: tuning.reward_multiplier,
+        barrier,
+        regen_timer: FrameTimer::new(ELITE_REGEN_TICK_FRAMES),
+    };
+    (elite, modifiers)
+}
+
+fn elite_color(elite: &Elite) -> Color {
+    if elite.is_shielded() {
+        return Color::srgb(0.4, 0.7, 1.0);
+    }
+    if elite.is_armored() {
+        return Color::srgb(0.6, 0.6, 0.65);
+    }
+    match elite.modifier {
+        EliteModifier::Berserker => Color::srgb(1.0, 0.3, 0.1),
+        EliteModifier::Regenerating => Color::srgb(0.3, 0.9, 0.4),
+        EliteModifier::Vampiric => Color::srgb(0.6, 0.1, 0.4),
+        EliteModifier::Explosive => Color::srgb(1.0, 0.6, 0.0),
+        // Broken armor or shield
+        _ => Color::srgb(0.8, 0.2, 0.2),
+    }
+}
+
+/// Regenerating elites heal in half-second ticks
 fn apply_elite_modifiers(
-    mut enemy_query: Query<(&mut Health, &mut CombatStats, &Enemy)>,
-    time: Res<Time>,
+    mut enemy_query: Query<(&mut Health, &mut Elite)>,
 ) {
-    // This would apply elite modifier effects
-    // For now, just a placeholder
-    for (mut health, mut stats, enemy) in enemy_query.iter_mut() {
-        if matches!(enemy.enemy_type, EnemyType::EliteMelee | EnemyType::EliteRanged) {
-            // Elite enemies could have special properties
-            // This would be expanded based on the EliteModifier enum
+    for (mut health, mut elite) in enemy_query.iter_mut() {
+        if elite.modifier != EliteModifier::Regenerating {
+            continue;
+        }
+
+        elite.regen_timer.tick();
+        if elite.regen_timer.finished() {
+            elite.regen_timer.reset();
+            let max_health = health.max;
+            health.heal(max_health * ELITE_REGEN_PER_SECOND * ELITE_REGEN_TICK_FRAMES as f32 * FRAME_SECONDS);
         }
     }
 }
 
-// Spawn functions for different enemy types
-pub fn spawn_melee_enemy(
-    commands: &mut Commands,
-    position: Vec2,
-    enemy_type: EnemyType,
-    room_number: u32,
-) -> Entity {
-    // Scale health based on room number (from Tuning Tables)
-    let base_health = match enemy_type {
-        EnemyType::CommonMelee => 200.0 + (room_number as f32 * 20.0),
-        EnemyType::EliteMelee => 1000.0 + (room_number as f32 * 200.0),
-        _ => 200.0,
-    };
+/// Wear down armor, show broken shields and let vampiric elites feed
+fn react_to_elite_damage(
+    mut dealt: EventReader<DamageDealt>,
+    mut elite_query: Query<(&mut Elite, &mut StatModifiers, &mut Health, &mut Sprite)>,
+) {
+    for event in dealt.read() {
+        if let Ok((mut elite, mut modifiers, _, mut sprite)) = elite_query.get_mut(event.target) {
+            if elite.is_armored() {
+                elite.barrier = (elite.barrier - (event.instance.amount + event.absorbed)).max(0.0);
+                if elite.barrier <= 0.0 {
+                    let armor = ModifierSource::Elite(EliteModifier::Armored);
+                    modifiers.retain(|modifier| !(modifier.source == armor && modifier.stat == StatKind::DamageReduction));
+                }
+            }
+            sprite.color = elite_color(&elite);
+        }
 
-    let damage = match enemy_type {
-        EnemyType::CommonMelee => 20.0 + (room_number as f32 * 2.0),
-        EnemyType::EliteMelee => 40.0 + (room_number as f32 * 4.0),
-        _ => 20.0,
-    };
+        let Some(source) = event.instance.source else {
+            continue;
+        };
+        if let Ok((elite, _, mut health, _)) = elite_query.get_mut(source) {
+            if elite.modifier == EliteModifier::Vampiric {
+                health.heal(event.instance.amount * ELITE_VAMPIRIC_HEAL);
+            }
+        }
+    }
+}
 
+/// Called by `handle_death` before the elite is despawned: Explosive elites
+/// leave a blast behind
+pub fn spawn_elite_death_effects(
+    commands: &mut Commands,
+    entity: Entity,
+    elite: &Elite,
+    position: Vec3,
+    health: &Health,
+) {
+    if elite.modifier != EliteModifier::Explosive {
+        return;
+    }
+
+    let size = Vec2::splat(ELITE_EXPLOSION_SIZE);
     commands.spawn((
-        Enemy {
-            enemy_type,
-            behavior: EnemyBehavior::Aggressive,
-            aggro_range: 300.0,
-            attack_range: 50.0,
-        },
-        EnemyAI {
-            state: AIState::Idle,
-            target: None,
-            last_known_position: None,
-            state_timer: Timer::from_seconds(0.0, TimerMode::Once),
-        },
-        Health::new(base_health),
-        CombatStats {
-            damage,
-            crit_chance: 0.05,
-            crit_damage: 1.5,
-            attack_speed: 1.0,
-            armor: 0.0,
-            damage_reduction: 0.0,
-        },
-        Velocity::default(),
-        Hurtbox {
-            size: Vec2::new(30.0, 30.0),
-            invulnerable: false,
+        Hitbox {
+            size,
+            offset: Vec2::ZERO,
+            damage: health.max * ELITE_EXPLOSION_DAMAGE,
+            damage_type: DamageType::Fire,
+            knockback: 200.0,
+            status: Some(OnHitStatus {
+                effect: StatusEffectType::Burn,
+                value: ELITE_EXPLOSION_BURN,
+            }),
+            active: true,
+            hit_entities: Vec::new(),
         },
+        AttackHitbox { owner: entity },
+        Team::Enemy,
+        HitboxLifetime(FrameTimer::from_seconds(0.25)),
         SpriteBundle {
             sprite: Sprite {
-                color: if matches!(enemy_type, EnemyType::EliteMelee) {
-                    Color::rgb(0.8, 0.2, 0.2)
-                } else {
-                    Color::rgb(0.6, 0.2, 0.2)
-                },
-                custom_size: Some(Vec2::new(30.0, 30.0)),
+                color: Color::srgba(1.0, 0.5, 0.0, 0.6),
+                custom_size: Some(size),
                 ..default()
             },
-            transform: Transform::from_translation(position.extend(0.0)),
+            transform: Transform::from_translation(position),
             ..default()
         },
-    )).id()
+    ));
 }
 
-pub fn spawn_ranged_enemy(
+pub fn enemy_projectile(damage: f32, speed: f32, range: f32) -> ProjectileSpec {
+    ProjectileSpec {
+        damage,
+        damage_type: DamageType::Physical,
+        speed,
+        range,
+        piercing: 0,
+        size: Vec2::new(10.0, 10.0),
+        color: Color::srgb(1.0, 0.5, 0.0),
+        homing: None,
+        status: None,
+    }
+}
+
+/// Roll the archetype's drops; elites scale them by their reward multiplier
+pub fn spawn_enemy_drops(
+    commands: &mut Commands,
+    archetype: &EnemyArchetype,
+    position: Vec2,
+    reward_multiplier: f32,
+    rng: &mut impl Rng,
+) {
+    for drop in archetype.drops.iter() {
+        if drop.chance >= 1.0 || rng.gen::<f32>() < drop.chance {
+            spawn_pickup(commands, drop.pickup, position, drop.value * reward_multiplier);
+        }
+    }
+}
+
+/// Patrol enemies start out walking their route; everyone else waits
+fn enemy_ai(behavior: EnemyBehavior) -> EnemyAI {
+    EnemyAI {
+        state: if behavior == EnemyBehavior::Patrol { AIState::Patrolling } else { AIState::Idle },
+        target: None,
+        last_known_position: None,
+        state_timer: FrameTimer::new(0),
+        strafe_sign: 1.0,
+        attack_index: 0,
+        phase: 0,
+    }
+}
+
+/// Spawn an enemy from its archetype, scaled to the room. Unknown ids spawn
+/// nothing.
+pub fn spawn_enemy(
     commands: &mut Commands,
+    archetypes: &EnemyArchetypes,
+    archetype_id: &str,
     position: Vec2,
-    enemy_type: EnemyType,
     room_number: u32,
-) -> Entity {
-    let base_health = match enemy_type {
-        EnemyType::CommonRanged => 150.0 + (room_number as f32 * 15.0),
-        EnemyType::EliteRanged => 800.0 + (room_number as f32 * 150.0),
-        _ => 150.0,
+    elite_modifier: Option<EliteModifier>,
+) -> Option<Entity> {
+    let Some(archetype) = archetypes.get(archetype_id) else {
+        warn!("Unknown enemy archetype '{}'", archetype_id);
+        return None;
     };
 
-    let damage = match enemy_type {
-        EnemyType::CommonRanged => 15.0 + (room_number as f32 * 1.5),
-        EnemyType::EliteRanged => 30.0 + (room_number as f32 * 3.0),
-        _ => 15.0,
+    let base_health = archetype.health.at(room_number);
+    let stats = CombatStats {
+        damage: archetype.damage.at(room_number),
+        crit_chance: archetype.crit_chance,
+        crit_damage: archetype.crit_damage,
+        attack_speed: archetype.attack_speed,
+        armor: 0.0,
+        damage_reduction: 0.0,
+        ..default()
+    };
+    let (elite, modifiers) = match elite_modifier {
+        Some(modifier) => {
+            let (elite, modifiers) = make_elite(modifier, base_health);
+            (Some(elite), modifiers)
+        }
+        None => (None, StatModifiers::default()),
+    };
+    let color = match &elite {
+        Some(elite) => elite_color(elite),
+        None => {
+            let [r, g, b] = archetype.sprite.color;
+            Color::srgb(r, g, b)
+        }
     };
 
-    commands.spawn((
+    let entity = commands.spawn((
         Enemy {
-            enemy_type,
-            behavior: EnemyBehavior::Defensive,
-            aggro_range: 400.0,
-            attack_range: 200.0,
-        },
-        EnemyAI {
-            state: AIState::Idle,
-            target: None,
-            last_known_position: None,
-            state_timer: Timer::from_seconds(0.0, TimerMode::Once),
+            archetype: archetype_id.to_string(),
+            enemy_type: archetype.enemy_type,
+            behavior: archetype.behavior,
+            aggro_range: archetype.aggro_range,
+            attack_range: archetype.attack_range,
         },
+        enemy_ai(archetype.behavior),
         Health::new(base_health),
-        CombatStats {
-            damage,
-            crit_chance: 0.1,
-            crit_damage: 2.0,
-            attack_speed: 0.8,
-            armor: 0.0,
-            damage_reduction: 0.0,
+        stats.clone(),
+        BaseStats {
+            combat: stats,
+            move_speed: archetype.move_speed,
+            max_health: base_health,
         },
+        modifiers,
         Velocity::default(),
+        StatusEffects::default(),
+        Team::Enemy,
         Hurtbox {
-            size: Vec2::new(25.0, 25.0),
+            size: archetype.sprite.size,
             invulnerable: false,
         },
         SpriteBundle {
             sprite: Sprite {
-                color: if matches!(enemy_type, EnemyType::EliteRanged) {
-                    Color::rgb(0.8, 0.8, 0.2)
-                } else {
-                    Color::rgb(0.6, 0.6, 0.2)
-                },
-                custom_size: Some(Vec2::new(25.0, 25.0)),
+                color,
+                custom_size: Some(archetype.sprite.size),
                 ..default()
             },
             transform: Transform::from_translation(position.extend(0.0)),
             ..default()
         },
-    )).id()
-}
\ No newline at end of file
+    )).id();
+
+    if let Some(elite) = elite {
+        commands.entity(entity).insert(elite);
+    }
+    Some(entity)
+}
@@ -0,0 +1,249 @@
+use bevy::prelude::*;
+use crate::components::*;
+use crate::resources::*;
+use crate::weapons::Weapons;
+
+// ============= Game Flow =============
+//
+// `CurrentGameState` drives the run from menu to death or victory. Screens are
+// spawned on `OnEnter` and scoped to their state, so leaving the state is all
+// it takes to tear them down. `PauseState` only exists while the player is in
+// a room; every gameplay plugin runs in `PauseState::Running`.
+
+pub struct GameFlowPlugin;
+
+impl Plugin for GameFlowPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<CurrentGameState>()
+            .add_sub_state::<PauseState>()
+            .enable_state_scoped_entities::<CurrentGameState>()
+            .enable_state_scoped_entities::<PauseState>()
+            .add_systems(OnEnter(CurrentGameState::MainMenu), (reset_run, spawn_main_menu).chain())
+            .add_systems(OnEnter(CurrentGameState::Death), spawn_death_screen)
+            .add_systems(OnEnter(CurrentGameState::Victory), spawn_victory_screen)
+            .add_systems(OnEnter(PauseState::Paused), spawn_pause_overlay)
+            .add_systems(Update, (
+                toggle_pause.run_if(state_exists::<PauseState>),
+                start_run.run_if(in_state(CurrentGameState::MainMenu)),
+                toggle_friendly_fire.run_if(in_state(CurrentGameState::MainMenu)),
+                refresh_main_menu
+                    .run_if(in_state(CurrentGameState::MainMenu))
+                    .run_if(
+                        resource_changed::<PlayerInventory>
+                            .or_else(resource_changed::<CombatSettings>)
+                            .or_else(resource_changed::<RoomGenerator>),
+                    ),
+                return_to_menu.run_if(
+                    in_state(CurrentGameState::Death).or_else(in_state(CurrentGameState::Victory)),
+                ),
+            ));
+    }
+}
+
+fn toggle_pause(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    pause_state: Res<State<PauseState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+) {
+    if !keyboard.just_pressed(KeyCode::Escape) {
+        return;
+    }
+
+    next_pause_state.set(match pause_state.get() {
+        PauseState::Running => PauseState::Paused,
+        PauseState::Paused => PauseState::Running,
+    });
+}
+
+fn start_run(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<CurrentGameState>>,
+) {
+    if keyboard.just_pressed(KeyCode::Enter) {
+        next_state.set(CurrentGameState::InRun);
+    }
+}
+
+fn return_to_menu(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<CurrentGameState>>,
+) {
+    if keyboard.just_pressed(KeyCode::Enter) {
+        next_state.set(CurrentGameState::MainMenu);
+    }
+}
+
+type RunEntityQuery<'w, 's> = Query<'w, 's, Entity, Or<(With<Room>, With<Enemy>, With<Projectile>, With<Pickup>, With<Hitbox>, With<DamageNumber>, With<LocalPlayer>)>>;
+
+/// Clear out whatever the last run left behind. Remote players stay, since
+/// their peers are still connected.
+fn reset_run(
+    mut commands: Commands,
+    run_entities: RunEntityQuery,
+) {
+    for entity in run_entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    commands.insert_resource(GameState::default());
+    commands.insert_resource(RunStats::default());
+    commands.insert_resource(PlayerInventory::default());
+    commands.insert_resource(RoomGenerator::default());
+    commands.insert_resource(WaveManager::default());
+    commands.insert_resource(RoomStatus::default());
+}
+
+// ============= Screens =============
+
+/// Full-screen overlay with a title and lines of text, torn down when `scope`
+/// is left
+pub fn spawn_screen(
+    commands: &mut Commands,
+    scope: impl States,
+    title: &str,
+    lines: Vec<String>,
+) -> Entity {
+    let mut sections = vec![TextSection::new(
+        format!("{}\n", title),
+        TextStyle {
+            font_size: 64.0,
+            color: Color::WHITE,
+            ..default()
+        },
+    )];
+    sections.extend(lines.into_iter().map(|line| {
+        TextSection::new(
+            format!("\n{}", line),
+            TextStyle {
+                font_size: 28.0,
+                color: Color::srgb(0.8, 0.8, 0.8),
+                ..default()
+            },
+        )
+    }));
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    position_type: PositionType::Absolute,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.7).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            StateScoped(scope),
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_sections(sections).with_text_justify(JustifyText::Center));
+        })
+        .id()
+}
+
+/// Only the host's setting matters, since only the host resolves hits
+fn toggle_friendly_fire(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<CombatSettings>,
+) {
+    if keyboard.just_pressed(KeyCode::KeyF) {
+        settings.friendly_fire = !settings.friendly_fire;
+    }
+}
+
+#[derive(Component)]
+struct MainMenuScreen;
+
+fn spawn_main_menu(
+    mut commands: Commands,
+    meta: Res<MetaProgression>,
+    weapons: Res<Weapons>,
+    inventory: Res<PlayerInventory>,
+    settings: Res<CombatSettings>,
+    room_gen: Res<RoomGenerator>,
+) {
+    let mut lines = vec!["Choose a weapon:".to_string()];
+    for (i, (id, weapon)) in weapons.unlocked(&meta).enumerate() {
+        let marker = if id == inventory.weapon { " <" } else { "" };
+        lines.push(format!("[{}] {}{}", i + 1, weapon.name, marker));
+    }
+    lines.push(String::new());
+    lines.push(format!("[F] Friendly fire: {}", if settings.friendly_fire { "On" } else { "Off" }));
+    lines.push(format!("Seed: {}", room_gen.seed));
+    lines.push("Press Enter to start a run".to_string());
+
+    let screen = spawn_screen(&mut commands, CurrentGameState::MainMenu, "Roguelike ARPG", lines);
+    commands.entity(screen).insert(MainMenuScreen);
+}
+
+/// Redraw the menu when the weapon pick, friendly fire or the host's seed changes
+fn refresh_main_menu(
+    mut commands: Commands,
+    screens: Query<Entity, With<MainMenuScreen>>,
+    meta: Res<MetaProgression>,
+    weapons: Res<Weapons>,
+    inventory: Res<PlayerInventory>,
+    settings: Res<CombatSettings>,
+    room_gen: Res<RoomGenerator>,
+) {
+    if screens.is_empty() {
+        return;
+    }
+    for screen in screens.iter() {
+        commands.entity(screen).despawn_recursive();
+    }
+    spawn_main_menu(commands, meta, weapons, inventory, settings, room_gen);
+}
+
+fn spawn_death_screen(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    run_stats: Res<RunStats>,
+    inventory: Res<PlayerInventory>,
+) {
+    spawn_screen(
+        &mut commands,
+        CurrentGameState::Death,
+        "You Died",
+        vec![
+            format!("Reached room {}", game_state.room_number),
+            format!("Enemies killed: {}", run_stats.enemies_killed),
+            format!("Gold: {}", inventory.gold),
+            format!("Time: {:.0}s", run_stats.run_time),
+            String::new(),
+            "Press Enter to return to the menu".to_string(),
+        ],
+    );
+}
+
+fn spawn_victory_screen(
+    mut commands: Commands,
+    run_stats: Res<RunStats>,
+    inventory: Res<PlayerInventory>,
+) {
+    spawn_screen(
+        &mut commands,
+        CurrentGameState::Victory,
+        "Victory",
+        vec![
+            format!("Enemies killed: {}", run_stats.enemies_killed),
+            format!("Gold: {}", inventory.gold),
+            format!("Time: {:.0}s", run_stats.run_time),
+            String::new(),
+            "Press Enter to return to the menu".to_string(),
+        ],
+    );
+}
+
+fn spawn_pause_overlay(mut commands: Commands) {
+    spawn_screen(
+        &mut commands,
+        PauseState::Paused,
+        "Paused",
+        vec!["Press Escape to resume".to_string()],
+    );
+}
@@ -1,11 +1,27 @@
 use bevy::prelude::*;
-use serde::{Deserialize, Serialize};
 use crate::components::*;
 use crate::resources::*;
 use crate::movement::MovementPlugin;
-use crate::combat::{CombatPlugin, cleanup_hitboxes};
+use crate::combat::CombatPlugin;
+use crate::archetypes::ArchetypePlugin;
 use crate::enemy::EnemyPlugin;
+use crate::boss::BossPlugin;
+use crate::navigation::NavigationPlugin;
 use crate::room::RoomPlugin;
+use crate::timestep::TimestepPlugin;
+use crate::flow::GameFlowPlugin;
+use crate::status::StatusPlugin;
+use crate::boons::BoonPlugin;
+use crate::stats::StatsPlugin;
+use crate::curses::CursePlugin;
+use crate::weapons::WeaponPlugin;
+use crate::combat::Dead;
+use crate::netcode::has_authority;
+use crate::network::{GameMessage, NetworkState, PeerMessageReceived};
+use crate::protocol;
+use crate::transport::ActiveTransport;
+
+const PICKUP_RANGE: f32 = 30.0;
 
 pub struct GamePlugin;
 
@@ -16,106 +32,129 @@ impl Plugin for GamePlugin {
             .init_resource::<PlayerInventory>()
             .init_resource::<RunStats>()
             .init_resource::<CombatLog>()
+            .init_resource::<CombatSettings>()
+            .init_resource::<MetaProgression>()
             .init_resource::<InputSettings>()
             .init_resource::<AudioSettings>()
             .init_resource::<PerformanceStats>();
         
         // Add sub-plugins for different systems
         app.add_plugins((
+            GameFlowPlugin,
             MovementPlugin,
             CombatPlugin,
+            ArchetypePlugin,
             EnemyPlugin,
+            BossPlugin,
+            NavigationPlugin,
             RoomPlugin,
+            StatusPlugin,
+            BoonPlugin,
+            StatsPlugin,
+            CursePlugin,
+            WeaponPlugin,
+            TimestepPlugin,
         ));
         
         // Add core game systems
         app.add_systems(Startup, setup_game)
             .add_systems(Update, (
-                handle_pickups,
+                handle_pickups.run_if(has_authority).run_if(in_state(PauseState::Running)),
+                receive_collected_pickups.run_if(not(has_authority)),
                 update_ui,
-                cleanup_hitboxes,
-                update_run_timer,
+                update_run_timer.run_if(in_state(PauseState::Running)),
             ).chain());
     }
 }
 
-#[derive(Resource, Default, Debug, Clone, Serialize, Deserialize)]
-pub struct GameState {
-    pub round: u32,
-    pub is_host: bool,
-    pub game_started: bool,
-    pub players: Vec<PlayerData>,
-}
+// New systems for the roguelike ARPG
 
-#[derive(Resource, Default)]
-pub struct PlayerList {
-    pub local_player_id: Option<String>,
-    pub players: Vec<Entity>,
-}
+type PickupCollectorQuery<'w, 's> = Query<'w, 's, (
+    &'static Player,
+    &'static Transform,
+    &'static mut Health,
+    &'static PlayerCurses,
+    Has<LocalPlayer>,
+), Without<Dead>>;
 
-#[derive(Component, Debug, Clone, Serialize, Deserialize)]
-pub struct Player {
-    pub id: String,
-    pub position: Vec2,
-    pub velocity: Vec2,
-    pub color: Color,
-    pub score: u32,
-    pub is_local: bool,
-}
+/// Host only. Healing lands on the player's `Health`, which snapshots carry
+/// back to its owner; gold and souls go to the owner's inventory.
+fn handle_pickups(
+    mut commands: Commands,
+    pickup_query: Query<(Entity, &Pickup, &Transform)>,
+    mut player_query: PickupCollectorQuery,
+    mut inventory: ResMut<PlayerInventory>,
+    mut run_stats: ResMut<RunStats>,
+    mut transport: Option<ResMut<ActiveTransport>>,
+) {
+    for (pickup_entity, pickup, pickup_transform) in pickup_query.iter() {
+        if !pickup.auto_collect {
+            continue;
+        }
+        let pickup_pos = pickup_transform.translation.truncate();
+        let Some((player, _, mut health, curses, is_local)) = player_query
+            .iter_mut()
+            .find(|(_, transform, ..)| transform.translation.truncate().distance(pickup_pos) < PICKUP_RANGE)
+        else {
+            continue;
+        };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PlayerData {
-    pub id: String,
-    pub position: Vec2,
-    pub velocity: Vec2,
-    pub color: [f32; 4],
-    pub score: u32,
-}
+        let amount = match pickup.pickup_type {
+            PickupType::Gold => (pickup.value * gold_find_multiplier(&curses.0)) as u32,
+            PickupType::Soul => pickup.value as u32,
+            PickupType::Health => {
+                let max_health = health.max;
+                health.heal(max_health * pickup.value);
+                0
+            }
+            PickupType::PowerUp => 0,
+        };
 
-#[derive(Component)]
-pub struct LocalPlayer;
+        if is_local {
+            credit_pickup(&mut inventory, &mut run_stats, pickup.pickup_type, amount);
+        } else if let Some(transport) = transport.as_mut() {
+            let message = GameMessage::PickupCollected { pickup_type: pickup.pickup_type, amount };
+            match protocol::encode(&message) {
+                Ok(payload) => transport.0.send(&player.id, &payload),
+                Err(e) => warn!("Failed to encode pickup: {}", e),
+            }
+        }
 
-#[derive(Component)]
-pub struct RemotePlayer {
-    pub id: String,
+        // Remove pickup
+        commands.entity(pickup_entity).despawn();
+    }
 }
 
-// New systems for the roguelike ARPG
-
-fn handle_pickups(
-    mut commands: Commands,
-    pickup_query: Query<(Entity, &Pickup, &Transform)>,
-    mut player_query: Query<(&Transform, &mut Health), With<LocalPlayer>>,
+/// Client: the host gave our player something
+fn receive_collected_pickups(
+    network_state: Res<NetworkState>,
+    mut messages: EventReader<PeerMessageReceived>,
     mut inventory: ResMut<PlayerInventory>,
     mut run_stats: ResMut<RunStats>,
 ) {
-    for (player_transform, mut health) in player_query.iter_mut() {
-        let player_pos = player_transform.translation.truncate();
-        
-        for (pickup_entity, pickup, pickup_transform) in pickup_query.iter() {
-            let pickup_pos = pickup_transform.translation.truncate();
-            let distance = player_pos.distance(pickup_pos);
-            
-            // Check if player is close enough to collect
-            if distance < 30.0 && pickup.auto_collect {
-                match pickup.pickup_type {
-                    PickupType::Gold => {
-                        inventory.gold += pickup.value as u32;
-                        run_stats.gold_collected += pickup.value as u32;
-                    }
-                    PickupType::Health => {
-                        health.heal(health.max * pickup.value);
-                    }
-                    PickupType::Soul => {
-                        inventory.souls += pickup.value as u32;
-                    }
-                    _ => {}
-                }
-                
-                // Remove pickup
-                commands.entity(pickup_entity).despawn();
-            }
+    for PeerMessageReceived { peer_id, message } in messages.read() {
+        if network_state.host_id.as_ref() != Some(peer_id) {
+            continue;
+        }
+        if let GameMessage::PickupCollected { pickup_type, amount } = message {
+            credit_pickup(&mut inventory, &mut run_stats, *pickup_type, *amount);
+        }
+    }
+}
+
+fn credit_pickup(inventory: &mut PlayerInventory, run_stats: &mut RunStats, pickup_type: PickupType, amount: u32) {
+    match pickup_type {
+        PickupType::Gold => {
+            inventory.gold += amount;
+            run_stats.gold_collected += amount;
+        }
+        PickupType::Health => {
+            inventory.curses.retain(|curse| !curse.lifts_on_heal());
+        }
+        PickupType::Soul => {
+            inventory.souls += amount;
         }
+        PickupType::PowerUp => {}
     }
 }
 
@@ -125,10 +164,16 @@ fn update_ui(
     inventory: Res<PlayerInventory>,
     run_stats: Res<RunStats>,
     current_room: Option<Res<CurrentRoom>>,
+    waves: Res<WaveManager>,
 ) {
     for mut text in text_query.iter_mut() {
-        let room_info = if let Some(room) = current_room {
-            format!("Enemies: {} | ", room.enemies_remaining)
+        let room_info = if let Some(room) = current_room.as_ref() {
+            let to_come = waves.spawn_queue.len() + waves.incoming.len();
+            if to_come > 0 {
+                format!("Enemies: {} (+{} to come) | ", room.enemies_remaining, to_come)
+            } else {
+                format!("Enemies: {} | ", room.enemies_remaining)
+            }
         } else {
             String::new()
         };
@@ -147,17 +192,11 @@ fn update_ui(
 fn update_run_timer(
     mut run_stats: ResMut<RunStats>,
     time: Res<Time>,
-    game_state: Res<GameState>,
 ) {
-    if matches!(game_state.current_state, CurrentGameState::InRun) {
-        run_stats.run_time += time.delta_seconds();
-    }
+    run_stats.run_time += time.delta_seconds();
 }
 
-fn setup_game(
-    mut commands: Commands,
-    mut game_state: ResMut<GameState>,
-) {
+fn setup_game(mut commands: Commands) {
     // Camera
     commands.spawn(Camera2dBundle::default());
 
@@ -179,156 +218,42 @@ fn setup_game(
         }),
     )
     .insert(GameInfoText);
-    
-    // Set initial game state
-    game_state.current_state = CurrentGameState::InRun;
 }
 
-#[derive(Component)]
-struct GameInfoText;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curses::make_curse;
 
-fn handle_player_input(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    time: Res<Time>,
-    mut query: Query<&mut Player, With<LocalPlayer>>,
-) {
-    for mut player in query.iter_mut() {
-        let mut direction = Vec2::ZERO;
-        
-        if keyboard_input.pressed(KeyCode::ArrowLeft) || keyboard_input.pressed(KeyCode::KeyA) {
-            direction.x -= 1.0;
-        }
-        if keyboard_input.pressed(KeyCode::ArrowRight) || keyboard_input.pressed(KeyCode::KeyD) {
-            direction.x += 1.0;
-        }
-        if keyboard_input.pressed(KeyCode::ArrowUp) || keyboard_input.pressed(KeyCode::KeyW) {
-            direction.y += 1.0;
-        }
-        if keyboard_input.pressed(KeyCode::ArrowDown) || keyboard_input.pressed(KeyCode::KeyS) {
-            direction.y -= 1.0;
-        }
+    #[test]
+    fn heals_lift_only_until_healed_curses() {
+        // (curse, still there after a heal)
+        let table = [
+            (CurseKind::Fragile, true),
+            (CurseKind::Slow, true),
+            (CurseKind::Blind, true),
+            (CurseKind::Cursed, false),
+            (CurseKind::Marked, true),
+            (CurseKind::Doomed, true),
+        ];
 
-        if direction != Vec2::ZERO {
-            direction = direction.normalize();
-            player.velocity = direction * 300.0; // Speed in pixels per second
-        } else {
-            player.velocity = Vec2::ZERO;
+        for (kind, kept) in table {
+            let mut inventory = PlayerInventory { curses: vec![make_curse(kind)], ..default() };
+            let mut run_stats = RunStats::default();
+            credit_pickup(&mut inventory, &mut run_stats, PickupType::Health, 25);
+            assert_eq!(!inventory.curses.is_empty(), kept, "{:?}", kind);
         }
     }
-}
 
-fn update_player_positions(
-    time: Res<Time>,
-    mut query: Query<(&mut Transform, &Player)>,
-) {
-    for (mut transform, player) in query.iter_mut() {
-        if player.velocity != Vec2::ZERO {
-            let new_position = transform.translation.xy() + player.velocity * time.delta_seconds();
-            
-            // Clamp to game boundaries
-            let clamped_x = new_position.x.clamp(-580.0, 580.0);
-            let clamped_y = new_position.y.clamp(-380.0, 380.0);
-            
-            transform.translation.x = clamped_x;
-            transform.translation.y = clamped_y;
-        }
-    }
-}
+    #[test]
+    fn gold_and_souls_are_credited() {
+        let mut inventory = PlayerInventory { curses: vec![make_curse(CurseKind::Cursed)], ..default() };
+        let mut run_stats = RunStats::default();
+        credit_pickup(&mut inventory, &mut run_stats, PickupType::Gold, 30);
+        credit_pickup(&mut inventory, &mut run_stats, PickupType::Soul, 2);
 
-fn sync_game_state(
-    game_state: Res<GameState>,
-    mut text_query: Query<&mut Text, With<GameInfoText>>,
-) {
-    for mut text in text_query.iter_mut() {
-        if game_state.game_started {
-            text.sections[0].value = format!(
-                "Round: {} | Players: {} | Host: {}",
-                game_state.round,
-                game_state.players.len(),
-                if game_state.is_host { "Yes" } else { "No" }
-            );
-        } else {
-            text.sections[0].value = format!(
-                "Waiting for players... ({} connected)",
-                game_state.players.len()
-            );
-        }
-    }
-}
-
-fn render_players(
-    mut commands: Commands,
-    game_state: Res<GameState>,
-    player_list: Res<PlayerList>,
-    mut existing_players: Query<(Entity, &mut Transform, &mut Sprite, &Player)>,
-) {
-    // Update existing players
-    for (entity, mut transform, mut sprite, player) in existing_players.iter_mut() {
-        if let Some(player_data) = game_state.players.iter().find(|p| p.id == player.id) {
-            transform.translation.x = player_data.position.x;
-            transform.translation.y = player_data.position.y;
-            sprite.color = Color::srgba(
-                player_data.color[0],
-                player_data.color[1],
-                player_data.color[2],
-                player_data.color[3],
-            );
-        }
+        assert_eq!((inventory.gold, run_stats.gold_collected, inventory.souls), (30, 30, 2));
+        // Only a heal lifts the drain
+        assert_eq!(inventory.curses.len(), 1);
     }
 }
-
-pub fn spawn_local_player(
-    commands: &mut Commands,
-    player_id: String,
-    position: Vec2,
-    color: Color,
-) -> Entity {
-    commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                color,
-                custom_size: Some(Vec2::new(30.0, 30.0)),
-                ..default()
-            },
-            transform: Transform::from_translation(position.extend(0.0)),
-            ..default()
-        },
-        Player {
-            id: player_id.clone(),
-            position,
-            velocity: Vec2::ZERO,
-            color,
-            score: 0,
-            is_local: true,
-        },
-        LocalPlayer,
-    )).id()
-}
-
-pub fn spawn_remote_player(
-    commands: &mut Commands,
-    player_id: String,
-    position: Vec2,
-    color: Color,
-) -> Entity {
-    commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                color,
-                custom_size: Some(Vec2::new(30.0, 30.0)),
-                ..default()
-            },
-            transform: Transform::from_translation(position.extend(0.0)),
-            ..default()
-        },
-        Player {
-            id: player_id.clone(),
-            position,
-            velocity: Vec2::ZERO,
-            color,
-            score: 0,
-            is_local: false,
-        },
-        RemotePlayer { id: player_id },
-    )).id()
-}
\ No newline at end of file
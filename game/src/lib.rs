@@ -1,21 +1,38 @@
 use bevy::prelude::*;
 use wasm_bindgen::prelude::*;
 
-mod game;
-mod network;
-mod systems;
-mod components;
-mod resources;
-mod combat;
-mod movement;
-mod enemy;
-mod room;
+pub mod game;
+pub mod network;
+pub mod negotiation;
+pub mod transport;
+pub mod protocol;
+pub mod netcode;
+pub mod replication;
+pub mod peer_sync;
+pub mod components;
+pub mod resources;
+pub mod combat;
+pub mod damage;
+pub mod movesets;
+pub mod weapons;
+pub mod projectiles;
+pub mod movement;
+pub mod timestep;
+pub mod flow;
+pub mod status;
+pub mod boons;
+pub mod stats;
+pub mod curses;
+pub mod archetypes;
+pub mod enemy;
+pub mod boss;
+pub mod navigation;
+pub mod room;
 
 use game::GamePlugin;
 use network::NetworkPlugin;
-
-#[cfg(target_arch = "wasm32")]
-use wasm_bindgen::JsCast;
+use netcode::NetcodePlugin;
+use replication::ReplicationPlugin;
 
 #[wasm_bindgen]
 pub fn run() {
@@ -39,5 +56,12 @@ pub fn run() {
         }))
         .add_plugins(GamePlugin)
         .add_plugins(NetworkPlugin)
+        .add_plugins(NetcodePlugin)
+        .add_plugins(ReplicationPlugin)
         .run();
-}
\ No newline at end of file
+}
+/// Switch outgoing network messages to JSON (or back) from the devtools console
+#[wasm_bindgen]
+pub fn set_network_debug(enabled: bool) {
+    protocol::set_json_debug(enabled);
+}
@@ -1,193 +1,281 @@
 use bevy::prelude::*;
+use bevy::input::InputSystem;
 use crate::components::*;
 use crate::resources::*;
+use crate::combat::Dead;
+use crate::replication::SnapshotBuffer;
 
 pub struct MovementPlugin;
 
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
-            handle_movement_input,
-            handle_dash_input,
-            update_dash_state,
-            apply_velocity,
-            clamp_to_arena,
-        ).chain().run_if(in_game));
+        app.init_resource::<LocalInputHistory>()
+            .init_resource::<LocalInputLatch>()
+            .add_systems(PreUpdate, latch_local_input.after(InputSystem).run_if(in_state(PauseState::Running)))
+            .add_systems(FixedUpdate, (
+                sample_local_input,
+                simulate_players,
+                apply_velocity,
+            ).chain().in_set(MovementSet).run_if(in_state(PauseState::Running)));
     }
 }
 
-fn in_game(game_state: Res<GameState>) -> bool {
-    matches!(game_state.current_state, CurrentGameState::InRun)
-        && !game_state.paused
+/// Sampling and simulating player input; netcode hooks in before and after it
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MovementSet;
+
+// Arena boundaries (adjust based on your arena size)
+pub const ARENA_HALF_WIDTH: f32 = 600.0;
+pub const ARENA_HALF_HEIGHT: f32 = 400.0;
+const PLAYER_RADIUS: f32 = 16.0;
+
+/// Local input gathered every render frame, ahead of that frame's simulation ticks.
+/// Presses are held until a tick consumes them, so a tap between two ticks
+/// isn't lost.
+#[derive(Resource, Default)]
+struct LocalInputLatch {
+    movement: Vec2,
+    dash: bool,
+    light_attack: bool,
+    heavy_attack: bool,
 }
 
-fn handle_movement_input(
+/// Read keyboard/gamepad state into the latch
+fn latch_local_input(
     keyboard: Res<ButtonInput<KeyCode>>,
     gamepads: Res<Gamepads>,
     button_inputs: Res<ButtonInput<GamepadButton>>,
     axes: Res<Axis<GamepadAxis>>,
-    mut query: Query<(
-        &mut Velocity,
-        &MovementStats,
-        &DashState,
-    ), With<LocalPlayer>>,
+    mut latch: ResMut<LocalInputLatch>,
 ) {
-    for (mut velocity, stats, dash_state) in query.iter_mut() {
-        // Skip movement input while dashing
-        if dash_state.is_dashing {
-            continue;
-        }
+    let mut movement = Vec2::ZERO;
 
-        let mut movement = Vec2::ZERO;
+    // Keyboard input (8-directional)
+    if keyboard.pressed(KeyCode::KeyW) || keyboard.pressed(KeyCode::ArrowUp) {
+        movement.y += 1.0;
+    }
+    if keyboard.pressed(KeyCode::KeyS) || keyboard.pressed(KeyCode::ArrowDown) {
+        movement.y -= 1.0;
+    }
+    if keyboard.pressed(KeyCode::KeyA) || keyboard.pressed(KeyCode::ArrowLeft) {
+        movement.x -= 1.0;
+    }
+    if keyboard.pressed(KeyCode::KeyD) || keyboard.pressed(KeyCode::ArrowRight) {
+        movement.x += 1.0;
+    }
 
-        // Keyboard input (8-directional)
-        if keyboard.pressed(KeyCode::KeyW) || keyboard.pressed(KeyCode::ArrowUp) {
-            movement.y += 1.0;
-        }
-        if keyboard.pressed(KeyCode::KeyS) || keyboard.pressed(KeyCode::ArrowDown) {
-            movement.y -= 1.0;
-        }
-        if keyboard.pressed(KeyCode::KeyA) || keyboard.pressed(KeyCode::ArrowLeft) {
-            movement.x -= 1.0;
-        }
-        if keyboard.pressed(KeyCode::KeyD) || keyboard.pressed(KeyCode::ArrowRight) {
-            movement.x += 1.0;
-        }
+    let mut dash = keyboard.just_pressed(KeyCode::Space) || keyboard.just_pressed(KeyCode::ShiftLeft);
+    let mut light_attack = keyboard.just_pressed(KeyCode::KeyJ);
+    let mut heavy_attack = keyboard.just_pressed(KeyCode::KeyK);
 
-        // Gamepad input
-        for gamepad in gamepads.iter() {
-            let left_stick_x = axes
-                .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
-                .unwrap_or(0.0);
-            let left_stick_y = axes
-                .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY))
-                .unwrap_or(0.0);
-
-            // Apply deadzone
-            if left_stick_x.abs() > 0.15 {
-                movement.x += left_stick_x;
-            }
-            if left_stick_y.abs() > 0.15 {
-                movement.y += left_stick_y;
-            }
-        }
+    // Gamepad input
+    for gamepad in gamepads.iter() {
+        let left_stick_x = axes
+            .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
+            .unwrap_or(0.0);
+        let left_stick_y = axes
+            .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY))
+            .unwrap_or(0.0);
 
-        // Normalize diagonal movement to maintain consistent speed
-        if movement.length() > 0.0 {
-            movement = movement.normalize();
+        // Apply deadzone
+        if left_stick_x.abs() > 0.15 {
+            movement.x += left_stick_x;
+        }
+        if left_stick_y.abs() > 0.15 {
+            movement.y += left_stick_y;
         }
 
-        velocity.linear = movement * stats.current_speed;
+        // A on Xbox / X on PlayStation dashes; West and North attack
+        dash |= button_inputs.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::South));
+        light_attack |= button_inputs.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::West));
+        heavy_attack |= button_inputs.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::North));
+    }
+
+    // Normalize diagonal movement to maintain consistent speed
+    if movement.length() > 0.0 {
+        movement = movement.normalize();
     }
+
+    latch.movement = movement;
+    latch.dash |= dash;
+    latch.light_attack |= light_attack;
+    latch.heavy_attack |= heavy_attack;
 }
 
-fn handle_dash_input(
-    keyboard: Res<ButtonInput<KeyCode>>,
-    gamepads: Res<Gamepads>,
-    button_inputs: Res<ButtonInput<GamepadButton>>,
-    time: Res<Time>,
-    mut query: Query<(
-        &mut DashState,
-        &mut Velocity,
-        &MovementStats,
-        &mut Hurtbox,
-        &mut InputBuffer,
-    ), With<LocalPlayer>>,
+/// Turn the latched input into this tick's `InputFrame` for the local player
+fn sample_local_input(
+    mut latch: ResMut<LocalInputLatch>,
+    mut history: ResMut<LocalInputHistory>,
+    mut query: Query<&mut PendingInputs, With<LocalPlayer>>,
 ) {
-    for (mut dash_state, mut velocity, stats, mut hurtbox, mut input_buffer) in query.iter_mut() {
-        // Check if dash is on cooldown
-        if !dash_state.cooldown_timer.finished() {
-            continue;
-        }
+    let Ok(mut pending) = query.get_single_mut() else {
+        return;
+    };
 
-        let mut should_dash = false;
+    let mut actions = Vec::new();
+    if latch.movement != Vec2::ZERO {
+        actions.push(InputAction::Move(latch.movement));
+    }
+    if latch.dash {
+        actions.push(InputAction::Dash);
+    }
+    if latch.light_attack {
+        actions.push(InputAction::LightAttack);
+    } else if latch.heavy_attack {
+        actions.push(InputAction::HeavyAttack);
+    }
+    latch.dash = false;
+    latch.light_attack = false;
+    latch.heavy_attack = false;
 
-        // Keyboard dash input (Space or Shift)
-        if keyboard.just_pressed(KeyCode::Space) || keyboard.just_pressed(KeyCode::ShiftLeft) {
-            should_dash = true;
-        }
+    let frame = InputFrame {
+        sequence: history.next_sequence(),
+        actions,
+    };
+    pending.frames.push_back(frame.clone());
+    history.record(frame);
+}
 
-        // Gamepad dash input (A button on Xbox, X on PlayStation)
-        for gamepad in gamepads.iter() {
-            if button_inputs.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::South)) {
-                should_dash = true;
-            }
-        }
+type SimulatedPlayerQuery<'w, 's> = Query<'w, 's, (
+    &'static mut PendingInputs,
+    &'static mut InputBuffer,
+    &'static MovementStats,
+    &'static mut DashState,
+    &'static mut Velocity,
+    &'static mut Hurtbox,
+    &'static mut Transform,
+    Has<Incapacitated>,
+    Has<Dead>,
+), With<Player>>;
 
-        // Check input buffer for dash
-        input_buffer.buffer.retain(|buffered| {
-            if buffered.action == InputAction::Dash 
-                && time.elapsed_seconds() - buffered.timestamp < input_buffer.max_buffer_time {
-                should_dash = true;
-                false // Remove from buffer
-            } else {
-                true // Keep in buffer
+/// Run every queued input frame for every player we simulate. On the host this
+/// covers remote players too; clients only queue frames for their own player.
+fn simulate_players(
+    mut query: SimulatedPlayerQuery,
+) {
+    for (mut pending, mut input_buffer, stats, mut dash_state, mut velocity, mut hurtbox, mut transform, incapacitated, dead) in query.iter_mut() {
+        while let Some(mut frame) = pending.frames.pop_front() {
+            pending.last_sequence = frame.sequence;
+
+            // Stunned, frozen or downed players still use up their frames, but do nothing
+            if incapacitated || dead {
+                frame.actions.clear();
             }
-        });
 
-        if should_dash && !dash_state.is_dashing {
-            // Start dash
-            dash_state.is_dashing = true;
-            dash_state.dash_timer.reset();
-            
-            // Dash in current movement direction, or last facing direction
-            let dash_dir = if velocity.linear.length() > 0.0 {
-                velocity.linear.normalize()
-            } else {
-                Vec2::new(1.0, 0.0) // Default to right if standing still
-            };
-            
-            dash_state.dash_direction = dash_dir;
-            velocity.linear = dash_dir * stats.dash_speed;
-            
-            // Enable i-frames during dash
-            if dash_state.has_iframes {
-                hurtbox.invulnerable = true;
+            // Attacks go through the input buffer so combat can chain them
+            for action in frame.actions.iter() {
+                if matches!(action, InputAction::LightAttack | InputAction::HeavyAttack) {
+                    input_buffer.buffer.push(BufferedInput {
+                        action: action.clone(),
+                        age: 0,
+                    });
+                }
             }
+
+            step_player(
+                &frame,
+                stats,
+                input_buffer.max_buffer_frames,
+                &mut dash_state,
+                &mut velocity,
+                &mut hurtbox,
+                &mut transform,
+            );
         }
+
+        // Age buffered inputs by one tick and drop the expired ones
+        let max_buffer_frames = input_buffer.max_buffer_frames;
+        input_buffer.buffer.retain_mut(|buffered| {
+            buffered.age += 1;
+            buffered.age < max_buffer_frames
+        });
     }
 }
 
-fn update_dash_state(
-    time: Res<Time>,
-    mut query: Query<(
-        &mut DashState,
-        &mut Velocity,
-        &MovementStats,
-        &mut Hurtbox,
-    ), With<LocalPlayer>>,
+/// Advance one player by one simulation tick. Shared by live simulation and by
+/// client-side replay after a correction, so both must stay in lockstep.
+pub fn step_player(
+    frame: &InputFrame,
+    stats: &MovementStats,
+    buffer_frames: u32,
+    dash_state: &mut DashState,
+    velocity: &mut Velocity,
+    hurtbox: &mut Hurtbox,
+    transform: &mut Transform,
 ) {
-    for (mut dash_state, mut velocity, stats, mut hurtbox) in query.iter_mut() {
-        // Update dash timer
-        if dash_state.is_dashing {
-            dash_state.dash_timer.tick(time.delta());
-            
-            if dash_state.dash_timer.finished() {
-                // End dash
-                dash_state.is_dashing = false;
-                dash_state.cooldown_timer.reset();
-                
-                // Remove i-frames
-                hurtbox.invulnerable = false;
-                
-                // Return to normal movement
-                velocity.linear = Vec2::ZERO;
-            } else {
-                // Maintain dash velocity
-                velocity.linear = dash_state.dash_direction * stats.dash_speed;
-            }
+    let movement = frame.movement();
+
+    // Skip movement input while dashing
+    if !dash_state.is_dashing {
+        velocity.linear = movement * stats.current_speed;
+    }
+
+    // Dashes pressed during cooldown are held for the buffer window
+    if frame.pressed(&InputAction::Dash) {
+        dash_state.buffered_dash = buffer_frames;
+    }
+
+    // Mid-attack, dashes wait for the move's dash-cancel window
+    if dash_state.buffered_dash > 0 && dash_state.cooldown_timer.finished() && !dash_state.is_dashing && !dash_state.attack_locked {
+        // Start dash
+        dash_state.is_dashing = true;
+        dash_state.buffered_dash = 0;
+        dash_state.dash_timer.reset();
+
+        // Dash in current movement direction, or default to right if standing still
+        let dash_dir = if movement.length() > 0.0 {
+            movement.normalize()
+        } else {
+            Vec2::new(1.0, 0.0)
+        };
+
+        dash_state.dash_direction = dash_dir;
+        velocity.linear = dash_dir * stats.dash_speed;
+
+        // Enable i-frames during dash
+        if dash_state.has_iframes {
+            hurtbox.invulnerable = true;
         }
-        
-        // Update cooldown timer
-        if !dash_state.cooldown_timer.finished() {
-            dash_state.cooldown_timer.tick(time.delta());
+    } else {
+        dash_state.buffered_dash = dash_state.buffered_dash.saturating_sub(1);
+    }
+
+    // Update dash timer
+    if dash_state.is_dashing {
+        dash_state.dash_timer.tick();
+
+        if dash_state.dash_timer.finished() {
+            // End dash
+            dash_state.is_dashing = false;
+            dash_state.cooldown_timer.reset();
+
+            // Remove i-frames
+            hurtbox.invulnerable = false;
+
+            // Return to normal movement
+            velocity.linear = Vec2::ZERO;
+        } else {
+            // Maintain dash velocity
+            velocity.linear = dash_state.dash_direction * stats.dash_speed;
         }
     }
+
+    // Update cooldown timer
+    if !dash_state.cooldown_timer.finished() {
+        dash_state.cooldown_timer.tick();
+    }
+
+    transform.translation.x += velocity.linear.x * FRAME_SECONDS;
+    transform.translation.y += velocity.linear.y * FRAME_SECONDS;
+    clamp_to_arena(transform);
 }
 
+type FreeBodyQuery<'w, 's> = Query<'w, 's, (&'static mut Transform, &'static Velocity), (Without<Player>, Without<SnapshotBuffer>)>;
+
+/// Players integrate inside `step_player`, and replicas are placed by interpolation
 fn apply_velocity(
     time: Res<Time>,
-    mut query: Query<(&mut Transform, &Velocity)>,
+    mut query: FreeBodyQuery,
 ) {
     for (mut transform, velocity) in query.iter_mut() {
         transform.translation.x += velocity.linear.x * time.delta_seconds();
@@ -195,62 +283,16 @@ fn apply_velocity(
     }
 }
 
-fn clamp_to_arena(
-    mut query: Query<&mut Transform, With<Player>>,
-) {
-    // Arena boundaries (adjust based on your arena size)
-    const ARENA_HALF_WIDTH: f32 = 600.0;
-    const ARENA_HALF_HEIGHT: f32 = 400.0;
-    const PLAYER_RADIUS: f32 = 16.0;
-
-    for mut transform in query.iter_mut() {
-        // Clamp X position
-        transform.translation.x = transform.translation.x.clamp(
-            -ARENA_HALF_WIDTH + PLAYER_RADIUS,
-            ARENA_HALF_WIDTH - PLAYER_RADIUS,
-        );
-        
-        // Clamp Y position
-        transform.translation.y = transform.translation.y.clamp(
-            -ARENA_HALF_HEIGHT + PLAYER_RADIUS,
-            ARENA_HALF_HEIGHT - PLAYER_RADIUS,
-        );
-    }
-}
+fn clamp_to_arena(transform: &mut Transform) {
+    // Clamp X position
+    transform.translation.x = transform.translation.x.clamp(
+        -ARENA_HALF_WIDTH + PLAYER_RADIUS,
+        ARENA_HALF_WIDTH - PLAYER_RADIUS,
+    );
 
-// System to handle input buffering
-pub fn buffer_input(
-    keyboard: Res<ButtonInput<KeyCode>>,
-    time: Res<Time>,
-    mut query: Query<&mut InputBuffer, With<LocalPlayer>>,
-) {
-    for mut input_buffer in query.iter_mut() {
-        // Buffer attack inputs
-        if keyboard.just_pressed(KeyCode::KeyJ) {
-            input_buffer.buffer.push(BufferedInput {
-                action: InputAction::LightAttack,
-                timestamp: time.elapsed_seconds(),
-            });
-        }
-        
-        if keyboard.just_pressed(KeyCode::KeyK) {
-            input_buffer.buffer.push(BufferedInput {
-                action: InputAction::HeavyAttack,
-                timestamp: time.elapsed_seconds(),
-            });
-        }
-        
-        if keyboard.just_pressed(KeyCode::Space) {
-            input_buffer.buffer.push(BufferedInput {
-                action: InputAction::Dash,
-                timestamp: time.elapsed_seconds(),
-            });
-        }
-        
-        // Clean up old buffered inputs
-        let current_time = time.elapsed_seconds();
-        input_buffer.buffer.retain(|buffered| {
-            current_time - buffered.timestamp < input_buffer.max_buffer_time
-        });
-    }
-}
\ No newline at end of file
+    // Clamp Y position
+    transform.translation.y = transform.translation.y.clamp(
+        -ARENA_HALF_HEIGHT + PLAYER_RADIUS,
+        ARENA_HALF_HEIGHT - PLAYER_RADIUS,
+    );
+}
@@ -0,0 +1,193 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::components::{AttackType, OnHitStatus};
+
+// ============= Move-Sets =============
+//
+// Weapon move-sets live in assets/movesets.json rather than in match arms, so
+// combo strings, frame data and cancel windows can be tuned without touching
+// Rust. Each move lists its startup/active/recovery frames, hitbox, damage
+// multiplier and knockback, plus:
+//
+//   cancels      - which input chains into which move, and the frames (counted
+//                  from the start of the move) where that chain is open
+//   dash_cancel  - the frames where a dash interrupts the move
+//
+// Projectile weapons also read `projectile` for how many shots fan out, how
+// many targets they pierce and whether they home. A move's optional `status`
+// goes on everything it hits, from its hitbox or its shots.
+//
+// Once a move finishes its chains stay open for `combo_reset_frames`; after
+// that the next press starts the string over from its starter.
+
+pub const DEFAULT_MOVE_SET: &str = "sword";
+
+const MOVE_SETS_JSON: &str = include_str!("../assets/movesets.json");
+
+/// Inclusive range of frames from the start of a move, e.g. `[12, 18]`
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct FrameWindow(pub u32, pub u32);
+
+impl FrameWindow {
+    /// Whether `frame` falls inside the window once every frame count is
+    /// stretched by `speed_multiplier`
+    pub fn contains(&self, frame: u32, speed_multiplier: f32) -> bool {
+        let scale = |frames: u32| (frames as f32 * speed_multiplier).round() as u32;
+        frame >= scale(self.0) && frame <= scale(self.1)
+    }
+}
+
+/// Ignored by projectile weapons, which may leave it out
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HitboxShape {
+    pub size: Vec2,
+    pub offset: Vec2,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CancelRule {
+    pub input: AttackType,
+    pub into: String,
+    pub window: FrameWindow,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Move {
+    pub name: String,
+    pub startup: u32,
+    pub active: u32,
+    pub recovery: u32,
+    #[serde(default)]
+    pub hitbox: HitboxShape,
+    #[serde(default = "default_damage_multiplier")]
+    pub damage_multiplier: f32,
+    #[serde(default)]
+    pub knockback: f32,
+    #[serde(default)]
+    pub dash_cancel: Option<FrameWindow>,
+    #[serde(default)]
+    pub cancels: Vec<CancelRule>,
+    #[serde(default)]
+    pub projectile: ProjectilePattern,
+    #[serde(default)]
+    pub status: Option<OnHitStatus>,
+}
+
+fn default_damage_multiplier() -> f32 {
+    1.0
+}
+
+/// What a projectile weapon fires for this move. Melee weapons ignore it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectilePattern {
+    #[serde(default = "default_projectile_count")]
+    pub count: u32,
+    #[serde(default)]
+    pub spread: f32, // Degrees the shots fan out across
+    #[serde(default)]
+    pub piercing: u32,
+    #[serde(default)]
+    pub homing: Option<f32>, // Turn rate in radians per second
+}
+
+impl Default for ProjectilePattern {
+    fn default() -> Self {
+        Self {
+            count: default_projectile_count(),
+            spread: 0.0,
+            piercing: 0,
+            homing: None,
+        }
+    }
+}
+
+fn default_projectile_count() -> u32 {
+    1
+}
+
+/// The move each input starts when no chain is open
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MoveStarters {
+    pub light: Option<String>,
+    pub heavy: Option<String>,
+    pub dash_attack: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MoveSet {
+    pub combo_reset_frames: u32,
+    pub starters: MoveStarters,
+    pub moves: Vec<Move>,
+}
+
+impl MoveSet {
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.moves.iter().position(|candidate| candidate.name == name)
+    }
+
+    pub fn starter(&self, input: AttackType) -> Option<usize> {
+        let name = match input {
+            AttackType::LightAttack => self.starters.light.as_deref(),
+            AttackType::HeavyAttack => self.starters.heavy.as_deref(),
+            AttackType::DashAttack => self.starters.dash_attack.as_deref(),
+            AttackType::Special => None,
+        };
+        name.and_then(|name| self.find(name))
+    }
+
+    /// The move `input` chains into from `current`, if that chain is open.
+    /// `frame` is None once `current` has finished and only the combo reset
+    /// window is left, in which case every chain is open.
+    pub fn chain(&self, current: usize, input: AttackType, frame: Option<u32>, speed_multiplier: f32) -> Option<usize> {
+        self.moves
+            .get(current)?
+            .cancels
+            .iter()
+            .filter(|rule| rule.input == input)
+            .find(|rule| frame.is_none_or(|frame| rule.window.contains(frame, speed_multiplier)))
+            .and_then(|rule| self.find(&rule.into))
+    }
+}
+
+/// Every move-set by name, parsed from the embedded asset at startup
+#[derive(Resource, Debug, Clone, Default)]
+pub struct MoveSets {
+    pub sets: HashMap<String, MoveSet>,
+}
+
+impl MoveSets {
+    pub fn load() -> Self {
+        match serde_json::from_str::<HashMap<String, MoveSet>>(MOVE_SETS_JSON) {
+            Ok(sets) => {
+                for (name, set) in sets.iter() {
+                    warn_unknown_moves(name, set);
+                }
+                Self { sets }
+            }
+            Err(e) => {
+                error!("Failed to parse assets/movesets.json: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&MoveSet> {
+        self.sets.get(name)
+    }
+}
+
+/// Catch typos in move names up front instead of on the first swing
+fn warn_unknown_moves(set_name: &str, set: &MoveSet) {
+    let starters = [&set.starters.light, &set.starters.heavy, &set.starters.dash_attack];
+    let targets = starters
+        .into_iter()
+        .flatten()
+        .chain(set.moves.iter().flat_map(|candidate| candidate.cancels.iter().map(|rule| &rule.into)));
+
+    for target in targets {
+        if set.find(target).is_none() {
+            warn!("Move-set '{}' refers to unknown move '{}'", set_name, target);
+        }
+    }
+}
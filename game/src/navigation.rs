@@ -0,0 +1,475 @@
+use bevy::prelude::*;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use crate::components::*;
+use crate::resources::*;
+use crate::combat::Dead;
+use crate::movement::{ARENA_HALF_HEIGHT, ARENA_HALF_WIDTH};
+use crate::netcode::has_authority;
+
+// ============= Navigation =============
+//
+// Enemies find their way over a coarse grid of the arena, rebuilt from the
+// room's `NavObstacle`s whenever they change: walls block cells, hazards only
+// make them expensive to cross. Each player gets a flow field of path costs
+// to them, recomputed only when they step into another cell, so chasing or
+// fleeing is a neighbour lookup per enemy however many there are. A field
+// over the 600-cell grid takes microseconds, well inside the AI budget.
+
+const CELL_SIZE: f32 = 40.0;
+const GRID_WIDTH: usize = (ARENA_HALF_WIDTH * 2.0 / CELL_SIZE) as usize;
+const GRID_HEIGHT: usize = (ARENA_HALF_HEIGHT * 2.0 / CELL_SIZE) as usize;
+const NAV_CLEARANCE: f32 = 15.0; // Obstacles grow by roughly an enemy's half-width
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+const HAZARD_COST_MULTIPLIER: u32 = 5;
+const UNREACHABLE: u32 = u32::MAX;
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        // Only the host moves enemies
+        app.init_resource::<Navigation>()
+            .add_systems(FixedUpdate, (
+                rebuild_nav_grid,
+                update_flow_fields,
+            ).chain()
+                .in_set(NavigationSet)
+                .run_if(in_state(PauseState::Running))
+                .run_if(has_authority));
+    }
+}
+
+/// Grid and flow field upkeep; enemy movement runs after it
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NavigationSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NavCell {
+    Open,
+    Hazard,
+    Blocked,
+}
+
+type Cell = (usize, usize);
+
+#[derive(Debug, Clone)]
+struct NavGrid {
+    cells: Vec<NavCell>,
+}
+
+impl Default for NavGrid {
+    fn default() -> Self {
+        Self {
+            cells: vec![NavCell::Open; GRID_WIDTH * GRID_HEIGHT],
+        }
+    }
+}
+
+impl NavGrid {
+    fn index((x, y): Cell) -> usize {
+        y * GRID_WIDTH + x
+    }
+
+    fn cell_at(position: Vec2) -> Option<Cell> {
+        let x = ((position.x + ARENA_HALF_WIDTH) / CELL_SIZE).floor();
+        let y = ((position.y + ARENA_HALF_HEIGHT) / CELL_SIZE).floor();
+        if x < 0.0 || y < 0.0 || x >= GRID_WIDTH as f32 || y >= GRID_HEIGHT as f32 {
+            return None;
+        }
+        Some((x as usize, y as usize))
+    }
+
+    fn center((x, y): Cell) -> Vec2 {
+        Vec2::new(
+            (x as f32 + 0.5) * CELL_SIZE - ARENA_HALF_WIDTH,
+            (y as f32 + 0.5) * CELL_SIZE - ARENA_HALF_HEIGHT,
+        )
+    }
+
+    fn get(&self, cell: Cell) -> NavCell {
+        self.cells[Self::index(cell)]
+    }
+
+    /// Mark every cell the box (grown by the clearance) touches, keeping the
+    /// worse of what's already there
+    fn mark(&mut self, center: Vec2, size: Vec2, marking: NavCell) {
+        let half = size / 2.0 + Vec2::splat(NAV_CLEARANCE);
+        let min = center - half + Vec2::new(ARENA_HALF_WIDTH, ARENA_HALF_HEIGHT);
+        let max = center + half + Vec2::new(ARENA_HALF_WIDTH, ARENA_HALF_HEIGHT);
+        if max.x < 0.0 || max.y < 0.0 {
+            return;
+        }
+        let x_range = (min.x / CELL_SIZE).floor().max(0.0) as usize..=((max.x / CELL_SIZE).floor() as usize).min(GRID_WIDTH - 1);
+        let y_range = (min.y / CELL_SIZE).floor().max(0.0) as usize..=((max.y / CELL_SIZE).floor() as usize).min(GRID_HEIGHT - 1);
+
+        for y in y_range {
+            for x in x_range.clone() {
+                let cell = &mut self.cells[Self::index((x, y))];
+                if marking == NavCell::Blocked || *cell == NavCell::Open {
+                    *cell = marking;
+                }
+            }
+        }
+    }
+
+    /// Walkable neighbours and the cost of stepping into them. Diagonals
+    /// can't cut a blocked corner.
+    fn neighbors(&self, (x, y): Cell) -> impl Iterator<Item = (Cell, u32)> + '_ {
+        const OFFSETS: [(i32, i32); 8] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+        let passable = move |dx: i32, dy: i32| -> Option<Cell> {
+            let nx = x as i32 + dx;
+            let ny = y as i32 + dy;
+            if nx < 0 || ny < 0 || nx >= GRID_WIDTH as i32 || ny >= GRID_HEIGHT as i32 {
+                return None;
+            }
+            let cell = (nx as usize, ny as usize);
+            (self.get(cell) != NavCell::Blocked).then_some(cell)
+        };
+
+        OFFSETS.into_iter().filter_map(move |(dx, dy)| {
+            let cell = passable(dx, dy)?;
+            let base = if dx != 0 && dy != 0 {
+                passable(dx, 0)?;
+                passable(0, dy)?;
+                DIAGONAL_COST
+            } else {
+                STRAIGHT_COST
+            };
+            let cost = match self.get(cell) {
+                NavCell::Hazard => base * HAZARD_COST_MULTIPLIER,
+                _ => base,
+            };
+            Some((cell, cost))
+        })
+    }
+
+    /// Whether walking straight from `from` to `to` only crosses open cells
+    fn is_clear(&self, from: Vec2, to: Vec2) -> bool {
+        let steps = (from.distance(to) / (CELL_SIZE / 4.0)).ceil().max(1.0) as usize;
+        (0..=steps).all(|step| {
+            let point = from.lerp(to, step as f32 / steps as f32);
+            Self::cell_at(point).is_some_and(|cell| self.get(cell) == NavCell::Open)
+        })
+    }
+}
+
+/// Path cost from every cell to one target cell
+#[derive(Debug, Clone)]
+struct FlowField {
+    target: Cell,
+    costs: Vec<u32>,
+}
+
+impl FlowField {
+    /// Dijkstra out from the target. The target's own cell counts even if
+    /// it's blocked, so a player hugging a wall can still be reached.
+    fn build(grid: &NavGrid, target: Cell) -> Self {
+        let mut costs = vec![UNREACHABLE; grid.cells.len()];
+        let mut frontier = BinaryHeap::new();
+        costs[NavGrid::index(target)] = 0;
+        frontier.push(Reverse((0, target)));
+
+        while let Some(Reverse((cost, cell))) = frontier.pop() {
+            if cost > costs[NavGrid::index(cell)] {
+                continue;
+            }
+            for (next, step) in grid.neighbors(cell) {
+                let next_cost = cost + step;
+                if next_cost < costs[NavGrid::index(next)] {
+                    costs[NavGrid::index(next)] = next_cost;
+                    frontier.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+
+        Self { target, costs }
+    }
+
+    fn cost(&self, cell: Cell) -> u32 {
+        self.costs[NavGrid::index(cell)]
+    }
+}
+
+/// The room's nav grid and a flow field to each player
+#[derive(Resource, Debug, Default)]
+pub struct Navigation {
+    grid: NavGrid,
+    fields: HashMap<Entity, FlowField>,
+}
+
+impl Navigation {
+    /// Which way to walk from `from` to reach `target`: straight when nothing
+    /// is in the way, otherwise down its flow field
+    pub fn toward(&self, from: Vec2, target: Entity, target_pos: Vec2) -> Vec2 {
+        let straight = (target_pos - from).normalize_or_zero();
+        if self.grid.is_clear(from, target_pos) {
+            return straight;
+        }
+        self.follow_field(from, target, true).unwrap_or(straight)
+    }
+
+    /// Which way to walk to get further from `target`, around obstacles
+    /// rather than into a wall
+    pub fn away(&self, from: Vec2, target: Entity, target_pos: Vec2) -> Vec2 {
+        let straight = (from - target_pos).normalize_or_zero();
+        self.follow_field(from, target, false).unwrap_or(straight)
+    }
+
+    /// Where something moving from `from` to `to` ends up without stepping
+    /// into a blocked cell, sliding along the wall if only one axis is in the
+    /// way. Anything already in a blocked cell moves freely so it can get out.
+    pub fn resolve_move(&self, from: Vec2, to: Vec2) -> Vec2 {
+        let blocked = |point: Vec2| NavGrid::cell_at(point).is_some_and(|cell| self.grid.get(cell) == NavCell::Blocked);
+        if !blocked(to) || blocked(from) {
+            return to;
+        }
+        [Vec2::new(to.x, from.y), Vec2::new(from.x, to.y)]
+            .into_iter()
+            .find(|slide| !blocked(*slide))
+            .unwrap_or(from)
+    }
+
+    /// Head for the neighbouring cell that's cheapest (downhill) or dearest
+    /// (uphill) to reach the target from
+    fn follow_field(&self, from: Vec2, target: Entity, downhill: bool) -> Option<Vec2> {
+        let field = self.fields.get(&target)?;
+        let cell = NavGrid::cell_at(from)?;
+        let here = field.cost(cell);
+
+        let reachable = self
+            .grid
+            .neighbors(cell)
+            .map(|(next, _)| (next, field.cost(next)))
+            .filter(|(_, cost)| *cost != UNREACHABLE);
+        let (next, cost) = if downhill {
+            reachable.min_by_key(|(_, cost)| *cost)?
+        } else {
+            reachable.max_by_key(|(_, cost)| *cost)?
+        };
+
+        let better = if downhill { cost < here } else { here != UNREACHABLE && cost > here };
+        better.then(|| (NavGrid::center(next) - from).normalize_or_zero())
+    }
+}
+
+/// Rasterize obstacles whenever the room's geometry changes. Rooms sit at
+/// the origin, so their children's local transforms are world positions.
+fn rebuild_nav_grid(
+    mut navigation: ResMut<Navigation>,
+    obstacle_query: Query<(&NavObstacle, &Transform)>,
+    added: Query<(), Added<NavObstacle>>,
+    mut removed: RemovedComponents<NavObstacle>,
+) {
+    let removed_any = removed.read().count() > 0;
+    if added.is_empty() && !removed_any {
+        return;
+    }
+
+    let mut grid = NavGrid::default();
+    for (obstacle, transform) in obstacle_query.iter() {
+        let marking = if obstacle.blocks { NavCell::Blocked } else { NavCell::Hazard };
+        grid.mark(transform.translation.truncate(), obstacle.size, marking);
+    }
+    navigation.grid = grid;
+    navigation.fields.clear();
+}
+
+type StandingPlayerQuery<'w, 's> = Query<'w, 's, (Entity, &'static Transform), (With<Player>, Without<Dead>)>;
+
+fn update_flow_fields(
+    mut navigation: ResMut<Navigation>,
+    player_query: StandingPlayerQuery,
+) {
+    let navigation = &mut *navigation;
+    navigation.fields.retain(|player, _| player_query.contains(*player));
+
+    for (player, transform) in player_query.iter() {
+        let Some(cell) = NavGrid::cell_at(transform.translation.truncate()) else {
+            continue;
+        };
+        if navigation.fields.get(&player).is_some_and(|field| field.target == cell) {
+            continue;
+        }
+        navigation.fields.insert(player, FlowField::build(&navigation.grid, cell));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: Entity = Entity::from_raw(7);
+
+    fn grid_with(blocked: &[Cell], hazards: &[Cell]) -> NavGrid {
+        let mut grid = NavGrid::default();
+        for &cell in blocked {
+            grid.cells[NavGrid::index(cell)] = NavCell::Blocked;
+        }
+        for &cell in hazards {
+            grid.cells[NavGrid::index(cell)] = NavCell::Hazard;
+        }
+        grid
+    }
+
+    fn navigation_to(grid: NavGrid, target: Cell) -> Navigation {
+        let field = FlowField::build(&grid, target);
+        Navigation {
+            grid,
+            fields: HashMap::from([(TARGET, field)]),
+        }
+    }
+
+    /// Cells visited walking the field downhill from `start` until it stops
+    fn walk(navigation: &Navigation, start: Cell) -> Vec<Cell> {
+        let mut path = vec![start];
+        let mut position = NavGrid::center(start);
+        while let Some(direction) = navigation.follow_field(position, TARGET, true) {
+            // Each step lands on the next cell's centre
+            let here = *path.last().unwrap();
+            let next = navigation
+                .grid
+                .neighbors(here)
+                .map(|(cell, _)| cell)
+                .find(|cell| (NavGrid::center(*cell) - position).normalize_or_zero().abs_diff_eq(direction, 1e-4))
+                .expect("direction points at a neighbour");
+            path.push(next);
+            position = NavGrid::center(next);
+            assert!(path.len() < GRID_WIDTH * GRID_HEIGHT, "walk never ended");
+        }
+        path
+    }
+
+    #[test]
+    fn marking_covers_the_box_plus_clearance() {
+        let mut grid = NavGrid::default();
+        // Inside one cell even after growing by the clearance
+        grid.mark(NavGrid::center((3, 3)), Vec2::ZERO, NavCell::Blocked);
+        // 80 wide centred on a cell edge, grown by 15 either side: 55 each way
+        grid.mark(NavGrid::center((10, 5)) + Vec2::new(CELL_SIZE / 2.0, 0.0), Vec2::new(80.0, 0.0), NavCell::Hazard);
+
+        let marked: Vec<(Cell, NavCell)> = (0..GRID_HEIGHT)
+            .flat_map(|y| (0..GRID_WIDTH).map(move |x| (x, y)))
+            .map(|cell| (cell, grid.get(cell)))
+            .filter(|(_, marking)| *marking != NavCell::Open)
+            .collect();
+        assert_eq!(
+            marked,
+            vec![
+                ((3, 3), NavCell::Blocked),
+                ((9, 5), NavCell::Hazard),
+                ((10, 5), NavCell::Hazard),
+                ((11, 5), NavCell::Hazard),
+                ((12, 5), NavCell::Hazard),
+            ]
+        );
+    }
+
+    #[test]
+    fn marking_keeps_the_worse_cell_and_clips_to_the_grid() {
+        let mut grid = NavGrid::default();
+        grid.mark(NavGrid::center((4, 4)), Vec2::ZERO, NavCell::Blocked);
+        grid.mark(NavGrid::center((4, 4)), Vec2::ZERO, NavCell::Hazard);
+        assert_eq!(grid.get((4, 4)), NavCell::Blocked);
+
+        grid.mark(NavGrid::center((6, 6)), Vec2::ZERO, NavCell::Hazard);
+        grid.mark(NavGrid::center((6, 6)), Vec2::ZERO, NavCell::Blocked);
+        assert_eq!(grid.get((6, 6)), NavCell::Blocked);
+
+        // Entirely off the grid, then overlapping its corner
+        grid.mark(Vec2::new(-ARENA_HALF_WIDTH - 200.0, 0.0), Vec2::splat(40.0), NavCell::Blocked);
+        grid.mark(Vec2::new(-ARENA_HALF_WIDTH, -ARENA_HALF_HEIGHT), Vec2::splat(20.0), NavCell::Blocked);
+        let blocked = grid.cells.iter().filter(|cell| **cell == NavCell::Blocked).count();
+        assert_eq!(blocked, 3);
+        assert_eq!(grid.get((0, 0)), NavCell::Blocked);
+    }
+
+    #[test]
+    fn field_costs_count_straight_and_diagonal_steps() {
+        let field = FlowField::build(&NavGrid::default(), (10, 10));
+        assert_eq!(field.cost((10, 10)), 0);
+        assert_eq!(field.cost((11, 10)), STRAIGHT_COST);
+        assert_eq!(field.cost((11, 11)), DIAGONAL_COST);
+        assert_eq!(field.cost((13, 10)), 3 * STRAIGHT_COST);
+        assert_eq!(field.cost((12, 13)), 2 * DIAGONAL_COST + STRAIGHT_COST);
+    }
+
+    #[test]
+    fn paths_route_around_a_wall() {
+        // A wall across x = 10 with one gap at the top
+        let wall: Vec<Cell> = (0..GRID_HEIGHT - 1).map(|y| (10, y)).collect();
+        let navigation = navigation_to(grid_with(&wall, &[]), (15, 5));
+
+        let start = (5, 5);
+        assert!(navigation.navigation_cost(start) > 10 * STRAIGHT_COST);
+        let path = walk(&navigation, start);
+        assert_eq!(path.last(), Some(&(15, 5)));
+        assert!(path.contains(&(10, GRID_HEIGHT - 1)), "goes through the gap");
+        assert!(path.iter().all(|cell| navigation.grid.get(*cell) != NavCell::Blocked));
+
+        // Sealing the gap leaves the far side unreachable
+        let wall: Vec<Cell> = (0..GRID_HEIGHT).map(|y| (10, y)).collect();
+        let field = FlowField::build(&grid_with(&wall, &[]), (15, 5));
+        assert_eq!(field.cost(start), UNREACHABLE);
+    }
+
+    #[test]
+    fn diagonals_never_cut_a_blocked_corner() {
+        let grid = grid_with(&[(6, 5)], &[]);
+        let neighbors: Vec<Cell> = grid.neighbors((5, 5)).map(|(cell, _)| cell).collect();
+        assert!(!neighbors.contains(&(6, 5)));
+        assert!(!neighbors.contains(&(6, 6)));
+        assert!(!neighbors.contains(&(6, 4)));
+        assert!(neighbors.contains(&(4, 6)));
+
+        // Two blocked orthogonals make the diagonal target a detour
+        let navigation = navigation_to(grid_with(&[(6, 5), (5, 6)], &[]), (6, 6));
+        assert!(navigation.navigation_cost((5, 5)) > DIAGONAL_COST);
+        let path = walk(&navigation, (5, 5));
+        assert_eq!(path.last(), Some(&(6, 6)));
+        for step in path.windows(2) {
+            let ((x0, y0), (x1, y1)) = (step[0], step[1]);
+            if x0 != x1 && y0 != y1 {
+                assert_ne!(navigation.grid.get((x1, y0)), NavCell::Blocked, "cut a corner at {:?}", step);
+                assert_ne!(navigation.grid.get((x0, y1)), NavCell::Blocked, "cut a corner at {:?}", step);
+            }
+        }
+    }
+
+    #[test]
+    fn hazards_cost_more_but_stay_passable() {
+        let field = FlowField::build(&grid_with(&[], &[(11, 10)]), (10, 10));
+        assert_eq!(field.cost((11, 10)), STRAIGHT_COST * HAZARD_COST_MULTIPLIER);
+
+        // Stepping around one hazard cell beats walking through it
+        let navigation = navigation_to(grid_with(&[], &[(11, 10)]), (10, 10));
+        assert_eq!(navigation.navigation_cost((12, 10)), 2 * DIAGONAL_COST);
+        assert!(!walk(&navigation, (12, 10)).contains(&(11, 10)));
+
+        // A hazard strip with no way round is crossed rather than avoided
+        let strip: Vec<Cell> = (0..GRID_HEIGHT).map(|y| (11, y)).collect();
+        let navigation = navigation_to(grid_with(&[], &strip), (10, 10));
+        assert_eq!(walk(&navigation, (12, 10)).last(), Some(&(10, 10)));
+    }
+
+    #[test]
+    fn moves_slide_along_blocked_cells() {
+        let navigation = navigation_to(grid_with(&[(6, 5)], &[]), (0, 0));
+        let from = NavGrid::center((5, 5)) + Vec2::new(15.0, 0.0);
+
+        // Straight into the wall stops; diagonally into it slides
+        assert_eq!(navigation.resolve_move(from, from + Vec2::new(10.0, 0.0)), from);
+        assert_eq!(navigation.resolve_move(from, from + Vec2::new(10.0, 8.0)), from + Vec2::new(0.0, 8.0));
+        // Open ground and escaping a blocked cell are untouched
+        assert_eq!(navigation.resolve_move(from, from - Vec2::new(10.0, 0.0)), from - Vec2::new(10.0, 0.0));
+        let inside = NavGrid::center((6, 5));
+        assert_eq!(navigation.resolve_move(inside, inside + Vec2::new(5.0, 0.0)), inside + Vec2::new(5.0, 0.0));
+    }
+
+    impl Navigation {
+        fn navigation_cost(&self, cell: Cell) -> u32 {
+            self.fields[&TARGET].cost(cell)
+        }
+    }
+}
@@ -0,0 +1,615 @@
+use std::collections::{HashMap, VecDeque};
+use crate::network::SignalingMessage;
+
+// ============= Negotiation State =============
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NegotiationState {
+    New,
+    Offering,   // Local offer sent, waiting for an answer
+    Answering,  // Remote offer applied, local answer in flight
+    Connected,
+    Failed,
+    Closed,
+}
+
+#[derive(Debug, Clone)]
+pub struct PeerNegotiation {
+    pub state: NegotiationState,
+    pub polite: bool,
+    pub has_remote_description: bool,
+    pub ignoring_offer: bool,
+    pub pending_candidates: Vec<String>,
+}
+
+impl PeerNegotiation {
+    pub fn new(polite: bool) -> Self {
+        Self {
+            state: NegotiationState::New,
+            polite,
+            has_remote_description: false,
+            ignoring_offer: false,
+            pending_candidates: Vec::new(),
+        }
+    }
+}
+
+/// The polite peer yields on offer glare; both sides agree on who that is from their ids
+pub fn is_polite(local_id: &str, peer_id: &str) -> bool {
+    local_id > peer_id
+}
+
+// ============= Transport Abstraction =============
+
+/// Results the transport reports back asynchronously (SDP creation, ICE, channel lifecycle)
+#[derive(Debug, Clone, PartialEq)]
+pub enum NegotiationEvent {
+    LocalOffer { peer_id: String, sdp: String },
+    LocalAnswer { peer_id: String, sdp: String },
+    LocalIceCandidate { peer_id: String, candidate: String },
+    ChannelOpen { peer_id: String },
+    ChannelClosed { peer_id: String },
+    ConnectionFailed { peer_id: String },
+}
+
+/// Side effects the state machine needs. `WebRtcNegotiator` implements this in the
+/// browser, `LoopbackNegotiation` implements it natively so negotiation runs without one.
+pub trait NegotiationTransport: Send + Sync {
+    fn create_offer(&mut self, peer_id: &str);
+    fn accept_offer(&mut self, peer_id: &str, sdp: &str);
+    fn accept_answer(&mut self, peer_id: &str, sdp: &str);
+    fn add_ice_candidate(&mut self, peer_id: &str, candidate: &str);
+    fn rollback(&mut self, peer_id: &str);
+    fn close(&mut self, peer_id: &str);
+    fn send_signaling(&mut self, message: SignalingMessage);
+    fn poll_events(&mut self) -> Vec<NegotiationEvent>;
+}
+
+// ============= State Machine =============
+
+#[derive(Default)]
+pub struct NegotiationTable {
+    peers: HashMap<String, PeerNegotiation>,
+    changes: Vec<(String, NegotiationState)>,
+}
+
+impl NegotiationTable {
+    pub fn get(&self, peer_id: &str) -> Option<&PeerNegotiation> {
+        self.peers.get(peer_id)
+    }
+
+    pub fn state(&self, peer_id: &str) -> Option<NegotiationState> {
+        self.peers.get(peer_id).map(|peer| peer.state)
+    }
+
+    pub fn connected_peers(&self) -> impl Iterator<Item = &String> {
+        self.peers
+            .iter()
+            .filter(|(_, peer)| peer.state == NegotiationState::Connected)
+            .map(|(peer_id, _)| peer_id)
+    }
+
+    /// State transitions since the last call, in the order they happened
+    pub fn drain_changes(&mut self) -> Vec<(String, NegotiationState)> {
+        std::mem::take(&mut self.changes)
+    }
+
+    pub fn on_signaling(
+        &mut self,
+        local_id: &str,
+        message: &SignalingMessage,
+        transport: &mut dyn NegotiationTransport,
+    ) {
+        match message {
+            SignalingMessage::RoomUpdate { players } => {
+                self.on_room_update(local_id, players, transport);
+            }
+            SignalingMessage::Leave { player_id } => {
+                self.close_peer(player_id, transport);
+            }
+            SignalingMessage::Offer { from, to, sdp } if to == local_id => {
+                self.on_remote_offer(local_id, from, sdp, transport);
+            }
+            SignalingMessage::Answer { from, to, sdp } if to == local_id => {
+                self.on_remote_answer(from, sdp, transport);
+            }
+            SignalingMessage::IceCandidate { from, to, candidate } if to == local_id => {
+                self.on_remote_candidate(from, candidate, transport);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn on_room_update(
+        &mut self,
+        local_id: &str,
+        players: &[String],
+        transport: &mut dyn NegotiationTransport,
+    ) {
+        for peer_id in players.iter().filter(|id| id.as_str() != local_id) {
+            let peer = self
+                .peers
+                .entry(peer_id.clone())
+                .or_insert_with(|| PeerNegotiation::new(is_polite(local_id, peer_id)));
+
+            let idle = matches!(
+                peer.state,
+                NegotiationState::New | NegotiationState::Failed | NegotiationState::Closed
+            );
+            if !idle {
+                continue;
+            }
+
+            if peer.polite {
+                // Polite peers wait for the offer
+                self.set_state(peer_id, NegotiationState::New);
+            } else {
+                self.start_offer(peer_id, transport);
+            }
+        }
+
+        // Anyone no longer listed has left the room
+        let departed: Vec<String> = self
+            .peers
+            .keys()
+            .filter(|peer_id| !players.contains(peer_id))
+            .cloned()
+            .collect();
+        for peer_id in departed {
+            self.close_peer(&peer_id, transport);
+        }
+    }
+
+    pub fn on_transport_event(
+        &mut self,
+        local_id: &str,
+        event: NegotiationEvent,
+        transport: &mut dyn NegotiationTransport,
+    ) {
+        match event {
+            NegotiationEvent::LocalOffer { peer_id, sdp } => {
+                // Drop offers that were rolled back by glare or a close in the meantime
+                if self.state(&peer_id) == Some(NegotiationState::Offering) {
+                    transport.send_signaling(SignalingMessage::Offer {
+                        from: local_id.to_string(),
+                        to: peer_id,
+                        sdp,
+                    });
+                }
+            }
+            NegotiationEvent::LocalAnswer { peer_id, sdp } => {
+                if self.state(&peer_id) == Some(NegotiationState::Answering) {
+                    transport.send_signaling(SignalingMessage::Answer {
+                        from: local_id.to_string(),
+                        to: peer_id,
+                        sdp,
+                    });
+                }
+            }
+            NegotiationEvent::LocalIceCandidate { peer_id, candidate } => {
+                let active = matches!(
+                    self.state(&peer_id),
+                    Some(NegotiationState::Offering | NegotiationState::Answering | NegotiationState::Connected)
+                );
+                if active {
+                    transport.send_signaling(SignalingMessage::IceCandidate {
+                        from: local_id.to_string(),
+                        to: peer_id,
+                        candidate,
+                    });
+                }
+            }
+            NegotiationEvent::ChannelOpen { peer_id } => {
+                if self.peers.contains_key(&peer_id) {
+                    self.set_state(&peer_id, NegotiationState::Connected);
+                }
+            }
+            NegotiationEvent::ChannelClosed { peer_id } => {
+                self.close_peer(&peer_id, transport);
+            }
+            NegotiationEvent::ConnectionFailed { peer_id } => {
+                if let Some(peer) = self.peers.get_mut(&peer_id) {
+                    peer.has_remote_description = false;
+                    peer.pending_candidates.clear();
+                    transport.close(&peer_id);
+                    self.set_state(&peer_id, NegotiationState::Failed);
+                }
+            }
+        }
+    }
+
+    fn start_offer(&mut self, peer_id: &str, transport: &mut dyn NegotiationTransport) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.has_remote_description = false;
+            peer.ignoring_offer = false;
+            peer.pending_candidates.clear();
+        }
+        self.set_state(peer_id, NegotiationState::Offering);
+        transport.create_offer(peer_id);
+    }
+
+    fn on_remote_offer(
+        &mut self,
+        local_id: &str,
+        peer_id: &str,
+        sdp: &str,
+        transport: &mut dyn NegotiationTransport,
+    ) {
+        let peer = self
+            .peers
+            .entry(peer_id.to_string())
+            .or_insert_with(|| PeerNegotiation::new(is_polite(local_id, peer_id)));
+
+        // Glare: both sides offered at once
+        let glare = peer.state == NegotiationState::Offering && !peer.has_remote_description;
+        peer.ignoring_offer = glare && !peer.polite;
+        if peer.ignoring_offer {
+            return;
+        }
+        if glare {
+            transport.rollback(peer_id);
+        }
+
+        peer.has_remote_description = true;
+        let pending = std::mem::take(&mut peer.pending_candidates);
+        self.set_state(peer_id, NegotiationState::Answering);
+
+        transport.accept_offer(peer_id, sdp);
+        for candidate in pending {
+            transport.add_ice_candidate(peer_id, &candidate);
+        }
+    }
+
+    fn on_remote_answer(&mut self, peer_id: &str, sdp: &str, transport: &mut dyn NegotiationTransport) {
+        let Some(peer) = self.peers.get_mut(peer_id) else {
+            return;
+        };
+        if peer.state != NegotiationState::Offering {
+            return; // Stale answer
+        }
+
+        peer.has_remote_description = true;
+        peer.ignoring_offer = false;
+        let pending = std::mem::take(&mut peer.pending_candidates);
+
+        transport.accept_answer(peer_id, sdp);
+        for candidate in pending {
+            transport.add_ice_candidate(peer_id, &candidate);
+        }
+    }
+
+    fn on_remote_candidate(&mut self, peer_id: &str, candidate: &str, transport: &mut dyn NegotiationTransport) {
+        let Some(peer) = self.peers.get_mut(peer_id) else {
+            return;
+        };
+        if peer.ignoring_offer || peer.state == NegotiationState::Closed {
+            return;
+        }
+
+        // Candidates can outrun the description they belong to
+        if peer.has_remote_description {
+            transport.add_ice_candidate(peer_id, candidate);
+        } else {
+            peer.pending_candidates.push(candidate.to_string());
+        }
+    }
+
+    fn close_peer(&mut self, peer_id: &str, transport: &mut dyn NegotiationTransport) {
+        let Some(peer) = self.peers.get_mut(peer_id) else {
+            return;
+        };
+        if peer.state == NegotiationState::Closed {
+            return;
+        }
+
+        peer.has_remote_description = false;
+        peer.pending_candidates.clear();
+        transport.close(peer_id);
+        self.set_state(peer_id, NegotiationState::Closed);
+    }
+
+    fn set_state(&mut self, peer_id: &str, state: NegotiationState) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            if peer.state != state {
+                peer.state = state;
+                self.changes.push((peer_id.to_string(), state));
+            }
+        }
+    }
+}
+
+// ============= Loopback Fake =============
+
+/// In-memory transport: SDP "succeeds" immediately and outgoing signaling is
+/// collected so a harness can hand it to another peer's table
+#[derive(Default)]
+pub struct LoopbackNegotiation {
+    pub outbox: Vec<SignalingMessage>,
+    pub closed: Vec<String>,
+    events: VecDeque<NegotiationEvent>,
+}
+
+impl LoopbackNegotiation {
+    pub fn drain_outbox(&mut self) -> Vec<SignalingMessage> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// Simulate a network-level failure reported by the browser
+    pub fn fail(&mut self, peer_id: &str) {
+        self.events.push_back(NegotiationEvent::ConnectionFailed {
+            peer_id: peer_id.to_string(),
+        });
+    }
+}
+
+impl NegotiationTransport for LoopbackNegotiation {
+    fn create_offer(&mut self, peer_id: &str) {
+        self.events.push_back(NegotiationEvent::LocalOffer {
+            peer_id: peer_id.to_string(),
+            sdp: format!("loopback-offer:{}", peer_id),
+        });
+        self.events.push_back(NegotiationEvent::LocalIceCandidate {
+            peer_id: peer_id.to_string(),
+            candidate: format!("loopback-candidate:{}", peer_id),
+        });
+    }
+
+    fn accept_offer(&mut self, peer_id: &str, _sdp: &str) {
+        self.events.push_back(NegotiationEvent::LocalAnswer {
+            peer_id: peer_id.to_string(),
+            sdp: format!("loopback-answer:{}", peer_id),
+        });
+        // Answerer's channel opens once its answer is out
+        self.events.push_back(NegotiationEvent::ChannelOpen {
+            peer_id: peer_id.to_string(),
+        });
+    }
+
+    fn accept_answer(&mut self, peer_id: &str, _sdp: &str) {
+        self.events.push_back(NegotiationEvent::ChannelOpen {
+            peer_id: peer_id.to_string(),
+        });
+    }
+
+    fn add_ice_candidate(&mut self, _peer_id: &str, _candidate: &str) {}
+
+    fn rollback(&mut self, _peer_id: &str) {}
+
+    fn close(&mut self, peer_id: &str) {
+        self.closed.push(peer_id.to_string());
+    }
+
+    fn send_signaling(&mut self, message: SignalingMessage) {
+        self.outbox.push(message);
+    }
+
+    fn poll_events(&mut self) -> Vec<NegotiationEvent> {
+        self.events.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "player_0000000a";
+    const BOB: &str = "player_0000000b";
+
+    struct Side {
+        id: &'static str,
+        table: NegotiationTable,
+        transport: LoopbackNegotiation,
+    }
+
+    impl Side {
+        fn new(id: &'static str) -> Self {
+            Self {
+                id,
+                table: NegotiationTable::default(),
+                transport: LoopbackNegotiation::default(),
+            }
+        }
+
+        fn room_update(&mut self, players: &[&str]) {
+            let players: Vec<String> = players.iter().map(|id| id.to_string()).collect();
+            self.table.on_room_update(self.id, &players, &mut self.transport);
+        }
+
+        fn process_events(&mut self) {
+            for event in self.transport.poll_events() {
+                self.table.on_transport_event(self.id, event, &mut self.transport);
+            }
+        }
+
+        fn receive(&mut self, messages: Vec<SignalingMessage>) {
+            for message in messages {
+                self.table.on_signaling(self.id, &message, &mut self.transport);
+            }
+        }
+    }
+
+    /// Run both sides until neither has events or signaling left
+    fn pump(a: &mut Side, b: &mut Side) {
+        for _ in 0..16 {
+            a.process_events();
+            b.process_events();
+            let to_b = a.transport.drain_outbox();
+            let to_a = b.transport.drain_outbox();
+            if to_a.is_empty() && to_b.is_empty() && a.transport.events.is_empty() && b.transport.events.is_empty() {
+                return;
+            }
+            b.receive(to_b);
+            a.receive(to_a);
+        }
+        panic!("negotiation did not settle");
+    }
+
+    /// Both sides have sent an offer to each other and neither has arrived yet
+    fn glare() -> (Side, Side, Vec<SignalingMessage>, Vec<SignalingMessage>) {
+        let mut alice = Side::new(ALICE);
+        let mut bob = Side::new(BOB);
+        alice.room_update(&[ALICE, BOB]);
+        bob.room_update(&[ALICE, BOB]);
+        // The polite side would normally wait; force it to offer as well
+        bob.table.start_offer(ALICE, &mut bob.transport);
+
+        alice.process_events();
+        bob.process_events();
+        let from_alice = alice.transport.drain_outbox();
+        let from_bob = bob.transport.drain_outbox();
+        assert!(matches!(from_alice[0], SignalingMessage::Offer { .. }));
+        assert!(matches!(from_bob[0], SignalingMessage::Offer { .. }));
+        (alice, bob, from_alice, from_bob)
+    }
+
+    #[test]
+    fn exactly_one_side_is_polite() {
+        assert_ne!(is_polite(ALICE, BOB), is_polite(BOB, ALICE));
+        assert!(is_polite(BOB, ALICE));
+    }
+
+    #[test]
+    fn impolite_side_offers_and_both_connect() {
+        let mut alice = Side::new(ALICE);
+        let mut bob = Side::new(BOB);
+
+        alice.room_update(&[ALICE, BOB]);
+        bob.room_update(&[ALICE, BOB]);
+        assert_eq!(alice.table.state(BOB), Some(NegotiationState::Offering));
+        assert_eq!(bob.table.state(ALICE), Some(NegotiationState::New));
+
+        pump(&mut alice, &mut bob);
+
+        assert_eq!(alice.table.state(BOB), Some(NegotiationState::Connected));
+        assert_eq!(bob.table.state(ALICE), Some(NegotiationState::Connected));
+        assert_eq!(
+            alice.table.drain_changes(),
+            vec![
+                (BOB.to_string(), NegotiationState::Offering),
+                (BOB.to_string(), NegotiationState::Connected),
+            ]
+        );
+        assert_eq!(
+            bob.table.drain_changes(),
+            vec![
+                (ALICE.to_string(), NegotiationState::Answering),
+                (ALICE.to_string(), NegotiationState::Connected),
+            ]
+        );
+        assert_eq!(alice.table.connected_peers().collect::<Vec<_>>(), vec![BOB]);
+    }
+
+    #[test]
+    fn candidates_before_the_offer_are_queued() {
+        let mut alice = Side::new(ALICE);
+        let mut bob = Side::new(BOB);
+        alice.room_update(&[ALICE, BOB]);
+        bob.room_update(&[ALICE, BOB]);
+        alice.process_events();
+
+        // Deliver the candidate ahead of the offer it belongs to
+        let mut from_alice = alice.transport.drain_outbox();
+        from_alice.reverse();
+        bob.receive(vec![from_alice.remove(0)]);
+        assert_eq!(bob.table.get(ALICE).unwrap().pending_candidates.len(), 1);
+
+        bob.receive(from_alice);
+        assert!(bob.table.get(ALICE).unwrap().pending_candidates.is_empty());
+        pump(&mut alice, &mut bob);
+        assert_eq!(bob.table.state(ALICE), Some(NegotiationState::Connected));
+    }
+
+    #[test]
+    fn impolite_side_ignores_glare_offer() {
+        let (mut alice, _bob, _from_alice, from_bob) = glare();
+
+        alice.receive(from_bob);
+
+        let peer = alice.table.get(BOB).unwrap();
+        assert_eq!(peer.state, NegotiationState::Offering);
+        assert!(peer.ignoring_offer);
+        // The candidate that came with the ignored offer is dropped too
+        assert!(peer.pending_candidates.is_empty());
+        assert!(alice.transport.drain_outbox().is_empty());
+    }
+
+    #[test]
+    fn polite_side_yields_on_glare_and_both_connect() {
+        let (mut alice, mut bob, from_alice, from_bob) = glare();
+
+        bob.receive(from_alice);
+        assert_eq!(bob.table.state(ALICE), Some(NegotiationState::Answering));
+        alice.receive(from_bob);
+
+        pump(&mut alice, &mut bob);
+
+        assert_eq!(alice.table.state(BOB), Some(NegotiationState::Connected));
+        assert_eq!(bob.table.state(ALICE), Some(NegotiationState::Connected));
+        assert!(!alice.table.get(BOB).unwrap().ignoring_offer);
+    }
+
+    #[test]
+    fn failure_closes_the_connection_and_room_update_retries() {
+        let mut alice = Side::new(ALICE);
+        let mut bob = Side::new(BOB);
+        alice.room_update(&[ALICE, BOB]);
+        bob.room_update(&[ALICE, BOB]);
+        pump(&mut alice, &mut bob);
+        alice.table.drain_changes();
+
+        alice.transport.fail(BOB);
+        alice.process_events();
+        assert_eq!(alice.table.state(BOB), Some(NegotiationState::Failed));
+        assert_eq!(alice.transport.closed, vec![BOB.to_string()]);
+        assert_eq!(alice.table.drain_changes(), vec![(BOB.to_string(), NegotiationState::Failed)]);
+
+        // Failed peers are idle, so the next roster starts a fresh offer
+        alice.room_update(&[ALICE, BOB]);
+        assert_eq!(alice.table.state(BOB), Some(NegotiationState::Offering));
+        pump(&mut alice, &mut bob);
+        assert_eq!(alice.table.state(BOB), Some(NegotiationState::Connected));
+        assert_eq!(bob.table.state(ALICE), Some(NegotiationState::Connected));
+    }
+
+    #[test]
+    fn leave_closes_the_peer_once() {
+        let mut alice = Side::new(ALICE);
+        let mut bob = Side::new(BOB);
+        alice.room_update(&[ALICE, BOB]);
+        bob.room_update(&[ALICE, BOB]);
+        pump(&mut alice, &mut bob);
+        bob.table.drain_changes();
+
+        bob.receive(vec![SignalingMessage::Leave { player_id: ALICE.to_string() }]);
+        // The roster that follows a Leave must not close it a second time
+        bob.room_update(&[BOB]);
+
+        assert_eq!(bob.table.state(ALICE), Some(NegotiationState::Closed));
+        assert_eq!(bob.transport.closed, vec![ALICE.to_string()]);
+        assert_eq!(bob.table.drain_changes(), vec![(ALICE.to_string(), NegotiationState::Closed)]);
+        assert_eq!(bob.table.connected_peers().count(), 0);
+    }
+
+    #[test]
+    fn stale_signaling_after_close_is_ignored() {
+        let mut alice = Side::new(ALICE);
+        let mut bob = Side::new(BOB);
+        alice.room_update(&[ALICE, BOB]);
+        bob.room_update(&[ALICE, BOB]);
+        alice.process_events();
+        let from_alice = alice.transport.drain_outbox();
+
+        // Alice's channel closes before her offer is answered
+        alice.table.on_transport_event(
+            ALICE,
+            NegotiationEvent::ChannelClosed { peer_id: BOB.to_string() },
+            &mut alice.transport,
+        );
+        bob.receive(from_alice);
+        bob.process_events();
+        alice.receive(bob.transport.drain_outbox());
+
+        assert_eq!(alice.table.state(BOB), Some(NegotiationState::Closed));
+        assert!(alice.transport.events.is_empty());
+    }
+}
@@ -0,0 +1,333 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::combat::Dead;
+use crate::components::*;
+use crate::movement::{step_player, MovementSet};
+use crate::network::{GameMessage, NetworkState, PeerConnected, PeerDisconnected, PeerMessageReceived};
+use crate::protocol;
+use crate::resources::LocalInputHistory;
+use crate::room::spawn_player;
+use crate::transport::ActiveTransport;
+
+// ============= Host-Authoritative Simulation =============
+//
+// Every peer samples its own input into numbered `InputFrame`s and simulates
+// them immediately (prediction). Clients also send those frames to the host,
+// which runs them for their player and reports the result in each snapshot.
+// When a client receives its authoritative state it snaps to it, drops the
+// frames the host has acknowledged and replays the rest on top.
+
+// Unacknowledged frames resent with every input message, to ride out packet loss
+const INPUT_REDUNDANCY: usize = 8;
+// How many ticks a remote player's input clock may run ahead of the host's
+const MAX_INPUT_BACKLOG_FRAMES: u32 = 15;
+
+pub struct NetcodePlugin;
+
+impl Plugin for NetcodePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, track_peers)
+            .add_systems(FixedUpdate, (
+                receive_remote_inputs,
+                reconcile_local_player,
+            ).chain().before(MovementSet))
+            .add_systems(FixedUpdate, send_local_inputs.after(MovementSet));
+    }
+}
+
+/// Run condition for systems whose results only the host may decide
+pub fn has_authority(network_state: Res<NetworkState>) -> bool {
+    network_state.is_host()
+}
+
+/// Simulation ticks a remote player may still spend. Stops clients from
+/// fast-forwarding themselves by sending extra frames.
+#[derive(Component, Default)]
+pub struct InputBudget {
+    frames: u32,
+}
+
+/// Authoritative state of one player after the host simulated it up to `last_input`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlayerSnapshot {
+    pub player_id: String,
+    pub last_input: u32,
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub health: f32,
+    pub is_dashing: bool,
+    pub dash_direction: [f32; 2],
+    pub dash_elapsed: u32,
+    pub dash_cooldown_elapsed: u32,
+    pub buffered_dash: u32,
+    pub invulnerable: bool,
+    pub dead: bool,
+    // Statuses only tick on the host, so prediction takes their effect from here
+    pub move_speed: f32,
+    pub incapacitated: bool,
+}
+
+impl PlayerSnapshot {
+    pub fn capture(
+        player: &Player,
+        pending: &PendingInputs,
+        health: &Health,
+        dash_state: &DashState,
+        velocity: &Velocity,
+        hurtbox: &Hurtbox,
+        transform: &Transform,
+    ) -> Self {
+        Self {
+            player_id: player.id.clone(),
+            last_input: pending.last_sequence,
+            position: [transform.translation.x, transform.translation.y],
+            velocity: [velocity.linear.x, velocity.linear.y],
+            health: health.current,
+            is_dashing: dash_state.is_dashing,
+            dash_direction: [dash_state.dash_direction.x, dash_state.dash_direction.y],
+            dash_elapsed: dash_state.dash_timer.elapsed(),
+            dash_cooldown_elapsed: dash_state.cooldown_timer.elapsed(),
+            buffered_dash: dash_state.buffered_dash,
+            invulnerable: hurtbox.invulnerable,
+            // The host fills these in from `Dead`, `MovementStats` and `Incapacitated`
+            dead: false,
+            move_speed: 0.0,
+            incapacitated: false,
+        }
+    }
+
+    fn restore_dash(&self, dash_state: &mut DashState) {
+        dash_state.is_dashing = self.is_dashing;
+        dash_state.dash_direction = Vec2::from(self.dash_direction);
+        dash_state.buffered_dash = self.buffered_dash;
+        dash_state.dash_timer.set_elapsed(self.dash_elapsed);
+        dash_state.cooldown_timer.set_elapsed(self.dash_cooldown_elapsed);
+    }
+}
+
+/// Spawn and despawn remote player bodies, and re-elect the host. Everyone
+/// picks the lowest id among the connected players, so all peers agree
+/// without exchanging any messages.
+fn track_peers(
+    mut commands: Commands,
+    mut connected: EventReader<PeerConnected>,
+    mut disconnected: EventReader<PeerDisconnected>,
+    mut network_state: ResMut<NetworkState>,
+    transport: Option<Res<ActiveTransport>>,
+    players: Query<(Entity, &Player)>,
+) {
+    let mut spawned: HashMap<String, Entity> = HashMap::new();
+    let mut changed = false;
+
+    for event in connected.read() {
+        changed = true;
+        if spawned.contains_key(&event.peer_id) || players.iter().any(|(_, player)| player.id == event.peer_id) {
+            continue;
+        }
+        let entity = spawn_player(&mut commands, &event.peer_id, Vec2::ZERO, false);
+        commands.entity(entity).insert(InputBudget::default());
+        spawned.insert(event.peer_id.clone(), entity);
+    }
+
+    for event in disconnected.read() {
+        changed = true;
+        if let Some(entity) = spawned.remove(&event.peer_id) {
+            commands.entity(entity).despawn_recursive();
+        }
+        for (entity, player) in players.iter() {
+            if player.id == event.peer_id && !player.is_local {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+
+    if !changed {
+        return;
+    }
+
+    let peers = transport.map(|transport| transport.0.peers()).unwrap_or_default();
+    network_state.host_id = if peers.is_empty() {
+        None
+    } else {
+        peers.into_iter().chain(std::iter::once(network_state.player_id.clone())).min()
+    };
+}
+
+/// Host only: queue clients' input frames on their players for `simulate_players`
+fn receive_remote_inputs(
+    network_state: Res<NetworkState>,
+    mut messages: EventReader<PeerMessageReceived>,
+    mut players: Query<(&Player, &mut PendingInputs, &mut InputBudget), Without<LocalPlayer>>,
+) {
+    if !network_state.is_host() {
+        messages.clear();
+        return;
+    }
+
+    for (_, _, mut budget) in players.iter_mut() {
+        budget.frames = (budget.frames + 1).min(MAX_INPUT_BACKLOG_FRAMES);
+    }
+
+    for PeerMessageReceived { peer_id, message } in messages.read() {
+        let GameMessage::PlayerInput { player_id, frames } = message else {
+            continue;
+        };
+        // Peers may only drive their own player
+        if player_id != peer_id {
+            continue;
+        }
+        let Some((_, mut pending, mut budget)) = players.iter_mut().find(|(player, ..)| player.id == *player_id) else {
+            continue;
+        };
+
+        let mut newest = pending.frames.back().map_or(pending.last_sequence, |frame| frame.sequence);
+        for frame in frames.iter() {
+            if frame.sequence <= newest {
+                continue; // Already queued or simulated from an earlier resend
+            }
+            if budget.frames == 0 {
+                break; // Resent next time, once the budget has refilled
+            }
+            let frame = sanitize_frame(frame);
+            budget.frames -= 1;
+            newest = frame.sequence;
+            pending.frames.push_back(frame);
+        }
+    }
+}
+
+fn sanitize_frame(frame: &InputFrame) -> InputFrame {
+    let actions = frame
+        .actions
+        .iter()
+        .filter_map(|action| match action {
+            InputAction::Move(direction) if direction.is_finite() => {
+                Some(InputAction::Move(direction.clamp_length_max(1.0)))
+            }
+            InputAction::Move(_) => None,
+            other => Some(other.clone()),
+        })
+        .collect();
+
+    InputFrame {
+        sequence: frame.sequence,
+        actions,
+    }
+}
+
+type ReconcileQuery<'w, 's> = Query<'w, 's, (
+    &'static mut MovementStats,
+    &'static InputBuffer,
+    &'static mut DashState,
+    &'static mut Velocity,
+    &'static mut Hurtbox,
+    &'static mut Transform,
+    &'static mut Health,
+    Entity,
+    Has<Dead>,
+    Has<Incapacitated>,
+), With<LocalPlayer>>;
+
+/// Client only: adopt the host's latest state for our player, then replay
+/// unacknowledged input. Everyone else is interpolated by replication.
+fn reconcile_local_player(
+    mut commands: Commands,
+    network_state: Res<NetworkState>,
+    mut messages: EventReader<PeerMessageReceived>,
+    mut history: ResMut<LocalInputHistory>,
+    mut last_tick: Local<Option<u32>>,
+    mut local_player: ReconcileQuery,
+) {
+    // Only the newest snapshot from the host matters
+    let latest = messages
+        .read()
+        .filter(|event| network_state.host_id.as_ref() == Some(&event.peer_id))
+        .filter_map(|event| match &event.message {
+            GameMessage::Snapshot { snapshot } => Some(snapshot),
+            _ => None,
+        })
+        .last();
+
+    if network_state.is_host() {
+        *last_tick = None;
+        return;
+    }
+    let Some(snapshot) = latest else {
+        return;
+    };
+    if last_tick.is_some_and(|tick| snapshot.tick <= tick) {
+        return; // Arrived out of order
+    }
+    *last_tick = Some(snapshot.tick);
+
+    let Some(state) = snapshot.local_player.as_ref() else {
+        return;
+    };
+    let Ok((mut stats, input_buffer, mut dash_state, mut velocity, mut hurtbox, mut transform, mut health, entity, is_dead, was_incapacitated)) = local_player.get_single_mut() else {
+        return;
+    };
+    // Only the host decides who dies
+    if state.dead && !is_dead {
+        commands.entity(entity).insert(Dead);
+    }
+    // Slows and stuns come from the host's status effects
+    if stats.current_speed != state.move_speed {
+        stats.current_speed = state.move_speed;
+    }
+    if state.incapacitated && !was_incapacitated {
+        commands.entity(entity).insert(Incapacitated);
+    } else if !state.incapacitated && was_incapacitated {
+        commands.entity(entity).remove::<Incapacitated>();
+    }
+
+    transform.translation.x = state.position[0];
+    transform.translation.y = state.position[1];
+    velocity.linear = Vec2::from(state.velocity);
+    health.current = state.health;
+    hurtbox.invulnerable = state.invulnerable;
+    state.restore_dash(&mut dash_state);
+
+    history.acknowledge(state.last_input);
+    // Frames replayed while stunned do nothing, as on the host
+    let idle = InputFrame::default();
+    for frame in history.frames.iter() {
+        step_player(
+            if state.incapacitated { &idle } else { frame },
+            &stats,
+            input_buffer.max_buffer_frames,
+            &mut dash_state,
+            &mut velocity,
+            &mut hurtbox,
+            &mut transform,
+        );
+    }
+}
+
+/// Client only: send the host our recent input frames
+fn send_local_inputs(
+    network_state: Res<NetworkState>,
+    transport: Option<ResMut<ActiveTransport>>,
+    history: Res<LocalInputHistory>,
+) {
+    let Some(mut transport) = transport else {
+        return;
+    };
+    if network_state.is_host() || !history.is_changed() || history.frames.is_empty() {
+        return;
+    }
+    let Some(host_id) = network_state.host_id.as_ref() else {
+        return;
+    };
+
+    let skip = history.frames.len().saturating_sub(INPUT_REDUNDANCY);
+    let message = GameMessage::PlayerInput {
+        player_id: network_state.player_id.clone(),
+        frames: history.frames.iter().skip(skip).cloned().collect(),
+    };
+
+    match protocol::encode(&message) {
+        Ok(payload) => transport.0.send(host_id, &payload),
+        Err(e) => warn!("Failed to encode input frames: {}", e),
+    }
+}
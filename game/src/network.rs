@@ -1,47 +1,127 @@
 use bevy::prelude::*;
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::JsCast;
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen_futures::JsFuture;
+#[cfg(target_arch = "wasm32")]
 use web_sys::{
     RtcPeerConnection, RtcDataChannel, RtcDataChannelEvent,
-    RtcPeerConnectionIceEvent, RtcIceCandidate, RtcSessionDescription,
-    RtcSessionDescriptionInit, MessageEvent, WebSocket, BinaryType,
+    RtcPeerConnectionIceEvent, RtcSessionDescriptionInit, MessageEvent, WebSocket, BinaryType,
 };
+#[cfg(target_arch = "wasm32")]
 use js_sys::{Object, Reflect, JSON};
+#[cfg(target_arch = "wasm32")]
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+#[cfg(target_arch = "wasm32")]
 use std::collections::HashMap;
+use std::collections::HashSet;
+#[cfg(target_arch = "wasm32")]
 use std::sync::{Arc, Mutex};
+use crate::components::{BoonClaim, Health, InputFrame, PickupType, Player, Velocity};
+use crate::replication::WorldSnapshot;
+use crate::protocol::{self, ProtocolError};
+use crate::resources::{CurseClaim, LobbySession, PlayerData};
+#[cfg(target_arch = "wasm32")]
+use crate::negotiation::{NegotiationEvent, NegotiationState, NegotiationTable, NegotiationTransport};
+use crate::transport::{ActiveTransport, TransportEvent};
+#[cfg(target_arch = "wasm32")]
+use crate::transport::{MessageQueue, RawPayload, Transport};
+
+/// Signaling room used when the page URL doesn't name one
+#[cfg(target_arch = "wasm32")]
+const DEFAULT_ROOM_ID: &str = "lobby";
 
 // Wrapper types to make web-sys types thread-safe for Bevy
+#[cfg(target_arch = "wasm32")]
 #[derive(Clone)]
 struct WebSocketWrapper(Arc<Mutex<Option<WebSocket>>>);
 
+#[cfg(target_arch = "wasm32")]
 #[derive(Clone)]
 struct RtcPeerConnectionWrapper(Arc<Mutex<Option<RtcPeerConnection>>>);
 
+#[cfg(target_arch = "wasm32")]
 #[derive(Clone)]
 struct RtcDataChannelWrapper(Arc<Mutex<Option<RtcDataChannel>>>);
 
 // Implement Send and Sync for our wrappers (safe in WASM single-threaded environment)
+#[cfg(target_arch = "wasm32")]
 unsafe impl Send for WebSocketWrapper {}
+#[cfg(target_arch = "wasm32")]
 unsafe impl Sync for WebSocketWrapper {}
+#[cfg(target_arch = "wasm32")]
 unsafe impl Send for RtcPeerConnectionWrapper {}
+#[cfg(target_arch = "wasm32")]
 unsafe impl Sync for RtcPeerConnectionWrapper {}
+#[cfg(target_arch = "wasm32")]
 unsafe impl Send for RtcDataChannelWrapper {}
+#[cfg(target_arch = "wasm32")]
 unsafe impl Sync for RtcDataChannelWrapper {}
 
+#[cfg(target_arch = "wasm32")]
+impl WebSocketWrapper {
+    fn get(&self) -> Option<WebSocket> {
+        self.0.lock().ok().and_then(|socket| socket.clone())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl RtcPeerConnectionWrapper {
+    fn get(&self) -> Option<RtcPeerConnection> {
+        self.0.lock().ok().and_then(|connection| connection.clone())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl RtcDataChannelWrapper {
+    fn get(&self) -> Option<RtcDataChannel> {
+        self.0.lock().ok().and_then(|channel| channel.clone())
+    }
+}
+
+// ============= Network Events =============
+
+#[derive(Event, Debug, Clone)]
+pub struct SignalingReceived(pub SignalingMessage);
+
+#[derive(Event, Debug, Clone)]
+pub struct PeerMessageReceived {
+    pub peer_id: String,
+    pub message: GameMessage,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct PeerConnected {
+    pub peer_id: String,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct PeerDisconnected {
+    pub peer_id: String,
+}
+
 pub struct NetworkPlugin;
 
 impl Plugin for NetworkPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<NetworkState>()
-            .init_resource::<PeerConnections>()
+            .init_resource::<LobbySession>()
+            .add_event::<SignalingReceived>()
+            .add_event::<PeerMessageReceived>()
+            .add_event::<PeerConnected>()
+            .add_event::<PeerDisconnected>()
             .add_systems(Startup, setup_networking)
+            // Drain the transport before gameplay so events are visible this frame
+            .add_systems(PreUpdate, poll_transport)
+            .add_systems(Update, toggle_json_debug)
             .add_systems(Update, (
-                handle_signaling_messages,
-                handle_peer_messages,
-                broadcast_game_state,
-            ));
+                update_lobby_session,
+                receive_lobby_sync,
+                broadcast_lobby_session,
+            ).chain());
     }
 }
 
@@ -49,8 +129,8 @@ impl Plugin for NetworkPlugin {
 pub struct NetworkState {
     pub player_id: String,
     pub room_id: Option<String>,
-    pub signaling_socket: WebSocketWrapper,
     pub is_connected: bool,
+    pub host_id: Option<String>, // None while alone, in which case we are the authority
 }
 
 impl Default for NetworkState {
@@ -58,28 +138,30 @@ impl Default for NetworkState {
         Self {
             player_id: generate_player_id(),
             room_id: None,
-            signaling_socket: WebSocketWrapper(Arc::new(Mutex::new(None))),
             is_connected: false,
+            host_id: None,
         }
     }
 }
 
-#[derive(Resource)]
-pub struct PeerConnections {
-    pub connections: HashMap<String, RtcPeerConnectionWrapper>,
-    pub data_channels: HashMap<String, RtcDataChannelWrapper>,
+impl NetworkState {
+    pub fn is_host(&self) -> bool {
+        self.host_id.as_ref().is_none_or(|host_id| *host_id == self.player_id)
+    }
 }
 
-impl Default for PeerConnections {
-    fn default() -> Self {
-        Self {
-            connections: HashMap::new(),
-            data_channels: HashMap::new(),
-        }
-    }
+/// Per-peer WebRTC bookkeeping owned by `WebRtcTransport`
+#[cfg(target_arch = "wasm32")]
+#[derive(Default)]
+struct PeerConnections {
+    negotiations: NegotiationTable,
+    data_channels: HashMap<String, RtcDataChannelWrapper>,
+    inbox: MessageQueue<(String, RawPayload)>,
+    // Channels opened by JS callbacks, waiting to be attached on the next poll
+    pending_channels: MessageQueue<(String, RtcDataChannelWrapper)>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(tag = "type")]
 pub enum SignalingMessage {
     Join { room_id: String, player_id: String },
@@ -90,62 +172,89 @@ pub enum SignalingMessage {
     RoomUpdate { players: Vec<String> },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(tag = "type")]
 pub enum GameMessage {
-    PlayerUpdate {
+    /// Client to host: recent input frames, resent until acknowledged
+    PlayerInput {
         player_id: String,
-        position: [f32; 2],
-        velocity: [f32; 2],
+        frames: Vec<InputFrame>,
+    },
+    /// Host to one client: world state, delta-compressed against its last ack
+    Snapshot {
+        snapshot: WorldSnapshot,
     },
-    GameStateSync {
-        state: crate::game::GameState,
+    /// Client to host: newest snapshot tick decoded, usable as a delta baseline
+    SnapshotAck {
+        tick: u32,
     },
+    /// Host to everyone: the lobby roster built from the real player entities
+    LobbySync {
+        lobby: LobbySession,
+    },
+    /// Every peer to the others: who we are and the weapon we picked, sent
+    /// on connect and again whenever the pick changes
     PlayerJoined {
         player_id: String,
+        weapon: String,
     },
     PlayerLeft {
         player_id: String,
     },
+    /// Host to everyone: the seed every room of the run is generated from
+    RunSeed {
+        seed: u64,
+    },
+    /// Client to host: every boon the client's player owns, sent whenever
+    /// they change
+    PlayerBoons {
+        player_id: String,
+        boons: Vec<BoonClaim>,
+    },
+    /// Client to host: every curse the client's player carries, sent
+    /// whenever they change
+    PlayerCurses {
+        player_id: String,
+        curses: Vec<CurseClaim>,
+    },
+    /// Client to host: our player used an open door out of `from_room`
+    RequestNextRoom {
+        from_room: u32,
+    },
+    /// Host to everyone: the room the party moves into
+    EnterRoom {
+        room_number: u32,
+    },
+    /// Host to one client: their player picked something up. `amount` is the
+    /// gold or souls credited, after the host's gold find.
+    PickupCollected {
+        pickup_type: PickupType,
+        amount: u32,
+    },
 }
 
 fn generate_player_id() -> String {
-    format!("player_{}", js_sys::Math::random().to_string().chars().skip(2).take(8).collect::<String>())
+    format!("player_{:08x}", rand::random::<u32>())
 }
 
 fn setup_networking(
+    mut commands: Commands,
     mut network_state: ResMut<NetworkState>,
+    existing: Option<Res<ActiveTransport>>,
 ) {
+    // Tests and tools may install their own transport before startup
+    if existing.is_some() {
+        network_state.is_connected = true;
+        return;
+    }
+
     #[cfg(target_arch = "wasm32")]
     {
-        // Connect to signaling server
-        let ws_url = get_signaling_url();
-        match WebSocket::new(&ws_url) {
-            Ok(ws) => {
-                ws.set_binary_type(BinaryType::Arraybuffer);
-                
-                // Set up WebSocket event handlers
-                let onopen = Closure::wrap(Box::new(move || {
-                    web_sys::console::log_1(&"Connected to signaling server".into());
-                }) as Box<dyn FnMut()>);
-                
-                let onerror = Closure::wrap(Box::new(move |e: web_sys::ErrorEvent| {
-                    web_sys::console::error_1(&format!("WebSocket error: {:?}", e.message()).into());
-                }) as Box<dyn FnMut(web_sys::ErrorEvent)>);
-                
-                let onclose = Closure::wrap(Box::new(move |e: web_sys::CloseEvent| {
-                    web_sys::console::log_1(&format!("WebSocket closed: code={}, reason={}", e.code(), e.reason()).into());
-                }) as Box<dyn FnMut(web_sys::CloseEvent)>);
-                
-                ws.set_onopen(Some(onopen.as_ref().unchecked_ref()));
-                ws.set_onerror(Some(onerror.as_ref().unchecked_ref()));
-                ws.set_onclose(Some(onclose.as_ref().unchecked_ref()));
-                
-                onopen.forget();
-                onerror.forget();
-                onclose.forget();
-                
-                network_state.signaling_socket = Some(ws);
+        let room_id = get_room_id();
+        network_state.room_id = Some(room_id.clone());
+        match WebRtcTransport::connect(&network_state.player_id, &room_id, &get_signaling_url()) {
+            Ok(transport) => {
+                commands.insert_resource(ActiveTransport(Box::new(transport)));
                 network_state.is_connected = true;
             }
             Err(e) => {
@@ -154,8 +263,43 @@ fn setup_networking(
             }
         }
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        match crate::transport::UdpTransport::from_env(&network_state.player_id) {
+            Some(Ok(transport)) => {
+                info!("Native UDP transport ready as {}", network_state.player_id);
+                commands.insert_resource(ActiveTransport(Box::new(transport)));
+                network_state.is_connected = true;
+            }
+            Some(Err(e)) => {
+                error!("Failed to bind UDP transport: {}", e);
+                network_state.is_connected = false;
+            }
+            None => {
+                network_state.is_connected = false; // Single player
+            }
+        }
+    }
 }
 
+/// Signaling room to join, taken from `?room=` so friends can share a link
+#[cfg(target_arch = "wasm32")]
+fn get_room_id() -> String {
+    web_sys::window()
+        .and_then(|window| window.location().search().ok())
+        .and_then(|search| {
+            search
+                .trim_start_matches('?')
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("room="))
+                .map(str::to_string)
+        })
+        .filter(|room_id| !room_id.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_ROOM_ID.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
 fn get_signaling_url() -> String {
     // Check if we're running locally or on GitHub Pages
     let window = match web_sys::window() {
@@ -189,16 +333,15 @@ fn get_signaling_url() -> String {
     }
 }
 
+#[cfg(target_arch = "wasm32")]
 fn get_signaling_url_from_config() -> Option<String> {
     // Try to get signaling URL from a meta tag or global config
     if let Some(window) = web_sys::window() {
         if let Ok(document) = window.document().ok_or("no document") {
             // Look for meta tag with signaling server URL
-            if let Ok(meta) = document.query_selector("meta[name='signaling-server']") {
-                if let Some(meta_element) = meta {
-                    if let Some(content) = meta_element.get_attribute("content") {
-                        return Some(content);
-                    }
+            if let Ok(Some(meta_element)) = document.query_selector("meta[name='signaling-server']") {
+                if let Some(content) = meta_element.get_attribute("content") {
+                    return Some(content);
                 }
             }
         }
@@ -215,69 +358,160 @@ fn get_signaling_url_from_config() -> Option<String> {
     None
 }
 
-fn handle_signaling_messages(
+#[cfg(target_arch = "wasm32")]
+fn payload_from_event(event: &MessageEvent) -> Option<RawPayload> {
+    let data = event.data();
+    if let Some(text) = data.as_string() {
+        return Some(RawPayload::Text(text));
+    }
+    
+    match data.dyn_into::<js_sys::ArrayBuffer>() {
+        Ok(buffer) => Some(RawPayload::Binary(js_sys::Uint8Array::new(&buffer).to_vec())),
+        Err(_) => None,
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn decode_payload<T: DeserializeOwned>(payload: &RawPayload) -> Result<T, serde_json::Error> {
+    match payload {
+        RawPayload::Text(text) => serde_json::from_str(text),
+        RawPayload::Binary(bytes) => serde_json::from_slice(bytes),
+    }
+}
+
+fn poll_transport(
+    transport: Option<ResMut<ActiveTransport>>,
+    mut signaling_events: EventWriter<SignalingReceived>,
+    mut peer_events: EventWriter<PeerMessageReceived>,
+    mut connected_events: EventWriter<PeerConnected>,
+    mut disconnected_events: EventWriter<PeerDisconnected>,
+    mut mismatched_peers: Local<HashSet<String>>,
+) {
+    let Some(mut transport) = transport else {
+        return; // Offline
+    };
+
+    for event in transport.0.poll() {
+        match event {
+            TransportEvent::Signaling(message) => {
+                signaling_events.send(SignalingReceived(message));
+            }
+            TransportEvent::PeerConnected { peer_id } => {
+                connected_events.send(PeerConnected { peer_id });
+            }
+            TransportEvent::PeerDisconnected { peer_id } => {
+                mismatched_peers.remove(&peer_id);
+                disconnected_events.send(PeerDisconnected { peer_id });
+            }
+            TransportEvent::Message { peer_id, payload } => {
+                match protocol::decode(&payload) {
+                    Ok(message) => {
+                        peer_events.send(PeerMessageReceived { peer_id, message });
+                    }
+                    Err(e @ ProtocolError::VersionMismatch { .. }) => {
+                        // A peer on another build would otherwise flood the log every frame
+                        if mismatched_peers.insert(peer_id.clone()) {
+                            warn!("Ignoring messages from {}: {}", peer_id, e);
+                        }
+                    }
+                    Err(e) => warn!("Dropping undecodable message from {}: {}", peer_id, e),
+                }
+            }
+        }
+    }
+}
+
+/// Host only: rebuild the lobby roster from the player entities
+fn update_lobby_session(
     network_state: Res<NetworkState>,
-    mut peer_connections: ResMut<PeerConnections>,
+    mut lobby: ResMut<LobbySession>,
+    players: Query<(&Player, &Transform, &Velocity, &Health)>,
 ) {
-    #[cfg(target_arch = "wasm32")]
-    {
-        if let Some(ws) = &network_state.signaling_socket {
-            // Handle incoming WebSocket messages
-            // This would typically be done with event handlers
+    let is_host = network_state.is_host();
+    if !is_host {
+        if lobby.is_host {
+            lobby.is_host = false;
         }
+        return;
     }
+
+    let mut roster: Vec<PlayerData> = players
+        .iter()
+        .map(|(player, transform, velocity, health)| PlayerData {
+            id: player.id.clone(),
+            position: transform.translation.truncate(),
+            velocity: velocity.linear,
+            health: health.current,
+        })
+        .collect();
+    roster.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let round = lobby.round;
+    lobby.set_if_neq(LobbySession {
+        round,
+        is_host,
+        players: roster,
+    });
 }
 
-fn handle_peer_messages(
-    peer_connections: Res<PeerConnections>,
-    mut game_state: ResMut<crate::game::GameState>,
+/// Client only: take the host's roster
+fn receive_lobby_sync(
+    network_state: Res<NetworkState>,
+    mut lobby: ResMut<LobbySession>,
+    mut messages: EventReader<PeerMessageReceived>,
 ) {
-    // Handle incoming messages from peer data channels
-    for (peer_id, channel) in peer_connections.data_channels.iter() {
-        // Process messages from each peer
+    let latest = messages
+        .read()
+        .filter(|event| network_state.host_id.as_ref() == Some(&event.peer_id))
+        .filter_map(|event| match &event.message {
+            GameMessage::LobbySync { lobby } => Some(lobby),
+            _ => None,
+        })
+        .last();
+
+    if network_state.is_host() {
+        return;
+    }
+    if let Some(host_lobby) = latest {
+        lobby.set_if_neq(LobbySession {
+            is_host: false,
+            ..host_lobby.clone()
+        });
     }
 }
 
-fn broadcast_game_state(
-    peer_connections: Res<PeerConnections>,
-    game_state: Res<crate::game::GameState>,
-    player_query: Query<&crate::game::Player, With<crate::game::LocalPlayer>>,
+fn broadcast_lobby_session(
+    network_state: Res<NetworkState>,
+    transport: Option<ResMut<ActiveTransport>>,
+    lobby: Res<LobbySession>,
 ) {
-    if game_state.is_changed() {
-        // Broadcast game state to all connected peers
-        let message = GameMessage::GameStateSync {
-            state: game_state.clone(),
+    let Some(mut transport) = transport else {
+        return;
+    };
+
+    if network_state.is_host() && lobby.is_changed() {
+        let message = GameMessage::LobbySync {
+            lobby: lobby.clone(),
         };
-        
-        if let Ok(json_str) = serde_json::to_string(&message) {
-            for (_, channel) in peer_connections.data_channels.iter() {
-                if channel.ready_state() == web_sys::RtcDataChannelState::Open {
-                    let _ = channel.send_with_str(&json_str);
-                }
-            }
+
+        match protocol::encode(&message) {
+            Ok(payload) => transport.0.broadcast(&payload),
+            Err(e) => warn!("Failed to encode lobby session: {}", e),
         }
     }
-    
-    // Broadcast local player updates
-    for player in player_query.iter() {
-        let message = GameMessage::PlayerUpdate {
-            player_id: player.id.clone(),
-            position: [player.position.x, player.position.y],
-            velocity: [player.velocity.x, player.velocity.y],
-        };
-        
-        if let Ok(json_str) = serde_json::to_string(&message) {
-            for (_, channel) in peer_connections.data_channels.iter() {
-                if channel.ready_state() == web_sys::RtcDataChannelState::Open {
-                    let _ = channel.send_with_str(&json_str);
-                }
-            }
-        }
+}
+
+/// F9 flips the wire format between binary and readable JSON
+fn toggle_json_debug(keyboard: Res<ButtonInput<KeyCode>>) {
+    if keyboard.just_pressed(KeyCode::F9) {
+        let enabled = !protocol::json_debug_enabled();
+        protocol::set_json_debug(enabled);
+        info!("Network JSON debug mode {}", if enabled { "on" } else { "off" });
     }
 }
 
 #[cfg(target_arch = "wasm32")]
-pub async fn create_peer_connection(peer_id: String) -> Result<RtcPeerConnection, JsValue> {
+pub fn create_peer_connection() -> Result<RtcPeerConnection, JsValue> {
     let window = web_sys::window().ok_or_else(|| JsValue::from_str("Failed to get window"))?;
     let rtc_peer_connection = Reflect::get(&window, &JsValue::from_str("RTCPeerConnection"))?;
     let rtc_peer_connection = rtc_peer_connection.dyn_into::<js_sys::Function>()?;
@@ -290,7 +524,7 @@ pub async fn create_peer_connection(peer_id: String) -> Result<RtcPeerConnection
     let config = Object::new();
     Reflect::set(&config, &JsValue::from_str("iceServers"), &ice_servers)?;
     
-    let peer_connection = rtc_peer_connection.construct_with_args(&js_sys::Array::of1(&config))?;
+    let peer_connection = Reflect::construct(&rtc_peer_connection, &js_sys::Array::of1(&config))?;
     let peer_connection: RtcPeerConnection = peer_connection.dyn_into()?;
     
     Ok(peer_connection)
@@ -302,4 +536,428 @@ pub fn create_data_channel(peer_connection: &RtcPeerConnection, label: &str) ->
     data_channel_init.set_ordered(true);
     
     peer_connection.create_data_channel_with_data_channel_dict(label, &data_channel_init)
-}
\ No newline at end of file
+}
+
+/// Register a data channel for `peer_id` and route its messages into the peer inbox
+#[cfg(target_arch = "wasm32")]
+fn attach_data_channel(
+    peer_connections: &mut PeerConnections,
+    peer_id: String,
+    channel: RtcDataChannel,
+) {
+    channel.set_binary_type(web_sys::RtcDataChannelType::Arraybuffer);
+    
+    let inbox = peer_connections.inbox.clone();
+    let from = peer_id.clone();
+    let onmessage = Closure::wrap(Box::new(move |e: MessageEvent| {
+        if let Some(payload) = payload_from_event(&e) {
+            inbox.push((from.clone(), payload));
+        }
+    }) as Box<dyn FnMut(MessageEvent)>);
+    
+    channel.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    onmessage.forget();
+    
+    peer_connections.data_channels.insert(
+        peer_id,
+        RtcDataChannelWrapper(Arc::new(Mutex::new(Some(channel)))),
+    );
+}
+
+// ============= WebRTC Transport =============
+
+#[cfg(target_arch = "wasm32")]
+pub struct WebRtcTransport {
+    local_id: String,
+    signaling_inbox: MessageQueue<RawPayload>,
+    peers: PeerConnections,
+    negotiator: Box<dyn NegotiationTransport>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl WebRtcTransport {
+    pub fn connect(local_id: &str, room_id: &str, ws_url: &str) -> Result<Self, JsValue> {
+        let ws = WebSocket::new(ws_url)?;
+        ws.set_binary_type(BinaryType::Arraybuffer);
+        
+        // Set up WebSocket event handlers
+        // Joining the room is what makes the server send RoomUpdate and start negotiation
+        let join_socket = ws.clone();
+        let join = SignalingMessage::Join {
+            room_id: room_id.to_string(),
+            player_id: local_id.to_string(),
+        };
+        let onopen = Closure::wrap(Box::new(move || {
+            web_sys::console::log_1(&"Connected to signaling server".into());
+            match serde_json::to_string(&join) {
+                Ok(json_str) => {
+                    if let Err(e) = join_socket.send_with_str(&json_str) {
+                        web_sys::console::error_1(&format!("Failed to send Join: {:?}", e).into());
+                    }
+                }
+                Err(e) => web_sys::console::error_1(&format!("Failed to encode Join: {}", e).into()),
+            }
+        }) as Box<dyn FnMut()>);
+        
+        let onerror = Closure::wrap(Box::new(move |e: web_sys::ErrorEvent| {
+            web_sys::console::error_1(&format!("WebSocket error: {:?}", e.message()).into());
+        }) as Box<dyn FnMut(web_sys::ErrorEvent)>);
+        
+        let onclose = Closure::wrap(Box::new(move |e: web_sys::CloseEvent| {
+            web_sys::console::log_1(&format!("WebSocket closed: code={}, reason={}", e.code(), e.reason()).into());
+        }) as Box<dyn FnMut(web_sys::CloseEvent)>);
+        
+        // Queue raw messages; poll() decodes them on the next frame
+        let signaling_inbox = MessageQueue::default();
+        let inbox = signaling_inbox.clone();
+        let onmessage = Closure::wrap(Box::new(move |e: MessageEvent| {
+            if let Some(payload) = payload_from_event(&e) {
+                inbox.push(payload);
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        
+        ws.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+        ws.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+        ws.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+        ws.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        
+        onopen.forget();
+        onerror.forget();
+        onclose.forget();
+        onmessage.forget();
+        
+        let peers = PeerConnections::default();
+        let negotiator = WebRtcNegotiator::new(
+            WebSocketWrapper(Arc::new(Mutex::new(Some(ws)))),
+            peers.pending_channels.clone(),
+        );
+        
+        Ok(Self {
+            local_id: local_id.to_string(),
+            signaling_inbox,
+            peers,
+            negotiator: Box::new(negotiator),
+        })
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Transport for WebRtcTransport {
+    fn local_id(&self) -> &str {
+        &self.local_id
+    }
+
+    fn peers(&self) -> Vec<String> {
+        self.peers.data_channels.keys().cloned().collect()
+    }
+
+    fn send(&mut self, peer_id: &str, payload: &RawPayload) {
+        let Some(channel) = self.peers.data_channels.get(peer_id).and_then(|c| c.get()) else {
+            return;
+        };
+        if channel.ready_state() != web_sys::RtcDataChannelState::Open {
+            return;
+        }
+        let _ = match payload {
+            RawPayload::Text(text) => channel.send_with_str(text),
+            RawPayload::Binary(bytes) => channel.send_with_u8_array(bytes),
+        };
+    }
+
+    fn poll(&mut self) -> Vec<TransportEvent> {
+        let mut events = Vec::new();
+        let local_id = self.local_id.as_str();
+        let negotiator = self.negotiator.as_mut();
+
+        for payload in self.signaling_inbox.drain() {
+            match decode_payload::<SignalingMessage>(&payload) {
+                Ok(message) => {
+                    self.peers.negotiations.on_signaling(local_id, &message, negotiator);
+                    events.push(TransportEvent::Signaling(message));
+                }
+                Err(e) => warn!("Dropping undecodable signaling message: {}", e),
+            }
+        }
+        for event in negotiator.poll_events() {
+            self.peers.negotiations.on_transport_event(local_id, event, negotiator);
+        }
+
+        // Attach channels before announcing peers so the first send succeeds
+        for (peer_id, channel) in self.peers.pending_channels.drain() {
+            if let Some(channel) = channel.get() {
+                attach_data_channel(&mut self.peers, peer_id, channel);
+            }
+        }
+
+        for (peer_id, state) in self.peers.negotiations.drain_changes() {
+            match state {
+                NegotiationState::Connected => {
+                    events.push(TransportEvent::PeerConnected { peer_id });
+                }
+                NegotiationState::Failed | NegotiationState::Closed
+                    if self.peers.data_channels.remove(&peer_id).is_some() =>
+                {
+                    events.push(TransportEvent::PeerDisconnected { peer_id });
+                }
+                _ => {}
+            }
+        }
+
+        for (peer_id, payload) in self.peers.inbox.drain() {
+            events.push(TransportEvent::Message { peer_id, payload });
+        }
+
+        events
+    }
+}
+
+// ============= WebRTC Negotiation =============
+
+#[cfg(target_arch = "wasm32")]
+pub struct WebRtcNegotiator {
+    signaling_socket: WebSocketWrapper,
+    connections: HashMap<String, RtcPeerConnectionWrapper>,
+    events: MessageQueue<NegotiationEvent>,
+    opened_channels: MessageQueue<(String, RtcDataChannelWrapper)>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl WebRtcNegotiator {
+    fn new(
+        signaling_socket: WebSocketWrapper,
+        opened_channels: MessageQueue<(String, RtcDataChannelWrapper)>,
+    ) -> Self {
+        Self {
+            signaling_socket,
+            connections: HashMap::new(),
+            events: MessageQueue::default(),
+            opened_channels,
+        }
+    }
+
+    fn connection_for(&mut self, peer_id: &str) -> Option<RtcPeerConnection> {
+        if let Some(connection) = self.connections.get(peer_id).and_then(|c| c.get()) {
+            return Some(connection);
+        }
+
+        let connection = match create_peer_connection() {
+            Ok(connection) => connection,
+            Err(e) => {
+                web_sys::console::error_1(&format!("Failed to create peer connection: {:?}", e).into());
+                return None;
+            }
+        };
+
+        // Trickle local candidates out through signaling
+        let events = self.events.clone();
+        let peer = peer_id.to_string();
+        let onicecandidate = Closure::wrap(Box::new(move |e: RtcPeerConnectionIceEvent| {
+            if let Some(candidate) = e.candidate() {
+                if let Ok(json) = JSON::stringify(&candidate.to_json()) {
+                    events.push(NegotiationEvent::LocalIceCandidate {
+                        peer_id: peer.clone(),
+                        candidate: json.into(),
+                    });
+                }
+            }
+        }) as Box<dyn FnMut(RtcPeerConnectionIceEvent)>);
+
+        // The answering side receives the offerer's channel here
+        let events = self.events.clone();
+        let opened = self.opened_channels.clone();
+        let peer = peer_id.to_string();
+        let ondatachannel = Closure::wrap(Box::new(move |e: RtcDataChannelEvent| {
+            watch_channel(&peer, e.channel(), &events, &opened);
+        }) as Box<dyn FnMut(RtcDataChannelEvent)>);
+
+        let events = self.events.clone();
+        let peer = peer_id.to_string();
+        let watched = connection.clone();
+        let onconnectionstatechange = Closure::wrap(Box::new(move || {
+            if watched.connection_state() == web_sys::RtcPeerConnectionState::Failed {
+                events.push(NegotiationEvent::ConnectionFailed { peer_id: peer.clone() });
+            }
+        }) as Box<dyn FnMut()>);
+
+        connection.set_onicecandidate(Some(onicecandidate.as_ref().unchecked_ref()));
+        connection.set_ondatachannel(Some(ondatachannel.as_ref().unchecked_ref()));
+        connection.set_onconnectionstatechange(Some(onconnectionstatechange.as_ref().unchecked_ref()));
+
+        onicecandidate.forget();
+        ondatachannel.forget();
+        onconnectionstatechange.forget();
+
+        self.connections.insert(
+            peer_id.to_string(),
+            RtcPeerConnectionWrapper(Arc::new(Mutex::new(Some(connection.clone())))),
+        );
+        Some(connection)
+    }
+
+    fn fail(&self, peer_id: &str) {
+        self.events.push(NegotiationEvent::ConnectionFailed { peer_id: peer_id.to_string() });
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn watch_channel(
+    peer_id: &str,
+    channel: RtcDataChannel,
+    events: &MessageQueue<NegotiationEvent>,
+    opened: &MessageQueue<(String, RtcDataChannelWrapper)>,
+) {
+    let on_open_events = events.clone();
+    let opened = opened.clone();
+    let peer = peer_id.to_string();
+    let opened_channel = channel.clone();
+    let onopen = Closure::wrap(Box::new(move || {
+        opened.push((
+            peer.clone(),
+            RtcDataChannelWrapper(Arc::new(Mutex::new(Some(opened_channel.clone())))),
+        ));
+        on_open_events.push(NegotiationEvent::ChannelOpen { peer_id: peer.clone() });
+    }) as Box<dyn FnMut()>);
+
+    let on_close_events = events.clone();
+    let peer = peer_id.to_string();
+    let onclose = Closure::wrap(Box::new(move || {
+        on_close_events.push(NegotiationEvent::ChannelClosed { peer_id: peer.clone() });
+    }) as Box<dyn FnMut()>);
+
+    channel.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+    channel.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+    onopen.forget();
+    onclose.forget();
+}
+
+#[cfg(target_arch = "wasm32")]
+fn session_description(sdp_type: web_sys::RtcSdpType, sdp: &str) -> RtcSessionDescriptionInit {
+    let description = RtcSessionDescriptionInit::new(sdp_type);
+    description.set_sdp(sdp);
+    description
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn local_description(
+    connection: &RtcPeerConnection,
+    sdp_type: web_sys::RtcSdpType,
+) -> Result<String, JsValue> {
+    let promise = if sdp_type == web_sys::RtcSdpType::Offer {
+        connection.create_offer()
+    } else {
+        connection.create_answer()
+    };
+    let description = JsFuture::from(promise).await?;
+    let sdp = Reflect::get(&description, &JsValue::from_str("sdp"))?
+        .as_string()
+        .unwrap_or_default();
+
+    JsFuture::from(connection.set_local_description(&session_description(sdp_type, &sdp))).await?;
+    Ok(sdp)
+}
+
+#[cfg(target_arch = "wasm32")]
+impl NegotiationTransport for WebRtcNegotiator {
+    fn create_offer(&mut self, peer_id: &str) {
+        let Some(connection) = self.connection_for(peer_id) else {
+            self.fail(peer_id);
+            return;
+        };
+
+        // The offerer owns the game channel; the answerer gets it via ondatachannel
+        let channel = create_data_channel(&connection, "game");
+        watch_channel(peer_id, channel, &self.events, &self.opened_channels);
+
+        let events = self.events.clone();
+        let peer = peer_id.to_string();
+        wasm_bindgen_futures::spawn_local(async move {
+            match local_description(&connection, web_sys::RtcSdpType::Offer).await {
+                Ok(sdp) => events.push(NegotiationEvent::LocalOffer { peer_id: peer, sdp }),
+                Err(_) => events.push(NegotiationEvent::ConnectionFailed { peer_id: peer }),
+            }
+        });
+    }
+
+    fn accept_offer(&mut self, peer_id: &str, sdp: &str) {
+        let Some(connection) = self.connection_for(peer_id) else {
+            self.fail(peer_id);
+            return;
+        };
+
+        let offer = session_description(web_sys::RtcSdpType::Offer, sdp);
+        let events = self.events.clone();
+        let peer = peer_id.to_string();
+        wasm_bindgen_futures::spawn_local(async move {
+            let answer = async {
+                JsFuture::from(connection.set_remote_description(&offer)).await?;
+                local_description(&connection, web_sys::RtcSdpType::Answer).await
+            };
+            match answer.await {
+                Ok(sdp) => events.push(NegotiationEvent::LocalAnswer { peer_id: peer, sdp }),
+                Err(_) => events.push(NegotiationEvent::ConnectionFailed { peer_id: peer }),
+            }
+        });
+    }
+
+    fn accept_answer(&mut self, peer_id: &str, sdp: &str) {
+        let Some(connection) = self.connections.get(peer_id).and_then(|c| c.get()) else {
+            return;
+        };
+
+        let answer = session_description(web_sys::RtcSdpType::Answer, sdp);
+        let events = self.events.clone();
+        let peer = peer_id.to_string();
+        wasm_bindgen_futures::spawn_local(async move {
+            if JsFuture::from(connection.set_remote_description(&answer)).await.is_err() {
+                events.push(NegotiationEvent::ConnectionFailed { peer_id: peer });
+            }
+        });
+    }
+
+    fn add_ice_candidate(&mut self, peer_id: &str, candidate: &str) {
+        let Some(connection) = self.connections.get(peer_id).and_then(|c| c.get()) else {
+            return;
+        };
+        let Ok(init) = JSON::parse(candidate) else {
+            return;
+        };
+
+        let init: web_sys::RtcIceCandidateInit = init.unchecked_into();
+        wasm_bindgen_futures::spawn_local(async move {
+            // Late or duplicate candidates are harmless; ignore their errors
+            let _ = JsFuture::from(
+                connection.add_ice_candidate_with_opt_rtc_ice_candidate_init(Some(&init)),
+            ).await;
+        });
+    }
+
+    fn rollback(&mut self, peer_id: &str) {
+        let Some(connection) = self.connections.get(peer_id).and_then(|c| c.get()) else {
+            return;
+        };
+
+        let rollback = RtcSessionDescriptionInit::new(web_sys::RtcSdpType::Rollback);
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = JsFuture::from(connection.set_local_description(&rollback)).await;
+        });
+    }
+
+    fn close(&mut self, peer_id: &str) {
+        if let Some(connection) = self.connections.remove(peer_id).and_then(|c| c.get()) {
+            connection.close();
+        }
+    }
+
+    fn send_signaling(&mut self, message: SignalingMessage) {
+        let Some(ws) = self.signaling_socket.get() else {
+            return;
+        };
+        if let Ok(json_str) = serde_json::to_string(&message) {
+            let _ = ws.send_with_str(&json_str);
+        }
+    }
+
+    fn poll_events(&mut self) -> Vec<NegotiationEvent> {
+        self.events.drain()
+    }
+}
@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+use crate::components::*;
+use crate::network::{GameMessage, NetworkState, PeerMessageReceived};
+use crate::protocol;
+use crate::resources::PlayerInventory;
+use crate::transport::ActiveTransport;
+
+// ============= Peer-Reported Player State =============
+//
+// Boons and curses are picked on each peer's own screens, but the host runs
+// everyone's effects. Clients mirror their inventory onto their player and
+// tell the host what they own; the host rebuilds the real values from its own
+// tables, so a client can claim a boon but never invent its numbers.
+
+/// A per-player component mirrored from `PlayerInventory` and reported to the host
+pub trait PeerReported: Component + Sized {
+    type Claim;
+
+    /// Used in log messages
+    const LABEL: &'static str;
+
+    /// What the local player's copy should hold
+    fn from_inventory(inventory: &PlayerInventory) -> Self;
+
+    fn claims(&self) -> Vec<Self::Claim>;
+
+    fn message(player_id: String, claims: Vec<Self::Claim>) -> GameMessage;
+
+    /// The player and claims `message` carries, if it's this kind of report
+    fn read_message(message: &GameMessage) -> Option<(&String, &[Self::Claim])>;
+
+    /// Host only: the real state behind a peer's claims, clamped to what the
+    /// game allows
+    fn rebuild(claims: &[Self::Claim]) -> Self;
+}
+
+/// Mirror the inventory onto the local player, including once it spawns
+pub fn sync_local<T: PeerReported>(
+    inventory: Res<PlayerInventory>,
+    mut player_query: Query<(Ref<Player>, &mut T), With<LocalPlayer>>,
+) {
+    let Ok((player, mut state)) = player_query.get_single_mut() else {
+        return;
+    };
+    if inventory.is_changed() || player.is_added() {
+        *state = T::from_inventory(&inventory);
+    }
+}
+
+/// Keep the host up to date with our state, and tell a new host after a
+/// migration
+pub fn send_to_host<T: PeerReported>(
+    network_state: Res<NetworkState>,
+    transport: Option<ResMut<ActiveTransport>>,
+    player_query: Query<(&Player, Ref<T>), With<LocalPlayer>>,
+) {
+    let (Some(mut transport), Some(host_id)) = (transport, network_state.host_id.as_ref()) else {
+        return;
+    };
+    let Ok((player, state)) = player_query.get_single() else {
+        return;
+    };
+    if !state.is_changed() && !network_state.is_changed() {
+        return;
+    }
+
+    match protocol::encode(&T::message(player.id.clone(), state.claims())) {
+        Ok(payload) => transport.0.send(host_id, &payload),
+        Err(e) => warn!("Failed to encode {}: {}", T::LABEL, e),
+    }
+}
+
+pub fn receive_from_peers<T: PeerReported>(
+    mut messages: EventReader<PeerMessageReceived>,
+    mut player_query: Query<(&Player, &mut T), Without<LocalPlayer>>,
+) {
+    for PeerMessageReceived { peer_id, message } in messages.read() {
+        let Some((player_id, claims)) = T::read_message(message) else {
+            continue;
+        };
+        // Peers only speak for themselves
+        if player_id != peer_id {
+            continue;
+        }
+        if let Some((_, mut state)) = player_query.iter_mut().find(|(player, _)| player.id == *player_id) {
+            *state = T::rebuild(claims);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use bevy::prelude::*;
+use crate::components::*;
+use crate::resources::*;
+use crate::combat::{can_hit, check_aabb_collision};
+use crate::damage::{DamageInstance, DamageTag, DealDamage};
+use crate::movement::{ARENA_HALF_HEIGHT, ARENA_HALF_WIDTH};
+use crate::status::ApplyStatus;
+
+// ============= Projectiles =============
+//
+// One projectile path for both sides. Players fire them from projectile
+// weapons, enemies from their ranged attacks; either way they fly until they
+// run out of range, reach a wall or use up their piercing, and every hit goes
+// through `DealDamage` like a melee hitbox. Like hit detection this only runs
+// on the host; clients see replicas.
+
+/// Everything needed to fire one projectile
+#[derive(Debug, Clone)]
+pub struct ProjectileSpec {
+    pub damage: f32,
+    pub damage_type: DamageType,
+    pub speed: f32,
+    pub range: f32, // Distance flown before it expires
+    pub piercing: u32,
+    pub size: Vec2,
+    pub color: Color,
+    pub homing: Option<Homing>,
+    pub status: Option<OnHitStatus>,
+}
+
+pub fn spawn_projectile(
+    commands: &mut Commands,
+    spec: &ProjectileSpec,
+    position: Vec3,
+    direction: Vec2,
+    owner: Entity,
+    team: Team,
+) -> Entity {
+    let speed = spec.speed.max(1.0);
+    let direction = direction.try_normalize().unwrap_or(Vec2::X);
+
+    let mut projectile = commands.spawn((
+        Projectile {
+            damage: spec.damage,
+            damage_type: spec.damage_type,
+            speed,
+            size: spec.size,
+            lifetime: FrameTimer::from_seconds(spec.range / speed),
+            piercing: spec.piercing,
+            status: spec.status,
+            owner,
+            hit_entities: Vec::new(),
+        },
+        team,
+        Velocity {
+            linear: direction * speed,
+        },
+        SpriteBundle {
+            sprite: Sprite {
+                color: spec.color,
+                custom_size: Some(spec.size),
+                ..default()
+            },
+            transform: Transform::from_translation(position)
+                .with_rotation(Quat::from_rotation_z(direction.to_angle())),
+            ..default()
+        },
+    ));
+
+    if let Some(homing) = spec.homing {
+        projectile.insert(homing);
+    }
+    projectile.id()
+}
+
+/// How many projectiles one shot fires and how wide they fan out
+#[derive(Debug, Clone, Copy)]
+pub struct SpreadPattern {
+    pub count: u32,
+    pub arc_degrees: f32,
+}
+
+/// Fire the pattern's projectiles fanned evenly across its arc, centred on
+/// `direction`
+pub fn spawn_projectile_spread(
+    commands: &mut Commands,
+    spec: &ProjectileSpec,
+    pattern: SpreadPattern,
+    position: Vec3,
+    direction: Vec2,
+    owner: Entity,
+    team: Team,
+) {
+    if pattern.count <= 1 {
+        spawn_projectile(commands, spec, position, direction, owner, team);
+        return;
+    }
+
+    let arc = pattern.arc_degrees.to_radians();
+    for i in 0..pattern.count {
+        let angle = -arc / 2.0 + arc * i as f32 / (pattern.count - 1) as f32;
+        let spread_direction = Vec2::from_angle(angle).rotate(direction);
+        spawn_projectile(commands, spec, position, spread_direction, owner, team);
+    }
+}
+
+type ProjectileQuery<'w, 's> = Query<'w, 's, (Entity, &'static mut Projectile, &'static mut Velocity, &'static mut Transform, &'static Team, Option<&'static Homing>)>;
+
+/// Steer, fly and expire. Walls are the arena edge.
+pub fn move_projectiles(
+    mut commands: Commands,
+    mut projectile_query: ProjectileQuery,
+    target_query: Query<(&Transform, &Hurtbox, &Team), Without<Projectile>>,
+    settings: Res<CombatSettings>,
+) {
+    for (entity, mut projectile, mut velocity, mut transform, projectile_team, homing) in projectile_query.iter_mut() {
+        let position = transform.translation.truncate();
+
+        if let Some(homing) = homing {
+            // Friendly fire never makes a shot hunt down an ally
+            let target = target_query
+                .iter()
+                .filter(|(_, hurtbox, team)| **team != *projectile_team && can_hit(*projectile_team, **team, &settings) && !hurtbox.invulnerable)
+                .map(|(target_transform, _, _)| target_transform.translation.truncate())
+                .filter(|target| target.distance(position) <= homing.range)
+                .min_by(|a, b| a.distance(position).total_cmp(&b.distance(position)));
+
+            if let Some(target) = target {
+                let heading = velocity.linear.try_normalize().unwrap_or(Vec2::X);
+                let max_turn = homing.turn_rate * FRAME_SECONDS;
+                let turn = heading.angle_between(target - position).clamp(-max_turn, max_turn);
+                let heading = Vec2::from_angle(turn).rotate(heading);
+
+                velocity.linear = heading * projectile.speed;
+                transform.rotation = Quat::from_rotation_z(heading.to_angle());
+            }
+        }
+
+        transform.translation.x += velocity.linear.x * FRAME_SECONDS;
+        transform.translation.y += velocity.linear.y * FRAME_SECONDS;
+
+        projectile.lifetime.tick();
+        let hit_wall = transform.translation.x.abs() > ARENA_HALF_WIDTH
+            || transform.translation.y.abs() > ARENA_HALF_HEIGHT;
+
+        if projectile.lifetime.finished() || hit_wall {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn check_projectile_hits(
+    mut commands: Commands,
+    mut projectile_query: Query<(Entity, &mut Projectile, &Transform, &Team)>,
+    hurtbox_query: Query<(Entity, &Hurtbox, &Transform, &Team), Without<Projectile>>,
+    settings: Res<CombatSettings>,
+    mut damage_events: EventWriter<DealDamage>,
+    mut status_events: EventWriter<ApplyStatus>,
+) {
+    for (entity, mut projectile, projectile_transform, projectile_team) in projectile_query.iter_mut() {
+        let projectile_pos = projectile_transform.translation.truncate();
+
+        for (target_entity, hurtbox, hurtbox_transform, team) in hurtbox_query.iter() {
+            if target_entity == projectile.owner || !can_hit(*projectile_team, *team, &settings) {
+                continue;
+            }
+            if hurtbox.invulnerable || projectile.hit_entities.contains(&target_entity) {
+                continue;
+            }
+            if !check_aabb_collision(projectile_pos, projectile.size, hurtbox_transform.translation.truncate(), hurtbox.size) {
+                continue;
+            }
+
+            projectile.hit_entities.push(target_entity);
+            damage_events.send(DealDamage {
+                target: target_entity,
+                instance: DamageInstance::new(projectile.damage, projectile.damage_type, Some(projectile.owner))
+                    .with_tag(DamageTag::Projectile),
+            });
+            if let Some(status) = projectile.status {
+                status_events.send(ApplyStatus::on_hit(target_entity, status));
+            }
+
+            // Out of piercing
+            if projectile.hit_entities.len() as u32 > projectile.piercing {
+                commands.entity(entity).despawn();
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,630 @@
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::netcode::PlayerSnapshot;
+use crate::network::GameMessage;
+use crate::replication::{EntityState, ReplicatedKind, WorldSnapshot};
+use crate::resources::RoomStatus;
+use crate::transport::RawPayload;
+
+// ============= Wire Format =============
+//
+// Binary frame: [MAGIC][version u16 LE][kind u8][bincode body]
+// JSON debug frame: {"version": N, "message": {...}} sent as a text payload
+
+pub const PROTOCOL_VERSION: u16 = 14;
+
+const MAGIC: u8 = 0xA7;
+const HEADER_LEN: usize = 4;
+const MAX_BODY_BYTES: u64 = 64 * 1024;
+
+// Positions are stored in 1/16 px, velocities in 1/8 px/s
+const POSITION_SCALE: f32 = 16.0;
+const VELOCITY_SCALE: f32 = 8.0;
+
+static JSON_DEBUG: AtomicBool = AtomicBool::new(false);
+
+/// Send readable JSON instead of binary frames. Receivers accept both either way.
+pub fn set_json_debug(enabled: bool) {
+    JSON_DEBUG.store(enabled, Ordering::Relaxed);
+}
+
+pub fn json_debug_enabled() -> bool {
+    JSON_DEBUG.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MessageKind {
+    PlayerInput = 1,
+    LobbySync = 2,
+    PlayerJoined = 3,
+    PlayerLeft = 4,
+    Snapshot = 5,
+    SnapshotAck = 6,
+    RunSeed = 7,
+    PlayerBoons = 8,
+    PlayerCurses = 9,
+    RequestNextRoom = 10,
+    EnterRoom = 11,
+    PickupCollected = 12,
+}
+
+impl MessageKind {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(MessageKind::PlayerInput),
+            2 => Some(MessageKind::LobbySync),
+            3 => Some(MessageKind::PlayerJoined),
+            4 => Some(MessageKind::PlayerLeft),
+            5 => Some(MessageKind::Snapshot),
+            6 => Some(MessageKind::SnapshotAck),
+            7 => Some(MessageKind::RunSeed),
+            8 => Some(MessageKind::PlayerBoons),
+            9 => Some(MessageKind::PlayerCurses),
+            10 => Some(MessageKind::RequestNextRoom),
+            11 => Some(MessageKind::EnterRoom),
+            12 => Some(MessageKind::PickupCollected),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    Truncated,
+    NotAGameFrame,
+    VersionMismatch { theirs: u16 },
+    UnknownKind(u8),
+    Malformed(String),
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::Truncated => write!(f, "frame shorter than header"),
+            ProtocolError::NotAGameFrame => write!(f, "missing protocol magic"),
+            ProtocolError::VersionMismatch { theirs } => {
+                write!(f, "protocol v{} but we speak v{}", theirs, PROTOCOL_VERSION)
+            }
+            ProtocolError::UnknownKind(kind) => write!(f, "unknown message kind {}", kind),
+            ProtocolError::Malformed(reason) => write!(f, "malformed body: {}", reason),
+        }
+    }
+}
+
+// ============= Quantisation =============
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct QuantizedVec2 {
+    pub x: i16,
+    pub y: i16,
+}
+
+impl QuantizedVec2 {
+    fn encode(value: [f32; 2], scale: f32) -> Self {
+        let quantize = |v: f32| (v * scale).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        Self {
+            x: quantize(value[0]),
+            y: quantize(value[1]),
+        }
+    }
+
+    fn decode(self, scale: f32) -> [f32; 2] {
+        [self.x as f32 / scale, self.y as f32 / scale]
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct WirePlayerSnapshot {
+    player_id: String,
+    last_input: u32,
+    position: QuantizedVec2,
+    velocity: QuantizedVec2,
+    health: f32,
+    is_dashing: bool,
+    dash_direction: [f32; 2],
+    dash_elapsed: u32,
+    dash_cooldown_elapsed: u32,
+    buffered_dash: u32,
+    invulnerable: bool,
+    dead: bool,
+    move_speed: f32,
+    incapacitated: bool,
+}
+
+impl WirePlayerSnapshot {
+    fn encode(snapshot: &PlayerSnapshot) -> Self {
+        Self {
+            player_id: snapshot.player_id.clone(),
+            last_input: snapshot.last_input,
+            position: QuantizedVec2::encode(snapshot.position, POSITION_SCALE),
+            velocity: QuantizedVec2::encode(snapshot.velocity, VELOCITY_SCALE),
+            health: snapshot.health,
+            is_dashing: snapshot.is_dashing,
+            dash_direction: snapshot.dash_direction,
+            dash_elapsed: snapshot.dash_elapsed,
+            dash_cooldown_elapsed: snapshot.dash_cooldown_elapsed,
+            buffered_dash: snapshot.buffered_dash,
+            invulnerable: snapshot.invulnerable,
+            dead: snapshot.dead,
+            move_speed: snapshot.move_speed,
+            incapacitated: snapshot.incapacitated,
+        }
+    }
+
+    fn decode(self) -> PlayerSnapshot {
+        PlayerSnapshot {
+            player_id: self.player_id,
+            last_input: self.last_input,
+            position: self.position.decode(POSITION_SCALE),
+            velocity: self.velocity.decode(VELOCITY_SCALE),
+            health: self.health,
+            is_dashing: self.is_dashing,
+            dash_direction: self.dash_direction,
+            dash_elapsed: self.dash_elapsed,
+            dash_cooldown_elapsed: self.dash_cooldown_elapsed,
+            buffered_dash: self.buffered_dash,
+            invulnerable: self.invulnerable,
+            dead: self.dead,
+            move_speed: self.move_speed,
+            incapacitated: self.incapacitated,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct WireEntityState {
+    kind: ReplicatedKind,
+    position: QuantizedVec2,
+    velocity: QuantizedVec2,
+    health: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WireWorldSnapshot {
+    tick: u32,
+    baseline: Option<u32>,
+    server_time: f64,
+    local_player: Option<WirePlayerSnapshot>,
+    room: RoomStatus,
+    changed: Vec<(u32, WireEntityState)>,
+    removed: Vec<u32>,
+}
+
+impl WireWorldSnapshot {
+    fn encode(snapshot: &WorldSnapshot) -> Self {
+        Self {
+            tick: snapshot.tick,
+            baseline: snapshot.baseline,
+            server_time: snapshot.server_time,
+            local_player: snapshot.local_player.as_ref().map(WirePlayerSnapshot::encode),
+            room: snapshot.room,
+            changed: snapshot
+                .changed
+                .iter()
+                .map(|(id, state)| {
+                    let wire = WireEntityState {
+                        kind: state.kind.clone(),
+                        position: QuantizedVec2::encode(state.position, POSITION_SCALE),
+                        velocity: QuantizedVec2::encode(state.velocity, VELOCITY_SCALE),
+                        health: state.health,
+                    };
+                    (*id, wire)
+                })
+                .collect(),
+            removed: snapshot.removed.clone(),
+        }
+    }
+
+    fn decode(self) -> WorldSnapshot {
+        WorldSnapshot {
+            tick: self.tick,
+            baseline: self.baseline,
+            server_time: self.server_time,
+            local_player: self.local_player.map(WirePlayerSnapshot::decode),
+            room: self.room,
+            changed: self
+                .changed
+                .into_iter()
+                .map(|(id, wire)| {
+                    let state = EntityState {
+                        kind: wire.kind,
+                        position: wire.position.decode(POSITION_SCALE),
+                        velocity: wire.velocity.decode(VELOCITY_SCALE),
+                        health: wire.health,
+                    };
+                    (id, state)
+                })
+                .collect(),
+            removed: self.removed,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonEnvelopeRef<'a> {
+    version: u16,
+    message: &'a GameMessage,
+}
+
+#[derive(Deserialize)]
+struct JsonEnvelope {
+    version: u16,
+    message: GameMessage,
+}
+
+fn wire_options() -> impl Options {
+    bincode::DefaultOptions::new().with_limit(MAX_BODY_BYTES)
+}
+
+fn malformed(error: impl std::fmt::Display) -> ProtocolError {
+    ProtocolError::Malformed(error.to_string())
+}
+
+// ============= Encode / Decode =============
+
+pub fn encode(message: &GameMessage) -> Result<RawPayload, ProtocolError> {
+    if json_debug_enabled() {
+        encode_json(message)
+    } else {
+        encode_binary(message)
+    }
+}
+
+fn encode_json(message: &GameMessage) -> Result<RawPayload, ProtocolError> {
+    let envelope = JsonEnvelopeRef {
+        version: PROTOCOL_VERSION,
+        message,
+    };
+    serde_json::to_string(&envelope)
+        .map(RawPayload::Text)
+        .map_err(malformed)
+}
+
+fn encode_binary(message: &GameMessage) -> Result<RawPayload, ProtocolError> {
+    let (kind, body) = match message {
+        GameMessage::PlayerInput { player_id, frames } => {
+            (MessageKind::PlayerInput, wire_options().serialize(&(player_id, frames)))
+        }
+        GameMessage::Snapshot { snapshot } => {
+            (MessageKind::Snapshot, wire_options().serialize(&WireWorldSnapshot::encode(snapshot)))
+        }
+        GameMessage::SnapshotAck { tick } => {
+            (MessageKind::SnapshotAck, wire_options().serialize(tick))
+        }
+        GameMessage::LobbySync { lobby } => {
+            (MessageKind::LobbySync, wire_options().serialize(lobby))
+        }
+        GameMessage::PlayerJoined { player_id, weapon } => {
+            (MessageKind::PlayerJoined, wire_options().serialize(&(player_id, weapon)))
+        }
+        GameMessage::PlayerLeft { player_id } => {
+            (MessageKind::PlayerLeft, wire_options().serialize(player_id))
+        }
+        GameMessage::RunSeed { seed } => {
+            (MessageKind::RunSeed, wire_options().serialize(seed))
+        }
+        GameMessage::PlayerBoons { player_id, boons } => {
+            (MessageKind::PlayerBoons, wire_options().serialize(&(player_id, boons)))
+        }
+        GameMessage::PlayerCurses { player_id, curses } => {
+            (MessageKind::PlayerCurses, wire_options().serialize(&(player_id, curses)))
+        }
+        GameMessage::RequestNextRoom { from_room } => {
+            (MessageKind::RequestNextRoom, wire_options().serialize(from_room))
+        }
+        GameMessage::EnterRoom { room_number } => {
+            (MessageKind::EnterRoom, wire_options().serialize(room_number))
+        }
+        GameMessage::PickupCollected { pickup_type, amount } => {
+            (MessageKind::PickupCollected, wire_options().serialize(&(pickup_type, amount)))
+        }
+    };
+    let body = body.map_err(malformed)?;
+
+    let mut frame = Vec::with_capacity(HEADER_LEN + body.len());
+    frame.push(MAGIC);
+    frame.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    frame.push(kind as u8);
+    frame.extend_from_slice(&body);
+    Ok(RawPayload::Binary(frame))
+}
+
+pub fn decode(payload: &RawPayload) -> Result<GameMessage, ProtocolError> {
+    match payload {
+        RawPayload::Text(text) => decode_json(text.as_bytes()),
+        RawPayload::Binary(bytes) if bytes.first() == Some(&b'{') => decode_json(bytes),
+        RawPayload::Binary(bytes) => decode_binary(bytes),
+    }
+}
+
+fn decode_json(bytes: &[u8]) -> Result<GameMessage, ProtocolError> {
+    let envelope: JsonEnvelope = serde_json::from_slice(bytes).map_err(malformed)?;
+    if envelope.version != PROTOCOL_VERSION {
+        return Err(ProtocolError::VersionMismatch { theirs: envelope.version });
+    }
+    Ok(envelope.message)
+}
+
+fn decode_binary(bytes: &[u8]) -> Result<GameMessage, ProtocolError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(ProtocolError::Truncated);
+    }
+    if bytes[0] != MAGIC {
+        return Err(ProtocolError::NotAGameFrame);
+    }
+
+    let version = u16::from_le_bytes([bytes[1], bytes[2]]);
+    if version != PROTOCOL_VERSION {
+        return Err(ProtocolError::VersionMismatch { theirs: version });
+    }
+
+    let kind = MessageKind::from_byte(bytes[3]).ok_or(ProtocolError::UnknownKind(bytes[3]))?;
+    let body = &bytes[HEADER_LEN..];
+
+    let message = match kind {
+        MessageKind::PlayerInput => {
+            let (player_id, frames) = wire_options().deserialize(body).map_err(malformed)?;
+            GameMessage::PlayerInput { player_id, frames }
+        }
+        MessageKind::Snapshot => {
+            let wire: WireWorldSnapshot = wire_options().deserialize(body).map_err(malformed)?;
+            GameMessage::Snapshot {
+                snapshot: wire.decode(),
+            }
+        }
+        MessageKind::SnapshotAck => GameMessage::SnapshotAck {
+            tick: wire_options().deserialize(body).map_err(malformed)?,
+        },
+        MessageKind::LobbySync => GameMessage::LobbySync {
+            lobby: wire_options().deserialize(body).map_err(malformed)?,
+        },
+        MessageKind::PlayerJoined => {
+            let (player_id, weapon) = wire_options().deserialize(body).map_err(malformed)?;
+            GameMessage::PlayerJoined { player_id, weapon }
+        }
+        MessageKind::PlayerLeft => GameMessage::PlayerLeft {
+            player_id: wire_options().deserialize(body).map_err(malformed)?,
+        },
+        MessageKind::RunSeed => GameMessage::RunSeed {
+            seed: wire_options().deserialize(body).map_err(malformed)?,
+        },
+        MessageKind::PlayerBoons => {
+            let (player_id, boons) = wire_options().deserialize(body).map_err(malformed)?;
+            GameMessage::PlayerBoons { player_id, boons }
+        }
+        MessageKind::PlayerCurses => {
+            let (player_id, curses) = wire_options().deserialize(body).map_err(malformed)?;
+            GameMessage::PlayerCurses { player_id, curses }
+        }
+        MessageKind::RequestNextRoom => GameMessage::RequestNextRoom {
+            from_room: wire_options().deserialize(body).map_err(malformed)?,
+        },
+        MessageKind::EnterRoom => GameMessage::EnterRoom {
+            room_number: wire_options().deserialize(body).map_err(malformed)?,
+        },
+        MessageKind::PickupCollected => {
+            let (pickup_type, amount) = wire_options().deserialize(body).map_err(malformed)?;
+            GameMessage::PickupCollected { pickup_type, amount }
+        }
+    };
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::Vec2;
+    use crate::components::{AIState, BoonClaim, BoonRarity, InputAction, InputFrame, PickupType, Team};
+    use crate::replication::WorldSnapshot;
+    use crate::resources::{CurseClaim, CurseKind, LobbySession, PlayerData};
+
+    // Values sit on the quantisation grid so snapshots survive the trip exactly
+    fn snapshot() -> WorldSnapshot {
+        let player = PlayerSnapshot {
+            player_id: "player_2".to_string(),
+            last_input: 41,
+            position: [120.5, -64.25],
+            velocity: [-300.0, 12.125],
+            health: 87.5,
+            is_dashing: true,
+            dash_direction: [0.6, 0.8],
+            dash_elapsed: 3,
+            dash_cooldown_elapsed: 7,
+            buffered_dash: 2,
+            invulnerable: true,
+            dead: false,
+            move_speed: 225.0,
+            incapacitated: false,
+        };
+        let state = |kind, position, velocity, health| EntityState { kind, position, velocity, health };
+        WorldSnapshot {
+            tick: 300,
+            baseline: Some(298),
+            server_time: 12.75,
+            local_player: Some(player),
+            room: RoomStatus { room_number: 4, cleared: true },
+            changed: vec![
+                (1, state(ReplicatedKind::Player { player_id: "player_1".to_string(), downed: true }, [0.0, 0.0], [0.0, 0.0], 0.0)),
+                (2, state(ReplicatedKind::Enemy { archetype: "skeleton".to_string(), ai_state: AIState::Chasing, phase: 1 }, [-10.0625, 33.0], [40.0, -0.5], 55.0)),
+                (3, state(ReplicatedKind::Projectile { team: Team::Player }, [200.0, 100.0], [-640.0, 0.0], 0.0)),
+                (4, state(ReplicatedKind::Pickup { pickup_type: PickupType::Gold, value: 12.0 }, [5.0, 5.0], [0.0, 0.0], 0.0)),
+                (5, state(ReplicatedKind::Projectile { team: Team::Enemy }, [-32.0, 8.5], [0.0, 250.0], 0.0)),
+            ],
+            removed: vec![7, 9],
+        }
+    }
+
+    /// One message of every kind, with the kind byte it must go out under
+    fn every_kind() -> Vec<(GameMessage, MessageKind)> {
+        vec![
+            (
+                GameMessage::PlayerInput {
+                    player_id: "player_2".to_string(),
+                    frames: vec![
+                        InputFrame { sequence: 40, actions: vec![InputAction::Move(Vec2::new(1.0, 0.0))] },
+                        InputFrame { sequence: 41, actions: vec![InputAction::Dash, InputAction::LightAttack] },
+                    ],
+                },
+                MessageKind::PlayerInput,
+            ),
+            (
+                GameMessage::LobbySync {
+                    lobby: LobbySession {
+                        round: 2,
+                        is_host: true,
+                        players: vec![PlayerData {
+                            id: "player_1".to_string(),
+                            position: Vec2::new(1.5, -2.0),
+                            velocity: Vec2::ZERO,
+                            health: 100.0,
+                        }],
+                    },
+                },
+                MessageKind::LobbySync,
+            ),
+            (
+                GameMessage::PlayerJoined { player_id: "player_2".to_string(), weapon: "spear".to_string() },
+                MessageKind::PlayerJoined,
+            ),
+            (GameMessage::PlayerLeft { player_id: "player_2".to_string() }, MessageKind::PlayerLeft),
+            (GameMessage::Snapshot { snapshot: snapshot() }, MessageKind::Snapshot),
+            (GameMessage::SnapshotAck { tick: 300 }, MessageKind::SnapshotAck),
+            (GameMessage::RunSeed { seed: 0xDEAD_BEEF_F00D }, MessageKind::RunSeed),
+            (
+                GameMessage::PlayerBoons {
+                    player_id: "player_2".to_string(),
+                    boons: vec![BoonClaim { name: "Keen Eye".to_string(), rarity: BoonRarity::Epic, stacks: 2 }],
+                },
+                MessageKind::PlayerBoons,
+            ),
+            (
+                GameMessage::PlayerCurses {
+                    player_id: "player_2".to_string(),
+                    curses: vec![CurseClaim { kind: CurseKind::Blind, rooms_remaining: Some(2) }],
+                },
+                MessageKind::PlayerCurses,
+            ),
+            (GameMessage::RequestNextRoom { from_room: 4 }, MessageKind::RequestNextRoom),
+            (GameMessage::EnterRoom { room_number: 5 }, MessageKind::EnterRoom),
+            (GameMessage::PickupCollected { pickup_type: PickupType::Soul, amount: 3 }, MessageKind::PickupCollected),
+        ]
+    }
+
+    fn binary(message: &GameMessage) -> Vec<u8> {
+        match encode_binary(message).expect("encodable message") {
+            RawPayload::Binary(bytes) => bytes,
+            RawPayload::Text(_) => panic!("binary encoding produced text"),
+        }
+    }
+
+    #[test]
+    fn every_kind_round_trips_in_binary() {
+        for (message, kind) in every_kind() {
+            let bytes = binary(&message);
+            assert_eq!(bytes[0], MAGIC);
+            assert_eq!(u16::from_le_bytes([bytes[1], bytes[2]]), PROTOCOL_VERSION);
+            assert_eq!(bytes[3], kind as u8, "{:?}", kind);
+            assert_eq!(MessageKind::from_byte(bytes[3]), Some(kind));
+            assert_eq!(decode(&RawPayload::Binary(bytes)).expect("decodable frame"), message);
+        }
+    }
+
+    #[test]
+    fn every_kind_round_trips_in_json() {
+        for (message, _) in every_kind() {
+            let payload = encode_json(&message).expect("encodable message");
+            let RawPayload::Text(text) = &payload else {
+                panic!("JSON encoding produced binary");
+            };
+            assert_eq!(decode(&payload).expect("decodable text"), message);
+            // Some transports hand text over as bytes
+            assert_eq!(decode(&RawPayload::Binary(text.clone().into_bytes())).expect("decodable bytes"), message);
+        }
+    }
+
+    #[test]
+    fn rejects_other_protocol_versions() {
+        let mut bytes = binary(&GameMessage::SnapshotAck { tick: 1 });
+        bytes[1..3].copy_from_slice(&(PROTOCOL_VERSION - 1).to_le_bytes());
+        assert!(matches!(
+            decode(&RawPayload::Binary(bytes)),
+            Err(ProtocolError::VersionMismatch { theirs }) if theirs == PROTOCOL_VERSION - 1
+        ));
+
+        let text = format!(r#"{{"version":{},"message":{{"type":"SnapshotAck","tick":1}}}}"#, PROTOCOL_VERSION + 1);
+        assert!(matches!(
+            decode(&RawPayload::Text(text)),
+            Err(ProtocolError::VersionMismatch { theirs }) if theirs == PROTOCOL_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn rejects_frames_shorter_than_the_header() {
+        let bytes = binary(&GameMessage::SnapshotAck { tick: 1 });
+        for len in 0..HEADER_LEN {
+            assert!(matches!(decode(&RawPayload::Binary(bytes[..len].to_vec())), Err(ProtocolError::Truncated)), "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn rejects_frames_without_the_magic_byte() {
+        let mut bytes = binary(&GameMessage::SnapshotAck { tick: 1 });
+        bytes[0] = 0x00;
+        assert!(matches!(decode(&RawPayload::Binary(bytes)), Err(ProtocolError::NotAGameFrame)));
+    }
+
+    #[test]
+    fn rejects_unknown_kinds() {
+        for kind in [0, 13, u8::MAX] {
+            let mut bytes = binary(&GameMessage::SnapshotAck { tick: 1 });
+            bytes[3] = kind;
+            assert!(matches!(decode(&RawPayload::Binary(bytes)), Err(ProtocolError::UnknownKind(theirs)) if theirs == kind));
+        }
+    }
+
+    #[test]
+    fn rejects_a_cut_off_body() {
+        let bytes = binary(&GameMessage::Snapshot { snapshot: snapshot() });
+        let cut = bytes[..bytes.len() - 1].to_vec();
+        assert!(matches!(decode(&RawPayload::Binary(cut)), Err(ProtocolError::Malformed(_))));
+    }
+
+    #[test]
+    fn quantisation_keeps_half_a_step_of_precision() {
+        for value in [0.0, 0.01, -0.03, 1.0 / 3.0, 99.99, -1234.567, 2047.9] {
+            let position = QuantizedVec2::encode([value, -value], POSITION_SCALE).decode(POSITION_SCALE);
+            assert!((position[0] - value).abs() <= 0.5 / POSITION_SCALE, "position {}", value);
+            assert!((position[1] + value).abs() <= 0.5 / POSITION_SCALE, "position {}", -value);
+
+            let velocity = QuantizedVec2::encode([value, -value], VELOCITY_SCALE).decode(VELOCITY_SCALE);
+            assert!((velocity[0] - value).abs() <= 0.5 / VELOCITY_SCALE, "velocity {}", value);
+            assert!((velocity[1] + value).abs() <= 0.5 / VELOCITY_SCALE, "velocity {}", -value);
+        }
+    }
+
+    #[test]
+    fn quantisation_clamps_at_the_i16_bounds() {
+        let max_position = i16::MAX as f32 / POSITION_SCALE;
+        let min_position = i16::MIN as f32 / POSITION_SCALE;
+        assert_eq!(
+            QuantizedVec2::encode([max_position, min_position], POSITION_SCALE),
+            QuantizedVec2 { x: i16::MAX, y: i16::MIN }
+        );
+        assert_eq!(
+            QuantizedVec2::encode([1.0e6, -1.0e6], POSITION_SCALE),
+            QuantizedVec2 { x: i16::MAX, y: i16::MIN }
+        );
+        assert_eq!(
+            QuantizedVec2::encode([f32::INFINITY, f32::NEG_INFINITY], POSITION_SCALE).decode(POSITION_SCALE),
+            [max_position, min_position]
+        );
+
+        let max_velocity = i16::MAX as f32 / VELOCITY_SCALE;
+        assert_eq!(
+            QuantizedVec2::encode([5000.0, -5000.0], VELOCITY_SCALE).decode(VELOCITY_SCALE),
+            [max_velocity, i16::MIN as f32 / VELOCITY_SCALE]
+        );
+    }
+}
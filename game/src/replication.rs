@@ -0,0 +1,613 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use crate::combat::Dead;
+use crate::components::*;
+use crate::archetypes::{EnemyArchetype, EnemyArchetypes};
+use crate::boss::spawn_boss;
+use crate::enemy::{enemy_projectile, spawn_enemy};
+use crate::weapons::player_projectile;
+use crate::projectiles::spawn_projectile;
+use crate::netcode::{has_authority, PlayerSnapshot};
+use crate::network::{GameMessage, NetworkState, PeerMessageReceived};
+use crate::protocol;
+use crate::resources::{GameState, RoomStatus};
+use crate::room::spawn_pickup;
+use crate::transport::ActiveTransport;
+
+// ============= Entity Replication =============
+//
+// The host tags every player, enemy, projectile and pickup with a `NetworkId`
+// and sends each peer a snapshot at `ReplicationConfig::tick_rate`. Snapshots
+// are deltas against the last tick that peer acknowledged. Clients rebuild the
+// full world state, spawn or despawn replicas to match it, and draw replicas
+// `interpolation_delay` seconds in the past so motion stays smooth between ticks.
+
+// Ticks of world state either side keeps around to decode/encode deltas
+const SNAPSHOT_HISTORY: usize = 64;
+// Samples kept per replica; far more than the interpolation delay needs
+const MAX_BUFFERED_SAMPLES: usize = 32;
+// Changes below the wire quantisation aren't worth sending
+const POSITION_EPSILON: f32 = 1.0 / 16.0;
+const VELOCITY_EPSILON: f32 = 1.0 / 8.0;
+// How quickly the estimated host clock follows new snapshots
+const CLOCK_SMOOTHING: f64 = 0.1;
+
+pub struct ReplicationPlugin;
+
+impl Plugin for ReplicationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplicationConfig>()
+            .init_resource::<ReplicationHost>()
+            .init_resource::<ReplicationClient>()
+            .add_systems(Update, (
+                receive_snapshots,
+                interpolate_replicas,
+            ).chain().run_if(not(has_authority)))
+            // Captured between simulation ticks, before positions are blended for drawing
+            .add_systems(FixedPostUpdate, (
+                adopt_replicas,
+                assign_network_ids,
+                receive_snapshot_acks,
+                send_snapshots,
+            ).chain().run_if(has_authority))
+            .add_systems(PostUpdate, despawn_unreplicated.run_if(not(has_authority)));
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct ReplicationConfig {
+    pub tick_rate: f32,           // Snapshots per second
+    pub interpolation_delay: f32, // Seconds replicas are drawn behind the host
+    pub max_extrapolation: f32,   // Seconds to keep moving a replica past its last sample
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            tick_rate: 20.0,
+            interpolation_delay: 0.1,
+            max_extrapolation: 0.25,
+        }
+    }
+}
+
+/// Identifies an entity across peers. Assigned by the host only.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NetworkId(pub u32);
+
+/// Spawned from host snapshots rather than simulated locally
+#[derive(Component)]
+pub struct Replica;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    time: f64, // Host clock
+    position: Vec2,
+    velocity: Vec2,
+}
+
+/// Recent authoritative positions of a replicated entity, oldest first
+#[derive(Component, Default)]
+pub struct SnapshotBuffer {
+    samples: VecDeque<Sample>,
+}
+
+impl SnapshotBuffer {
+    fn push(&mut self, sample: Sample) {
+        if self.samples.back().is_some_and(|last| last.time >= sample.time) {
+            return; // Stale or duplicate
+        }
+        self.samples.push_back(sample);
+        while self.samples.len() > MAX_BUFFERED_SAMPLES {
+            self.samples.pop_front();
+        }
+    }
+
+    fn position_at(&self, time: f64, max_extrapolation: f32) -> Option<Vec2> {
+        let newest = self.samples.back()?;
+        if time >= newest.time {
+            let ahead = ((time - newest.time) as f32).min(max_extrapolation);
+            return Some(newest.position + newest.velocity * ahead);
+        }
+
+        let oldest = self.samples.front()?;
+        if time <= oldest.time {
+            return Some(oldest.position);
+        }
+
+        self.samples
+            .iter()
+            .zip(self.samples.iter().skip(1))
+            .find(|(from, to)| time >= from.time && time <= to.time)
+            .map(|(from, to)| {
+                let t = ((time - from.time) / (to.time - from.time)) as f32;
+                from.position.lerp(to.position, t)
+            })
+    }
+
+    /// Drop samples that can no longer be interpolated from
+    fn prune(&mut self, time: f64) {
+        while self.samples.len() > 2 && self.samples[1].time <= time {
+            self.samples.pop_front();
+        }
+    }
+}
+
+// ============= Snapshot Types =============
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ReplicatedKind {
+    Player { player_id: String, downed: bool },
+    Enemy { archetype: String, ai_state: AIState, phase: usize },
+    Projectile { team: Team },
+    Pickup { pickup_type: PickupType, value: f32 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EntityState {
+    pub kind: ReplicatedKind,
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub health: f32,
+}
+
+impl EntityState {
+    fn differs_from(&self, baseline: &EntityState) -> bool {
+        self.kind != baseline.kind
+            || self.health != baseline.health
+            || Vec2::from(self.position).distance(Vec2::from(baseline.position)) >= POSITION_EPSILON
+            || Vec2::from(self.velocity).distance(Vec2::from(baseline.velocity)) >= VELOCITY_EPSILON
+    }
+}
+
+/// One tick of world state for one peer. `changed`/`removed` are relative to
+/// the `baseline` tick, or to an empty world when there is none.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorldSnapshot {
+    pub tick: u32,
+    pub baseline: Option<u32>,
+    pub server_time: f64,
+    pub local_player: Option<PlayerSnapshot>, // The receiving peer's own player
+    pub room: RoomStatus,
+    pub changed: Vec<(u32, EntityState)>,
+    pub removed: Vec<u32>,
+}
+
+type WorldState = HashMap<u32, EntityState>;
+
+#[derive(Resource, Default)]
+struct ReplicationHost {
+    next_id: u32,
+    tick: u32,
+    since_last_send: f32,
+    history: VecDeque<(u32, WorldState)>,
+    acked: HashMap<String, u32>,
+}
+
+#[derive(Resource, Default)]
+struct ReplicationClient {
+    host_id: Option<String>,
+    received: VecDeque<(u32, WorldState)>,
+    latest_tick: Option<u32>,
+    clock_offset: Option<f64>, // Host clock minus ours
+}
+
+// ============= Host =============
+
+type ReplicaQuery<'w, 's> = Query<'w, 's, Entity, Or<(With<Replica>, With<SnapshotBuffer>)>>;
+
+/// After a host migration, replicas we inherited become ours to simulate
+fn adopt_replicas(
+    mut commands: Commands,
+    mut host: ResMut<ReplicationHost>,
+    replicas: ReplicaQuery,
+    network_ids: Query<&NetworkId>,
+) {
+    if replicas.is_empty() {
+        return;
+    }
+    for entity in replicas.iter() {
+        commands.entity(entity).remove::<(Replica, SnapshotBuffer)>();
+    }
+    // Keep the old host's ids stable and never hand them out twice
+    let highest = network_ids.iter().map(|id| id.0).max().unwrap_or(0);
+    host.next_id = host.next_id.max(highest);
+}
+
+type UnassignedQuery<'w, 's> = Query<'w, 's, Entity, (
+    Without<NetworkId>,
+    Or<(With<Player>, With<Enemy>, With<Projectile>, With<Pickup>)>,
+)>;
+
+fn assign_network_ids(
+    mut commands: Commands,
+    mut host: ResMut<ReplicationHost>,
+    new_entities: UnassignedQuery,
+) {
+    for entity in new_entities.iter() {
+        host.next_id += 1;
+        commands.entity(entity).insert(NetworkId(host.next_id));
+    }
+}
+
+type ReplicatedEntityQuery<'w, 's> = Query<'w, 's, (
+    &'static NetworkId,
+    &'static Transform,
+    Option<&'static Velocity>,
+    Option<&'static Health>,
+    Option<&'static Player>,
+    Option<&'static Enemy>,
+    Option<&'static EnemyAI>,
+    Option<&'static Pickup>,
+    Option<&'static Team>,
+    Has<Dead>,
+)>;
+
+type SnapshotPlayerQuery<'w, 's> = Query<'w, 's, (
+    &'static Player,
+    &'static PendingInputs,
+    &'static Health,
+    &'static DashState,
+    &'static Velocity,
+    &'static Hurtbox,
+    &'static Transform,
+    &'static MovementStats,
+    Has<Dead>,
+    Has<Incapacitated>,
+)>;
+
+/// Everything a snapshot is built from
+#[derive(SystemParam)]
+struct SnapshotSources<'w, 's> {
+    players: SnapshotPlayerQuery<'w, 's>,
+    entities: ReplicatedEntityQuery<'w, 's>,
+    room: Res<'w, RoomStatus>,
+}
+
+fn send_snapshots(
+    time: Res<Time>,
+    config: Res<ReplicationConfig>,
+    network_state: Res<NetworkState>,
+    mut host: ResMut<ReplicationHost>,
+    transport: Option<ResMut<ActiveTransport>>,
+    sources: SnapshotSources,
+) {
+    let SnapshotSources { players, entities, room } = sources;
+    let Some(mut transport) = transport else {
+        return;
+    };
+    // No host id means nobody else is connected
+    if network_state.host_id.is_none() {
+        return;
+    }
+
+    host.since_last_send += time.delta_seconds();
+    if host.since_last_send < 1.0 / config.tick_rate.max(1.0) {
+        return;
+    }
+    host.since_last_send = 0.0;
+    host.tick += 1;
+    let tick = host.tick;
+
+    let world: WorldState = entities
+        .iter()
+        .map(|(id, transform, velocity, health, player, enemy, ai, pickup, team, is_dead)| {
+            let kind = if let Some(player) = player {
+                ReplicatedKind::Player { player_id: player.id.clone(), downed: is_dead }
+            } else if let Some(enemy) = enemy {
+                ReplicatedKind::Enemy {
+                    archetype: enemy.archetype.clone(),
+                    ai_state: ai.map_or(AIState::Idle, |ai| ai.state),
+                    phase: ai.map_or(0, |ai| ai.phase),
+                }
+            } else if let Some(pickup) = pickup {
+                ReplicatedKind::Pickup { pickup_type: pickup.pickup_type, value: pickup.value }
+            } else {
+                ReplicatedKind::Projectile { team: team.copied().unwrap_or(Team::Enemy) }
+            };
+            let velocity = velocity.map_or(Vec2::ZERO, |velocity| velocity.linear);
+            let state = EntityState {
+                kind,
+                position: [transform.translation.x, transform.translation.y],
+                velocity: [velocity.x, velocity.y],
+                health: health.map_or(0.0, |health| health.current),
+            };
+            (id.0, state)
+        })
+        .collect();
+
+    let host = &mut *host;
+    let peers = transport.0.peers();
+    host.acked.retain(|peer_id, _| peers.contains(peer_id));
+
+    for peer_id in peers {
+        let baseline = host
+            .acked
+            .get(&peer_id)
+            .and_then(|acked| host.history.iter().find(|(tick, _)| tick == acked));
+
+        let changed = world
+            .iter()
+            .filter(|(id, state)| {
+                baseline
+                    .and_then(|(_, old)| old.get(*id))
+                    .is_none_or(|old| state.differs_from(old))
+            })
+            .map(|(id, state)| (*id, state.clone()))
+            .collect();
+        let removed = baseline
+            .map(|(_, old)| old.keys().filter(|id| !world.contains_key(*id)).copied().collect())
+            .unwrap_or_default();
+
+        let local_player = players
+            .iter()
+            .find(|(player, ..)| player.id == peer_id)
+            .map(|(player, pending, health, dash_state, velocity, hurtbox, transform, stats, dead, incapacitated)| PlayerSnapshot {
+                dead,
+                move_speed: stats.current_speed,
+                incapacitated,
+                ..PlayerSnapshot::capture(player, pending, health, dash_state, velocity, hurtbox, transform)
+            });
+
+        let snapshot = WorldSnapshot {
+            tick,
+            baseline: baseline.map(|(tick, _)| *tick),
+            server_time: time.elapsed_seconds_f64(),
+            local_player,
+            room: *room,
+            changed,
+            removed,
+        };
+        match protocol::encode(&GameMessage::Snapshot { snapshot }) {
+            Ok(payload) => transport.0.send(&peer_id, &payload),
+            Err(e) => warn!("Failed to encode snapshot for {}: {}", peer_id, e),
+        }
+    }
+
+    host.history.push_back((tick, world));
+    while host.history.len() > SNAPSHOT_HISTORY {
+        host.history.pop_front();
+    }
+}
+
+fn receive_snapshot_acks(
+    mut host: ResMut<ReplicationHost>,
+    mut messages: EventReader<PeerMessageReceived>,
+) {
+    for PeerMessageReceived { peer_id, message } in messages.read() {
+        if let GameMessage::SnapshotAck { tick } = message {
+            let acked = host.acked.entry(peer_id.clone()).or_insert(*tick);
+            *acked = (*acked).max(*tick);
+        }
+    }
+}
+
+// ============= Client =============
+
+type ReplicaStateQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static NetworkId,
+    Option<&'static mut SnapshotBuffer>,
+    Option<&'static mut Health>,
+    Option<&'static mut EnemyAI>,
+    Has<Replica>,
+    Has<Dead>,
+), Without<LocalPlayer>>;
+
+/// The client's local copies of replicated entities and what it needs to spawn more
+#[derive(SystemParam)]
+struct ReplicaWorld<'w, 's> {
+    commands: Commands<'w, 's>,
+    archetypes: Res<'w, EnemyArchetypes>,
+    game_state: Res<'w, GameState>,
+    replicated: ReplicaStateQuery<'w, 's>,
+    unidentified_players: Query<'w, 's, (Entity, &'static Player), Without<NetworkId>>,
+}
+
+fn receive_snapshots(
+    time: Res<Time>,
+    network_state: Res<NetworkState>,
+    mut client: ResMut<ReplicationClient>,
+    mut room: ResMut<RoomStatus>,
+    transport: Option<ResMut<ActiveTransport>>,
+    mut messages: EventReader<PeerMessageReceived>,
+    replicas: ReplicaWorld,
+) {
+    let ReplicaWorld { mut commands, archetypes, game_state, mut replicated, unidentified_players } = replicas;
+    let Some(mut transport) = transport else {
+        return;
+    };
+    let Some(host_id) = network_state.host_id.clone() else {
+        return;
+    };
+    // A new host numbers its ticks and clock from scratch
+    if client.host_id.as_ref() != Some(&host_id) {
+        *client = ReplicationClient {
+            host_id: Some(host_id.clone()),
+            ..default()
+        };
+    }
+
+    let mut newest: Option<(f64, WorldState)> = None;
+    for PeerMessageReceived { peer_id, message } in messages.read() {
+        match message {
+            GameMessage::Snapshot { snapshot } if *peer_id == host_id => {
+                if client.latest_tick.is_some_and(|latest| snapshot.tick <= latest) {
+                    continue; // Arrived out of order
+                }
+                let Some(world) = client.rebuild(snapshot) else {
+                    continue; // Baseline already forgotten; the host falls back to a full snapshot
+                };
+
+                client.latest_tick = Some(snapshot.tick);
+                client.received.push_back((snapshot.tick, world.clone()));
+                while client.received.len() > SNAPSHOT_HISTORY {
+                    client.received.pop_front();
+                }
+                if let Ok(payload) = protocol::encode(&GameMessage::SnapshotAck { tick: snapshot.tick }) {
+                    transport.0.send(&host_id, &payload);
+                }
+                room.set_if_neq(snapshot.room);
+                newest = Some((snapshot.server_time, world));
+            }
+            _ => {}
+        }
+    }
+
+    let Some((server_time, world)) = newest else {
+        return;
+    };
+
+    let offset = server_time - time.elapsed_seconds_f64();
+    client.clock_offset = Some(match client.clock_offset {
+        Some(current) => current + (offset - current) * CLOCK_SMOOTHING,
+        None => offset,
+    });
+
+    let mut seen = HashMap::new();
+    for (entity, id, buffer, health, ai, is_replica, is_dead) in replicated.iter_mut() {
+        let Some(state) = world.get(&id.0) else {
+            if is_replica {
+                commands.entity(entity).despawn_recursive();
+            }
+            continue;
+        };
+        seen.insert(id.0, entity);
+
+        let sample = Sample {
+            time: server_time,
+            position: Vec2::from(state.position),
+            velocity: Vec2::from(state.velocity),
+        };
+        match buffer {
+            Some(mut buffer) => buffer.push(sample),
+            None => {
+                let mut buffer = SnapshotBuffer::default();
+                buffer.push(sample);
+                commands.entity(entity).insert(buffer);
+            }
+        }
+        if let Some(mut health) = health {
+            health.current = state.health;
+        }
+        if let (Some(mut ai), ReplicatedKind::Enemy { ai_state, phase, .. }) = (ai, &state.kind) {
+            // Only touch on change so telegraphs fire once per wind-up
+            if ai.state != *ai_state {
+                ai.state = *ai_state;
+            }
+            if ai.phase != *phase {
+                ai.phase = *phase;
+            }
+        }
+        if let ReplicatedKind::Player { downed: true, .. } = state.kind {
+            if !is_dead {
+                commands.entity(entity).insert(Dead);
+            }
+        }
+    }
+
+    for (id, state) in world.iter() {
+        if seen.contains_key(id) {
+            continue;
+        }
+        let position = Vec2::from(state.position);
+        let velocity = Vec2::from(state.velocity);
+
+        let entity = match &state.kind {
+            ReplicatedKind::Player { player_id, .. } => {
+                // Player bodies come from peer tracking; just learn their ids
+                if let Some((entity, _)) = unidentified_players.iter().find(|(_, player)| player.id == *player_id) {
+                    commands.entity(entity).insert(NetworkId(*id));
+                }
+                continue;
+            }
+            ReplicatedKind::Enemy { archetype, .. } => {
+                // Bosses need their phases for the health bar
+                let spawned = if archetypes.get(archetype).is_some_and(EnemyArchetype::is_boss) {
+                    spawn_boss(&mut commands, &archetypes, archetype, position, game_state.room_number, game_state.difficulty.ascension())
+                } else {
+                    spawn_enemy(&mut commands, &archetypes, archetype, position, game_state.room_number, None)
+                };
+                let Some(entity) = spawned else {
+                    continue;
+                };
+                entity
+            }
+            ReplicatedKind::Projectile { team } => {
+                // Hits, lifetime and walls are all resolved on the host, so the
+                // replica only needs to look right
+                let spec = match team {
+                    Team::Player => player_projectile(0.0, velocity.length(), 0.0),
+                    Team::Enemy | Team::Environment => enemy_projectile(0.0, velocity.length(), 0.0),
+                };
+                spawn_projectile(&mut commands, &spec, position.extend(0.0), velocity, Entity::PLACEHOLDER, *team)
+            }
+            ReplicatedKind::Pickup { pickup_type, value } => {
+                spawn_pickup(&mut commands, *pickup_type, position, *value)
+            }
+        };
+
+        let mut buffer = SnapshotBuffer::default();
+        buffer.push(Sample { time: server_time, position, velocity });
+        commands.entity(entity).insert((NetworkId(*id), Replica, buffer));
+    }
+}
+
+impl ReplicationClient {
+    fn rebuild(&self, snapshot: &WorldSnapshot) -> Option<WorldState> {
+        let mut world = match snapshot.baseline {
+            Some(baseline) => self
+                .received
+                .iter()
+                .find(|(tick, _)| *tick == baseline)
+                .map(|(_, world)| world.clone())?,
+            None => WorldState::new(),
+        };
+        for id in snapshot.removed.iter() {
+            world.remove(id);
+        }
+        for (id, state) in snapshot.changed.iter() {
+            world.insert(*id, state.clone());
+        }
+        Some(world)
+    }
+}
+
+fn interpolate_replicas(
+    time: Res<Time>,
+    config: Res<ReplicationConfig>,
+    client: Res<ReplicationClient>,
+    mut query: Query<(&mut SnapshotBuffer, &mut Transform), Without<LocalPlayer>>,
+) {
+    let Some(offset) = client.clock_offset else {
+        return;
+    };
+    let render_time = time.elapsed_seconds_f64() + offset - config.interpolation_delay as f64;
+
+    for (mut buffer, mut transform) in query.iter_mut() {
+        if let Some(position) = buffer.position_at(render_time, config.max_extrapolation) {
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+        }
+        buffer.prune(render_time);
+    }
+}
+
+type UnreplicatedQuery<'w, 's> = Query<'w, 's, Entity, (Or<(With<Enemy>, With<Projectile>, With<Pickup>)>, Without<Replica>)>;
+
+/// Enemies, projectiles and pickups belong to the host; anything a client
+/// spawned on its own (room generation, its own loot rolls) is discarded
+fn despawn_unreplicated(
+    mut commands: Commands,
+    network_state: Res<NetworkState>,
+    query: UnreplicatedQuery,
+) {
+    if network_state.host_id.is_none() {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
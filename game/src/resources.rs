@@ -1,34 +1,37 @@
 use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use crate::components::*;
+use crate::weapons::DEFAULT_WEAPON;
 
 // ============= Core Game State =============
 
+/// The local run. Which screen or phase the run is in lives in the
+/// `CurrentGameState` state, not here.
 #[derive(Resource, Debug, Clone)]
 pub struct GameState {
-    pub current_state: CurrentGameState,
     pub run_stats: RunStats,
     pub room_number: u32,
     pub biome: BiomeType,
     pub difficulty: DifficultyLevel,
-    pub paused: bool,
 }
 
 impl Default for GameState {
     fn default() -> Self {
         Self {
-            current_state: CurrentGameState::MainMenu,
             run_stats: RunStats::default(),
             room_number: 1,
             biome: BiomeType::Tutorial,
             difficulty: DifficultyLevel::Normal,
-            paused: false,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum CurrentGameState {
+    #[default]
     MainMenu,
     InRun,
     RoomTransition,
@@ -37,6 +40,15 @@ pub enum CurrentGameState {
     BossFight,
     Death,
     Victory,
+}
+
+/// Only exists while the player is out in a room. Gameplay plugins run in
+/// `PauseState::Running`, so pausing (or leaving for a menu) freezes all of them.
+#[derive(SubStates, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[source(CurrentGameState = CurrentGameState::InRun | CurrentGameState::Shop | CurrentGameState::BossFight)]
+pub enum PauseState {
+    #[default]
+    Running,
     Paused,
 }
 
@@ -59,6 +71,36 @@ pub enum DifficultyLevel {
     Ascension(u8),  // 1-20 ascension levels
 }
 
+impl DifficultyLevel {
+    /// 0 below ascension mode
+    pub fn ascension(self) -> u8 {
+        match self {
+            DifficultyLevel::Ascension(level) => level,
+            _ => 0,
+        }
+    }
+}
+
+// ============= Multiplayer Lobby =============
+
+/// Who is in the session and where, as the host sees it. Kept apart from
+/// `GameState`, which is the local run.
+#[derive(Resource, Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LobbySession {
+    pub round: u32,
+    pub is_host: bool,
+    pub players: Vec<PlayerData>,
+}
+
+/// Summary of one player entity, sorted by id in `LobbySession::players`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerData {
+    pub id: String,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub health: f32,
+}
+
 // ============= Run Statistics =============
 
 #[derive(Resource, Debug, Clone, Default)]
@@ -85,6 +127,8 @@ pub struct PlayerInventory {
     pub curses: Vec<Curse>,
     pub max_active_boons: usize,
     pub max_passive_boons: usize,
+    pub max_curses: usize,
+    pub weapon: String, // Picked on the main menu from the unlocked weapons
 }
 
 impl Default for PlayerInventory {
@@ -98,19 +142,59 @@ impl Default for PlayerInventory {
             curses: Vec::new(),
             max_active_boons: 3,
             max_passive_boons: 6,
+            max_curses: 1,
+            weapon: DEFAULT_WEAPON.to_string(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// Multiplier on gold picked up, from curse bonuses
+pub fn gold_find_multiplier(curses: &[Curse]) -> f32 {
+    1.0 + curses
+        .iter()
+        .map(|curse| match curse.bonus {
+            CurseBonus::GoldFind(bonus) => bonus,
+            _ => 0.0,
+        })
+        .sum::<f32>()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CurseKind {
+    Fragile,
+    Slow,
+    Blind,
+    Cursed,
+    Marked,
+    Doomed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Curse {
+    pub kind: CurseKind,
     pub name: String,
     pub description: String,
     pub effect: CurseEffect,
+    pub bonus: CurseBonus,
     pub duration: Option<u32>,  // Some curses last X rooms
 }
 
-#[derive(Debug, Clone)]
+/// What a client tells the host about one of its curses. The host rebuilds
+/// the curse itself rather than taking the client's numbers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CurseClaim {
+    pub kind: CurseKind,
+    pub rooms_remaining: Option<u32>,
+}
+
+impl Curse {
+    /// "Until healed" curses have no room count and lift on any heal
+    pub fn lifts_on_heal(&self) -> bool {
+        matches!(self.effect, CurseEffect::HealthDrain(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum CurseEffect {
     ReducedMaxHealth(f32),
     ReducedSpeed(f32),
@@ -118,6 +202,17 @@ pub enum CurseEffect {
     HealthDrain(f32),
     IncreasedEnemyDamage(f32),
     OneHitDeath,
+    Marked,  // Enemies target you first
+}
+
+/// What a curse pays for its penalty
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CurseBonus {
+    Damage(f32),
+    AttackSpeed(f32),
+    CritChance(f32),
+    GoldFind(f32),
+    AllStats(f32),
 }
 
 // ============= Room Generation Resources =============
@@ -132,14 +227,126 @@ pub struct CurrentRoom {
     pub hazards: Vec<Entity>,
 }
 
+/// The host's current room and whether it's cleared, sent with every
+/// snapshot so clients unlock their doors when the host does
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct RoomStatus {
+    pub room_number: u32,
+    pub cleared: bool,
+}
+
 #[derive(Resource)]
 pub struct RoomGenerator {
     pub seed: u64,
     pub rooms_generated: u32,
 }
 
+impl RoomGenerator {
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            rooms_generated: 0,
+        }
+    }
+
+    /// Build a generator from a human-shareable seed string (bug reports, daily challenges)
+    pub fn from_seed_str(seed: &str) -> Self {
+        Self::from_seed(seed_from_str(seed))
+    }
+}
+
+/// Stable FNV-1a hash so the same seed string maps to the same run on every platform
+pub fn seed_from_str(seed: &str) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    seed.trim().bytes().fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+// ============= Deterministic RNG Streams =============
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RngStream {
+    RoomLayout,  // Next room type
+    Exits,       // Door placement
+    Spawns,      // Enemy counts and type rolls
+    Combat,      // Crits and other hit rolls
+    Boons,       // Boon offers and rerolls
+    Loot,        // Enemy drop rolls
+}
+
+impl RngStream {
+    /// Room-scoped streams are re-derived per room so peers agree on room N
+    /// regardless of how much of the run-scoped streams they have consumed
+    fn is_room_scoped(self) -> bool {
+        matches!(self, RngStream::RoomLayout | RngStream::Exits | RngStream::Spawns)
+    }
+
+    fn salt(self) -> u64 {
+        match self {
+            RngStream::RoomLayout => 0x9e37_79b9_7f4a_7c15,
+            RngStream::Exits => 0xbf58_476d_1ce4_e5b9,
+            RngStream::Spawns => 0x94d0_49bb_1331_11eb,
+            RngStream::Combat => 0xd6e8_feb8_6659_fd93,
+            RngStream::Boons => 0xa076_1d64_78bd_642f,
+            RngStream::Loot => 0xe703_7ed1_a0b4_28db,
+        }
+    }
+}
+
+/// Every gameplay random decision in a run is drawn from one of these streams,
+/// all derived from `RoomGenerator::seed`
+#[derive(Resource)]
+pub struct RunRng {
+    pub seed: u64,
+    pub room_number: u32,
+    streams: HashMap<RngStream, StdRng>,
+}
+
+impl RunRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            room_number: 0,
+            streams: HashMap::new(),
+        }
+    }
+
+    /// Reseed the room-scoped streams for the given room number
+    pub fn begin_room(&mut self, room_number: u32) {
+        self.room_number = room_number;
+        self.streams.retain(|stream, _| !stream.is_room_scoped());
+    }
+
+    pub fn stream(&mut self, stream: RngStream) -> &mut StdRng {
+        let room_number = if stream.is_room_scoped() { self.room_number } else { 0 };
+        let seed = self.seed;
+        self.streams
+            .entry(stream)
+            .or_insert_with(|| StdRng::seed_from_u64(mix_seed(seed, stream, room_number)))
+    }
+}
+
+// SplitMix64 finaliser so neighbouring seeds/rooms give unrelated streams
+fn mix_seed(seed: u64, stream: RngStream, room_number: u32) -> u64 {
+    let mut z = seed
+        .wrapping_add(stream.salt())
+        .wrapping_add((room_number as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
 // ============= Combat Resources =============
 
+/// Hit rules. Only the host resolves hits, so only the host's copy matters.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct CombatSettings {
+    pub friendly_fire: bool, // Players' attacks hurt other players in co-op
+}
+
 #[derive(Resource, Default)]
 pub struct CombatLog {
     pub recent_damage: Vec<DamageEvent>,
@@ -156,7 +363,7 @@ pub struct DamageEvent {
     pub timestamp: f32,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DamageType {
     Physical,
     Fire,
@@ -164,7 +371,7 @@ pub enum DamageType {
     Lightning,
     Poison,
     Bleed,
-    True,  // Ignores armor
+    True,  // Ignores armor, resistances and damage reduction
 }
 
 // ============= Input Resources =============
@@ -188,25 +395,74 @@ impl InputSettings {
     }
 }
 
+/// Input frames the local player has already simulated, kept until the host
+/// acknowledges them so they can be resent and replayed after a correction
+#[derive(Resource, Default)]
+pub struct LocalInputHistory {
+    pub frames: VecDeque<InputFrame>,
+    last_sequence: u32,
+}
+
+impl LocalInputHistory {
+    // Bounds memory when offline or hosting, where nothing is ever acknowledged
+    const MAX_FRAMES: usize = 240;
+
+    pub fn next_sequence(&mut self) -> u32 {
+        self.last_sequence = self.last_sequence.wrapping_add(1);
+        self.last_sequence
+    }
+
+    pub fn record(&mut self, frame: InputFrame) {
+        self.frames.push_back(frame);
+        while self.frames.len() > Self::MAX_FRAMES {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Forget everything up to and including `sequence`
+    pub fn acknowledge(&mut self, sequence: u32) {
+        self.frames.retain(|frame| frame.sequence > sequence);
+    }
+}
+
 // ============= Wave/Spawn Management =============
 
-#[derive(Resource)]
+/// The current room's enemies still to come. Each wave is announced with
+/// spawn warnings and appears once `spawn_timer` runs out.
+#[derive(Resource, Debug, Default)]
 pub struct WaveManager {
-    pub current_wave: u32,
-    pub enemies_per_wave: Vec<u32>,
-    pub wave_timer: Timer,
-    pub spawn_timer: Timer,
+    pub current_wave: u32, // Waves released so far
+    pub enemies_per_wave: Vec<u32>, // Sizes of the waves still queued, next first; empty ones just take up their interval
+    pub wave_timer: FrameTimer, // Until the next wave is due
+    pub spawn_timer: FrameTimer, // Warning before `incoming` appears
     pub spawn_queue: Vec<EnemySpawnData>,
+    pub incoming: Vec<EnemySpawnData>,
+    pub last_wave_size: u32,
+}
+
+impl WaveManager {
+    pub fn new(spawn_queue: Vec<EnemySpawnData>, enemies_per_wave: Vec<u32>) -> Self {
+        Self {
+            enemies_per_wave,
+            spawn_queue,
+            ..default()
+        }
+    }
+
+    /// Every wave has been released and has appeared
+    pub fn is_finished(&self) -> bool {
+        self.spawn_queue.is_empty() && self.incoming.is_empty()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct EnemySpawnData {
-    pub enemy_type: EnemyType,
+    pub archetype: String,
     pub position: Vec2,
     pub elite_modifier: Option<EliteModifier>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum EliteModifier {
     Armored,      // 1.5x HP, 50% damage reduction
     Berserker,    // 2x damage, +50% attack speed
@@ -223,6 +479,24 @@ pub struct BoonSelectionState {
     pub available_boons: Vec<BoonChoice>,
     pub reroll_cost: u32,
     pub rerolls_used: u32,
+    pub rarity: BoonRarity, // Rarity of the reward being chosen; picks the drop-rate row
+}
+
+impl BoonSelectionState {
+    pub fn new(rarity: BoonRarity) -> Self {
+        Self {
+            available_boons: Vec::new(),
+            reroll_cost: 25,
+            rerolls_used: 0,
+            rarity,
+        }
+    }
+}
+
+impl Default for BoonSelectionState {
+    fn default() -> Self {
+        Self::new(BoonRarity::Common)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -253,6 +527,21 @@ pub struct MetaProgression {
     pub permanent_upgrades: Vec<PermanentUpgrade>,
 }
 
+impl Default for MetaProgression {
+    fn default() -> Self {
+        Self {
+            account_level: 1,
+            total_runs: 0,
+            successful_runs: 0,
+            // Nothing unlocks weapons yet, so every archetype starts available
+            unlocked_weapons: ["sword", "spear", "bow", "gauntlets"].map(String::from).to_vec(),
+            unlocked_boons: Vec::new(),
+            unlocked_characters: Vec::new(),
+            permanent_upgrades: Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PermanentUpgrade {
     pub name: String,
@@ -1,51 +1,195 @@
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use rand::Rng;
 use crate::components::*;
 use crate::resources::*;
-use crate::enemy::{spawn_melee_enemy, spawn_ranged_enemy};
+use crate::curses::{in_shrine_range, roll_curse, should_spawn_shrine, spawn_curse_shrine};
+use crate::archetypes::EnemyArchetypes;
+use crate::enemy::{roll_elite_modifier, spawn_enemy};
+use crate::boss::spawn_boss;
+use crate::combat::Dead;
+use crate::network::{GameMessage, NetworkState, PeerConnected, PeerMessageReceived};
+use crate::movement::{MovementSet, ARENA_HALF_HEIGHT, ARENA_HALF_WIDTH};
+use crate::netcode::has_authority;
+use crate::protocol;
+use crate::transport::ActiveTransport;
 
 // Explicitly use our Direction enum to avoid ambiguity with any potential Bevy Direction
 use crate::components::Direction;
 
+// Chance an elite room holds one miniboss instead of its elites
+const MINIBOSS_CHANCE: f64 = 0.25;
+
+// From the Wave Spawning table in tuning-tables.md
+const WAVE_SHARES: [f32; 4] = [0.4, 0.3, 0.2, 0.1];
+const WAVE_INTERVAL_SECS: f32 = 5.0;
+// The next wave comes early once the last one is down to this fraction
+const WAVE_NEARLY_DEAD: f32 = 0.25;
+const SPAWN_WARNING_SECS: f32 = 1.0;
+// Five biomes of ten rooms, each ending in a boss; beating the last wins the run
+const FINAL_BOSS_ROOM: u32 = 50;
+const DOOR_INTERACT_RANGE: f32 = 50.0;
+
 pub struct RoomPlugin;
 
 impl Plugin for RoomPlugin {
     fn build(&self, app: &mut App) {
         app
             .init_resource::<RoomGenerator>()
-            .add_systems(Startup, setup_first_room)
+            .init_resource::<WaveManager>()
+            .init_resource::<RoomStatus>()
+            // Leaving the menu starts a run, on the host's seed
+            .add_systems(OnExit(CurrentGameState::MainMenu), (
+                broadcast_run_seed.run_if(has_authority),
+                setup_first_room,
+            ).chain())
+            .add_systems(Update, (
+                send_run_seed_to_new_peers.run_if(has_authority),
+                receive_run_seed.run_if(not(has_authority)),
+            ))
+            .add_systems(OnEnter(CurrentGameState::RoomTransition), room_transition)
+            .add_systems(OnEnter(CurrentGameState::Shop), spawn_shop)
             .add_systems(Update, (
-                check_room_clear,
+                // Clients open their doors when the host says the room is clear
+                check_room_clear.run_if(has_authority),
+                follow_host_room_clear.run_if(not(has_authority)),
                 handle_door_interaction,
-                room_transition,
+                receive_room_requests.run_if(has_authority),
+                follow_host_room.run_if(not(has_authority)),
                 spawn_room_rewards,
-                update_room_hazards,
-            ).chain());
+                check_victory,
+                buy_shop_items.run_if(in_state(CurrentGameState::Shop)),
+            ).chain().run_if(in_state(PauseState::Running)))
+            .add_systems(FixedUpdate, update_room_hazards
+                .before(MovementSet)
+                .run_if(in_state(PauseState::Running))
+                .run_if(has_authority))
+            .add_systems(FixedUpdate, (
+                run_waves.run_if(has_authority),
+                warn_of_waves.run_if(not(has_authority)),
+            ).run_if(in_state(PauseState::Running)));
     }
 }
 
 impl Default for RoomGenerator {
     fn default() -> Self {
-        Self {
-            seed: rand::random(),
-            rooms_generated: 0,
+        // A seed given at launch replays the same run every time
+        match launch_seed() {
+            Some(seed) => match seed.parse() {
+                Ok(number) => Self::from_seed(number), // As shown on the menu
+                Err(_) => Self::from_seed_str(&seed),
+            },
+            None => Self::from_seed(rand::random()),
+        }
+    }
+}
+
+/// `?seed=` on the page URL on the web, `ARPG_SEED` natively
+fn launch_seed() -> Option<String> {
+    #[cfg(target_arch = "wasm32")]
+    let seed = {
+        let search = web_sys::window()?.location().search().ok()?;
+        search
+            .trim_start_matches('?')
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("seed="))
+            .map(str::to_string)
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    let seed = std::env::var("ARPG_SEED").ok();
+
+    seed.filter(|seed| !seed.trim().is_empty())
+}
+
+/// Host: send the run's seed so every peer generates the same rooms. Runs
+/// before the first room is built.
+fn broadcast_run_seed(
+    room_gen: Res<RoomGenerator>,
+    transport: Option<ResMut<ActiveTransport>>,
+) {
+    let Some(mut transport) = transport else {
+        return;
+    };
+    match protocol::encode(&GameMessage::RunSeed { seed: room_gen.seed }) {
+        Ok(payload) => transport.0.broadcast(&payload),
+        Err(e) => warn!("Failed to encode run seed: {}", e),
+    }
+}
+
+/// Host: peers who connect later get the seed too
+fn send_run_seed_to_new_peers(
+    room_gen: Res<RoomGenerator>,
+    transport: Option<ResMut<ActiveTransport>>,
+    mut connected: EventReader<PeerConnected>,
+) {
+    let Some(mut transport) = transport else {
+        connected.clear();
+        return;
+    };
+    for PeerConnected { peer_id } in connected.read() {
+        match protocol::encode(&GameMessage::RunSeed { seed: room_gen.seed }) {
+            Ok(payload) => transport.0.send(peer_id, &payload),
+            Err(e) => warn!("Failed to encode run seed: {}", e),
+        }
+    }
+}
+
+/// Client: play the host's seed. Mid-run, the rooms still to come follow it.
+fn receive_run_seed(
+    network_state: Res<NetworkState>,
+    mut room_gen: ResMut<RoomGenerator>,
+    run_rng: Option<ResMut<RunRng>>,
+    mut messages: EventReader<PeerMessageReceived>,
+) {
+    let seed = messages
+        .read()
+        .filter(|event| network_state.host_id.as_ref() == Some(&event.peer_id))
+        .filter_map(|event| match event.message {
+            GameMessage::RunSeed { seed } => Some(seed),
+            _ => None,
+        })
+        .last();
+    let Some(seed) = seed else {
+        return;
+    };
+
+    if room_gen.seed != seed {
+        room_gen.seed = seed;
+    }
+    if let Some(mut run_rng) = run_rng {
+        if run_rng.seed != seed {
+            let room_number = run_rng.room_number;
+            *run_rng = RunRng::new(seed);
+            run_rng.begin_room(room_number);
         }
     }
 }
 
 fn setup_first_room(
     mut commands: Commands,
-    mut game_state: ResMut<GameState>,
     mut room_gen: ResMut<RoomGenerator>,
+    network_state: Res<NetworkState>,
+    game_state: Res<GameState>,
+    archetypes: Res<EnemyArchetypes>,
 ) {
+    // All random decisions for this run flow from the generator's seed
+    let mut run_rng = RunRng::new(room_gen.seed);
+    run_rng.begin_room(1);
+
     // Generate the first room (tutorial room)
     let room_entity = generate_room(
         &mut commands,
-        RoomType::Safe,
-        1,
-        BiomeType::Tutorial,
+        RoomSpec {
+            room_type: RoomType::Safe,
+            room_number: 1,
+            biome: BiomeType::Tutorial,
+            ascension: game_state.difficulty.ascension(),
+        },
         &mut room_gen,
+        &mut run_rng,
+        &archetypes,
     );
+    commands.insert_resource(run_rng);
     
     // Set up initial room state
     commands.insert_resource(CurrentRoom {
@@ -63,32 +207,42 @@ fn setup_first_room(
     });
     
     // Spawn player in first room
-    spawn_player(&mut commands, Vec2::ZERO);
+    spawn_player(&mut commands, &network_state.player_id, Vec2::ZERO, true);
 }
 
-fn generate_room(
-    commands: &mut Commands,
+/// Which room to build and where it sits in the run
+struct RoomSpec {
     room_type: RoomType,
     room_number: u32,
     biome: BiomeType,
+    ascension: u8,
+}
+
+fn generate_room(
+    commands: &mut Commands,
+    spec: RoomSpec,
     room_gen: &mut RoomGenerator,
+    run_rng: &mut RunRng,
+    archetypes: &EnemyArchetypes,
 ) -> Entity {
+    let RoomSpec { room_type, room_number, biome, ascension } = spec;
     room_gen.rooms_generated += 1;
     
     // Determine room properties based on type and progression
     let (enemy_count, exits, rewards) = match room_type {
         RoomType::Combat => {
             // Scale enemies based on room number (from Tuning Tables)
+            let rng = run_rng.stream(RngStream::Spawns);
             let count = match room_number {
-                1..=3 => rand::thread_rng().gen_range(2..=4),
-                4..=6 => rand::thread_rng().gen_range(3..=6),
-                7..=10 => rand::thread_rng().gen_range(4..=8),
-                11..=15 => rand::thread_rng().gen_range(5..=10),
-                16..=20 => rand::thread_rng().gen_range(6..=12),
-                _ => rand::thread_rng().gen_range(8..=15),
+                1..=3 => rng.gen_range(2..=4),
+                4..=6 => rng.gen_range(3..=6),
+                7..=10 => rng.gen_range(4..=8),
+                11..=15 => rng.gen_range(5..=10),
+                16..=20 => rng.gen_range(6..=12),
+                _ => rng.gen_range(8..=15),
             };
             
-            let exits = generate_exits();
+            let exits = generate_exits(run_rng);
             let rewards = vec![
                 RewardType::Gold(20 + room_number * 5),
                 RewardType::Boon(BoonRarity::Common),
@@ -99,7 +253,7 @@ fn generate_room(
         
         RoomType::Elite => {
             let count = (room_number / 5 + 1).min(3);
-            let exits = generate_exits();
+            let exits = generate_exits(run_rng);
             let rewards = vec![
                 RewardType::Gold(60 + room_number * 10),
                 RewardType::Boon(BoonRarity::Rare),
@@ -109,12 +263,12 @@ fn generate_room(
         }
         
         RoomType::Shop => {
-            let exits = generate_exits();
+            let exits = generate_exits(run_rng);
             (0, exits, vec![])
         }
         
         RoomType::Treasure => {
-            let exits = generate_exits();
+            let exits = generate_exits(run_rng);
             let rewards = vec![
                 RewardType::Gold(100 + room_number * 15),
                 RewardType::Boon(BoonRarity::Epic),
@@ -135,7 +289,7 @@ fn generate_room(
         }
         
         RoomType::Secret => {
-            let exits = generate_exits();
+            let exits = generate_exits(run_rng);
             let rewards = vec![
                 RewardType::Gold(150 + room_number * 20),
                 RewardType::Boon(BoonRarity::Epic),
@@ -145,7 +299,7 @@ fn generate_room(
         }
         
         RoomType::Safe => {
-            let exits = generate_exits();
+            let exits = generate_exits(run_rng);
             (0, exits, vec![RewardType::Heal(0.25)])
         }
     };
@@ -171,9 +325,22 @@ fn generate_room(
         spawn_door(commands, room_entity, direction, room_type);
     }
     
-    // Spawn enemies if combat room
-    if enemy_count > 0 && room_type != RoomType::Boss {
-        spawn_room_enemies(commands, room_entity, enemy_count, room_number, room_type);
+    // Bosses are there from the start; everyone else arrives in waves
+    let mut waves = WaveManager::default();
+    if room_type == RoomType::Boss {
+        spawn_room_boss(commands, room_entity, EnemyType::Boss, room_number, ascension, run_rng, archetypes);
+    } else if room_type == RoomType::Elite && run_rng.stream(RngStream::Spawns).gen_bool(MINIBOSS_CHANCE) {
+        spawn_room_boss(commands, room_entity, EnemyType::MiniBoss, room_number, ascension, run_rng, archetypes);
+    } else if enemy_count > 0 {
+        waves = plan_room_enemies(enemy_count, room_number, room_type, run_rng, archetypes);
+    }
+    commands.insert_resource(waves);
+    
+    // Curse shrines sit above the spawn point
+    let rng = run_rng.stream(RngStream::Spawns);
+    if should_spawn_shrine(room_type, rng) {
+        let curse = roll_curse(rng);
+        spawn_curse_shrine(commands, room_entity, Vec2::new(0.0, 150.0), curse);
     }
     
     // Spawn hazards based on biome
@@ -184,8 +351,8 @@ fn generate_room(
     room_entity
 }
 
-fn generate_exits() -> Vec<Direction> {
-    let mut rng = rand::thread_rng();
+fn generate_exits(run_rng: &mut RunRng) -> Vec<Direction> {
+    let rng = run_rng.stream(RngStream::Exits);
     let mut exits = vec![Direction::North]; // Always have at least one exit
     
     // Randomly add more exits (2-3 total exits common)
@@ -213,12 +380,12 @@ fn spawn_room_geometry(
     const WALL_THICKNESS: f32 = 20.0;
     
     let wall_color = match biome {
-        BiomeType::Tutorial => Color::rgb(0.4, 0.4, 0.4),
-        BiomeType::Biome1 => Color::rgb(0.5, 0.3, 0.2),
-        BiomeType::Biome2 => Color::rgb(0.3, 0.5, 0.3),
-        BiomeType::Biome3 => Color::rgb(0.3, 0.3, 0.5),
-        BiomeType::Biome4 => Color::rgb(0.5, 0.2, 0.5),
-        BiomeType::Biome5 => Color::rgb(0.6, 0.5, 0.3),
+        BiomeType::Tutorial => Color::srgb(0.4, 0.4, 0.4),
+        BiomeType::Biome1 => Color::srgb(0.5, 0.3, 0.2),
+        BiomeType::Biome2 => Color::srgb(0.3, 0.5, 0.3),
+        BiomeType::Biome3 => Color::srgb(0.3, 0.3, 0.5),
+        BiomeType::Biome4 => Color::srgb(0.5, 0.2, 0.5),
+        BiomeType::Biome5 => Color::srgb(0.6, 0.5, 0.3),
     };
     
     // Top wall
@@ -233,6 +400,7 @@ fn spawn_room_geometry(
             ..default()
         },
         Wall,
+        NavObstacle { size: Vec2::new(ROOM_WIDTH, WALL_THICKNESS), blocks: true },
     )).set_parent(room_entity);
     
     // Bottom wall
@@ -247,6 +415,7 @@ fn spawn_room_geometry(
             ..default()
         },
         Wall,
+        NavObstacle { size: Vec2::new(ROOM_WIDTH, WALL_THICKNESS), blocks: true },
     )).set_parent(room_entity);
     
     // Left wall
@@ -261,6 +430,7 @@ fn spawn_room_geometry(
             ..default()
         },
         Wall,
+        NavObstacle { size: Vec2::new(WALL_THICKNESS, ROOM_HEIGHT), blocks: true },
     )).set_parent(room_entity);
     
     // Right wall
@@ -275,16 +445,17 @@ fn spawn_room_geometry(
             ..default()
         },
         Wall,
+        NavObstacle { size: Vec2::new(WALL_THICKNESS, ROOM_HEIGHT), blocks: true },
     )).set_parent(room_entity);
     
     // Floor (visual only)
     let floor_color = match biome {
-        BiomeType::Tutorial => Color::rgb(0.2, 0.2, 0.2),
-        BiomeType::Biome1 => Color::rgb(0.3, 0.2, 0.1),
-        BiomeType::Biome2 => Color::rgb(0.2, 0.3, 0.2),
-        BiomeType::Biome3 => Color::rgb(0.2, 0.2, 0.3),
-        BiomeType::Biome4 => Color::rgb(0.3, 0.1, 0.3),
-        BiomeType::Biome5 => Color::rgb(0.4, 0.3, 0.2),
+        BiomeType::Tutorial => Color::srgb(0.2, 0.2, 0.2),
+        BiomeType::Biome1 => Color::srgb(0.3, 0.2, 0.1),
+        BiomeType::Biome2 => Color::srgb(0.2, 0.3, 0.2),
+        BiomeType::Biome3 => Color::srgb(0.2, 0.2, 0.3),
+        BiomeType::Biome4 => Color::srgb(0.3, 0.1, 0.3),
+        BiomeType::Biome5 => Color::srgb(0.4, 0.3, 0.2),
     };
     
     commands.spawn((
@@ -321,9 +492,9 @@ fn spawn_door(
     };
     
     let door_color = if room_type == RoomType::Boss {
-        Color::rgb(0.8, 0.2, 0.2) // Red for boss doors
+        Color::srgb(0.8, 0.2, 0.2) // Red for boss doors
     } else {
-        Color::rgb(0.4, 0.3, 0.2) // Brown for normal doors
+        Color::srgb(0.4, 0.3, 0.2) // Brown for normal doors
     };
     
     commands.spawn((
@@ -345,17 +516,19 @@ fn spawn_door(
     )).set_parent(room_entity);
 }
 
-fn spawn_room_enemies(
-    commands: &mut Commands,
-    room_entity: Entity,
+/// Roll the room's enemies and queue them in waves. Basic enemies come
+/// first; elites and supports are held back for the later waves.
+fn plan_room_enemies(
     enemy_count: u32,
     room_number: u32,
     room_type: RoomType,
-) {
-    let mut rng = rand::thread_rng();
+    run_rng: &mut RunRng,
+    archetypes: &EnemyArchetypes,
+) -> WaveManager {
+    let rng = run_rng.stream(RngStream::Spawns);
     
     // Spawn points for enemies
-    let spawn_points = vec![
+    let spawn_points = [
         Vec2::new(-200.0, 100.0),
         Vec2::new(200.0, 100.0),
         Vec2::new(-200.0, -100.0),
@@ -366,16 +539,15 @@ fn spawn_room_enemies(
         Vec2::new(300.0, 0.0),
     ];
     
-    for i in 0..enemy_count {
-        let spawn_pos = spawn_points[i as usize % spawn_points.len()];
-        
-        // Determine enemy type based on room type and progression
-        let enemy_type = if room_type == RoomType::Elite {
+    let mut planned = Vec::new();
+    for _ in 0..enemy_count {
+        // Determine enemy archetype based on room type and progression
+        let archetype_id = if room_type == RoomType::Elite {
             // Elite rooms have elite enemies
             if rng.gen_bool(0.5) {
-                EnemyType::EliteMelee
+                "elite_melee"
             } else {
-                EnemyType::EliteRanged
+                "elite_ranged"
             }
         } else {
             // Normal rooms have mix of common enemies
@@ -383,36 +555,204 @@ fn spawn_room_enemies(
             
             if rng.gen_bool(elite_chance as f64) {
                 if rng.gen_bool(0.5) {
-                    EnemyType::EliteMelee
+                    "elite_melee"
                 } else {
-                    EnemyType::EliteRanged
+                    "elite_ranged"
                 }
             } else {
-                // 60% melee, 40% ranged for common enemies
-                if rng.gen_bool(0.6) {
-                    EnemyType::CommonMelee
-                } else {
-                    EnemyType::CommonRanged
+                // 60% melee, 40% ranged for common enemies, a quarter of
+                // each patrolling or supporting instead
+                match (rng.gen_bool(0.6), rng.gen_bool(0.25)) {
+                    (true, false) => "common_melee",
+                    (true, true) => "sentry",
+                    (false, false) => "common_ranged",
+                    (false, true) => "mender",
                 }
             }
         };
-        
-        // Spawn the enemy
-        let enemy_entity = match enemy_type {
-            EnemyType::CommonMelee | EnemyType::EliteMelee => {
-                spawn_melee_enemy(commands, spawn_pos, enemy_type, room_number)
-            }
-            EnemyType::CommonRanged | EnemyType::EliteRanged => {
-                spawn_ranged_enemy(commands, spawn_pos, enemy_type, room_number)
-            }
-            _ => continue,
+        let Some(archetype) = archetypes.get(archetype_id) else {
+            warn!("Unknown enemy archetype '{}'", archetype_id);
+            continue;
         };
         
-        // Parent enemy to room
-        commands.entity(enemy_entity).set_parent(room_entity);
+        let is_elite = matches!(archetype.enemy_type, EnemyType::EliteMelee | EnemyType::EliteRanged);
+        let elite_modifier = if is_elite {
+            Some(roll_elite_modifier(rng))
+        } else {
+            None
+        };
+        let held_back = is_elite || archetype.behavior == EnemyBehavior::Support;
+        planned.push((held_back, archetype_id, elite_modifier));
+    }
+    planned.sort_by_key(|(held_back, ..)| *held_back);
+    
+    let spawn_queue: Vec<EnemySpawnData> = planned
+        .into_iter()
+        .enumerate()
+        .map(|(i, (_, archetype_id, elite_modifier))| EnemySpawnData {
+            archetype: archetype_id.to_string(),
+            position: spawn_points[i % spawn_points.len()],
+            elite_modifier,
+        })
+        .collect();
+    let enemies_per_wave = split_waves(spawn_queue.len() as u32, room_type);
+    WaveManager::new(spawn_queue, enemies_per_wave)
+}
+
+/// Combat rooms split their enemies over the Wave Spawning table's four
+/// waves; anywhere else they all come at once
+fn split_waves(total: u32, room_type: RoomType) -> Vec<u32> {
+    if room_type != RoomType::Combat {
+        return vec![total];
+    }
+    
+    let mut waves = Vec::new();
+    let mut share_so_far = 0.0;
+    let mut released = 0;
+    for share in WAVE_SHARES {
+        share_so_far += share;
+        let through = ((total as f32 * share_so_far).round() as u32).min(total);
+        waves.push(through - released);
+        released = through;
+    }
+    waves
+}
+
+/// Spawn each wave once its warning is over. Only the host spawns enemies;
+/// clients get them as replicas.
+fn run_waves(
+    mut commands: Commands,
+    mut waves: ResMut<WaveManager>,
+    current_room: Option<Res<CurrentRoom>>,
+    enemy_query: Query<(), With<Enemy>>,
+    archetypes: Res<EnemyArchetypes>,
+    game_state: Res<GameState>,
+) {
+    let Some(current_room) = current_room else {
+        return;
+    };
+    let Some(landed) = tick_waves(&mut commands, &mut waves, enemy_query.iter().count(), &archetypes) else {
+        return;
+    };
+
+    for spawn in landed {
+        let Some(enemy) = spawn_enemy(&mut commands, &archetypes, &spawn.archetype, spawn.position, game_state.room_number, spawn.elite_modifier) else {
+            continue;
+        };
+        if archetypes.get(&spawn.archetype).is_some_and(|archetype| archetype.behavior == EnemyBehavior::Patrol) {
+            commands.entity(enemy).insert(patrol_route(spawn.position));
+        }
+        commands.entity(enemy).set_parent(current_room.entity);
     }
 }
 
+/// Clients run the same wave clock over the same seeded plan, counting the
+/// host's replicas, so they warn where each wave is about to appear
+fn warn_of_waves(
+    mut commands: Commands,
+    mut waves: ResMut<WaveManager>,
+    current_room: Option<Res<CurrentRoom>>,
+    enemy_query: Query<(), With<Enemy>>,
+    archetypes: Res<EnemyArchetypes>,
+) {
+    if current_room.is_none() {
+        return;
+    }
+    tick_waves(&mut commands, &mut waves, enemy_query.iter().count(), &archetypes);
+}
+
+/// Advance the wave clock. Releases the next wave, with spawn warnings, when
+/// it's due or the last one is nearly dead, and hands it back once the
+/// warning is over.
+fn tick_waves(
+    commands: &mut Commands,
+    waves: &mut WaveManager,
+    alive: usize,
+    archetypes: &EnemyArchetypes,
+) -> Option<Vec<EnemySpawnData>> {
+    // The interval counts from a wave's release, warning included
+    waves.wave_timer.tick();
+    if !waves.incoming.is_empty() {
+        waves.spawn_timer.tick();
+        return waves.spawn_timer.finished().then(|| std::mem::take(&mut waves.incoming));
+    }
+
+    let &wave_size = waves.enemies_per_wave.first()?;
+    let nearly_dead = waves.current_wave > 0 && alive as f32 <= waves.last_wave_size as f32 * WAVE_NEARLY_DEAD;
+    if !waves.wave_timer.finished() && !nearly_dead {
+        return None;
+    }
+
+    waves.enemies_per_wave.remove(0);
+    let count = (wave_size as usize).min(waves.spawn_queue.len());
+    waves.incoming = waves.spawn_queue.drain(..count).collect();
+    waves.current_wave += 1;
+    waves.last_wave_size = wave_size;
+    waves.wave_timer = FrameTimer::from_seconds(WAVE_INTERVAL_SECS);
+    waves.spawn_timer = FrameTimer::from_seconds(SPAWN_WARNING_SECS);
+
+    for spawn in waves.incoming.iter() {
+        let size = archetypes.get(&spawn.archetype).map_or(Vec2::splat(30.0), |archetype| archetype.sprite.size) * 1.5;
+        let color = Color::srgba(1.0, 0.2, 0.2, 0.5);
+        commands.spawn((
+            Telegraph {
+                duration: Timer::from_seconds(SPAWN_WARNING_SECS, TimerMode::Once),
+                telegraph_type: TelegraphType::EnemySpawn,
+                damage_area: size,
+                color,
+            },
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(size),
+                    ..default()
+                },
+                transform: Transform::from_translation(spawn.position.extend(0.5)),
+                ..default()
+            },
+        ));
+    }
+    None
+}
+
+/// One boss or miniboss of `enemy_type`, picked from the archetypes of that
+/// type, at the far end of the room
+fn spawn_room_boss(
+    commands: &mut Commands,
+    room_entity: Entity,
+    enemy_type: EnemyType,
+    room_number: u32,
+    ascension: u8,
+    run_rng: &mut RunRng,
+    archetypes: &EnemyArchetypes,
+) {
+    let ids = archetypes.ids_of_type(enemy_type);
+    if ids.is_empty() {
+        warn!("No {:?} archetypes to spawn", enemy_type);
+        return;
+    }
+    let archetype_id = ids[run_rng.stream(RngStream::Spawns).gen_range(0..ids.len())];
+
+    if let Some(boss) = spawn_boss(commands, archetypes, archetype_id, Vec2::new(0.0, 200.0), room_number, ascension) {
+        commands.entity(boss).set_parent(room_entity);
+    }
+}
+
+/// A square beat around the spawn point, kept inside the walls
+fn patrol_route(center: Vec2) -> PatrolRoute {
+    const HALF_SIDE: f32 = 120.0;
+    const WALL_MARGIN: f32 = 40.0;
+
+    let limit = Vec2::new(ARENA_HALF_WIDTH, ARENA_HALF_HEIGHT) - Vec2::splat(WALL_MARGIN);
+    let corners = [
+        Vec2::new(-HALF_SIDE, -HALF_SIDE),
+        Vec2::new(HALF_SIDE, -HALF_SIDE),
+        Vec2::new(HALF_SIDE, HALF_SIDE),
+        Vec2::new(-HALF_SIDE, HALF_SIDE),
+    ];
+    PatrolRoute::new(corners.iter().map(|corner| (center + *corner).clamp(-limit, limit)).collect())
+}
+
 fn spawn_room_hazards(
     commands: &mut Commands,
     room_entity: Entity,
@@ -434,53 +774,79 @@ fn spawn_room_hazards(
 }
 
 fn spawn_poison_pool(commands: &mut Commands, room_entity: Entity, position: Vec2) {
-    commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                color: Color::rgba(0.2, 0.8, 0.2, 0.6),
-                custom_size: Some(Vec2::new(80.0, 80.0)),
-                ..default()
-            },
-            transform: Transform::from_translation(position.extend(-0.5)),
-            ..default()
-        },
-        Hazard {
-            damage_per_second: 10.0,
-            hazard_type: HazardType::Poison,
-        },
-    )).set_parent(room_entity);
+    spawn_hazard(commands, room_entity, position, Vec2::new(80.0, 80.0), HazardType::Poison, 2.0, Color::srgba(0.2, 0.8, 0.2, 0.6));
 }
 
 fn spawn_spike_trap(commands: &mut Commands, room_entity: Entity, position: Vec2) {
+    spawn_hazard(commands, room_entity, position, Vec2::new(40.0, 40.0), HazardType::Spikes, 20.0, Color::srgb(0.5, 0.5, 0.5));
+}
+
+/// Hazards are environment hitboxes that forget who they've hit every tick,
+/// so anyone standing in one keeps getting hit. Spikes deal `per_second`
+/// damage; poison pools deal none themselves but add a stack of Poison doing
+/// `per_second` each tick, which lingers after stepping out.
+fn spawn_hazard(
+    commands: &mut Commands,
+    room_entity: Entity,
+    position: Vec2,
+    size: Vec2,
+    hazard_type: HazardType,
+    per_second: f32,
+    color: Color,
+) {
+    let (damage, status) = match hazard_type {
+        HazardType::Spikes => (per_second * HAZARD_TICK_FRAMES as f32 * FRAME_SECONDS, None),
+        HazardType::Poison => (0.0, Some(OnHitStatus { effect: StatusEffectType::Poison, value: per_second })),
+    };
+
     commands.spawn((
         SpriteBundle {
             sprite: Sprite {
-                color: Color::rgb(0.5, 0.5, 0.5),
-                custom_size: Some(Vec2::new(40.0, 40.0)),
+                color,
+                custom_size: Some(size),
                 ..default()
             },
             transform: Transform::from_translation(position.extend(-0.5)),
             ..default()
         },
         Hazard {
-            damage_per_second: 20.0,
-            hazard_type: HazardType::Spikes,
+            tick_timer: FrameTimer::new(HAZARD_TICK_FRAMES),
         },
+        Hitbox {
+            size,
+            offset: Vec2::ZERO,
+            damage,
+            damage_type: hazard_type.damage_type(),
+            knockback: 0.0,
+            status,
+            active: true,
+            hit_entities: Vec::new(),
+        },
+        Team::Environment,
+        NavObstacle { size, blocks: false },
     )).set_parent(room_entity);
 }
 
+const HAZARD_TICK_FRAMES: u32 = 30;
+
 #[derive(Component)]
 struct Hazard {
-    damage_per_second: f32,
-    hazard_type: HazardType,
+    tick_timer: FrameTimer,
 }
 
 #[derive(Debug, Clone, Copy)]
 enum HazardType {
     Poison,
     Spikes,
-    Fire,
-    Ice,
+}
+
+impl HazardType {
+    fn damage_type(self) -> DamageType {
+        match self {
+            HazardType::Poison => DamageType::Poison,
+            HazardType::Spikes => DamageType::Physical,
+        }
+    }
 }
 
 fn should_spawn_hazards(biome: BiomeType, room_number: u32) -> bool {
@@ -491,27 +857,46 @@ fn should_spawn_hazards(biome: BiomeType, room_number: u32) -> bool {
     }
 }
 
-fn spawn_player(commands: &mut Commands, position: Vec2) {
-    commands.spawn((
+// Player starts with 100 HP (survives 5 common hits per Design Bible)
+pub const PLAYER_BASE_HEALTH: f32 = 100.0;
+
+/// Spawn a player body. Remote players get the same components so the host can
+/// simulate them; only the local one is tagged `LocalPlayer`.
+pub fn spawn_player(commands: &mut Commands, player_id: &str, position: Vec2, is_local: bool) -> Entity {
+    let color = if is_local {
+        Color::srgb(0.2, 0.6, 1.0)
+    } else {
+        Color::srgb(0.3, 0.9, 0.5)
+    };
+
+    let mut player = commands.spawn((
         Player {
-            id: "player".to_string(),
-            is_local: true,
+            id: player_id.to_string(),
+            is_local,
         },
-        LocalPlayer,
-        Health::new(100.0), // Player starts with 100 HP (survives 5 common hits per Design Bible)
+        Health::new(PLAYER_BASE_HEALTH),
         CombatStats::default(),
         MovementStats::default(),
+        BaseStats {
+            combat: CombatStats::default(),
+            move_speed: MovementStats::default().base_speed,
+            max_health: PLAYER_BASE_HEALTH,
+        },
+        StatModifiers::default(),
         DashState::default(),
         AttackState::default(),
         Velocity::default(),
         InputBuffer::default(),
+        PendingInputs::default(),
+        StatusEffects::default(),
+        Team::Player,
         Hurtbox {
             size: Vec2::new(32.0, 32.0),
             invulnerable: false,
         },
         SpriteBundle {
             sprite: Sprite {
-                color: Color::rgb(0.2, 0.6, 1.0),
+                color,
                 custom_size: Some(Vec2::new(32.0, 32.0)),
                 ..default()
             },
@@ -519,6 +904,12 @@ fn spawn_player(commands: &mut Commands, position: Vec2) {
             ..default()
         },
     ));
+
+    player.insert((PlayerBoons::default(), PlayerCurses::default()));
+    if is_local {
+        player.insert(LocalPlayer);
+    }
+    player.id()
 }
 
 fn check_room_clear(
@@ -526,8 +917,15 @@ fn check_room_clear(
     enemy_query: Query<&Enemy>,
     mut current_room: ResMut<CurrentRoom>,
     mut door_query: Query<&mut Door>,
+    waves: Res<WaveManager>,
+    game_state: Res<GameState>,
+    mut room_status: ResMut<RoomStatus>,
 ) {
     if let Ok((mut room, children)) = room_query.get_mut(current_room.entity) {
+        room_status.set_if_neq(RoomStatus {
+            room_number: game_state.room_number,
+            cleared: room.cleared,
+        });
         if room.cleared {
             return;
         }
@@ -542,79 +940,201 @@ fn check_room_clear(
         
         current_room.enemies_remaining = enemies_remaining;
         
-        // Check if room is cleared
-        if enemies_remaining == 0 && room.enemy_count > 0 {
-            room.cleared = true;
-            current_room.doors_locked = false;
-            
-            // Unlock all doors
-            for child in children.iter() {
-                if let Ok(mut door) = door_query.get_mut(*child) {
-                    door.locked = false;
-                }
-            }
-            
+        // Check if room is cleared, counting waves still to come
+        if enemies_remaining == 0 && room.enemy_count > 0 && waves.is_finished() {
+            clear_room(&mut room, children, &mut current_room, &mut door_query);
             // Room cleared! Spawn rewards will be handled by another system
         }
     }
 }
 
+/// Clients only count the host's enemy replicas for the HUD; the room is
+/// cleared when the host's snapshots say so
+fn follow_host_room_clear(
+    mut room_query: Query<(&mut Room, &Children)>,
+    enemy_query: Query<(), With<Enemy>>,
+    mut current_room: ResMut<CurrentRoom>,
+    mut door_query: Query<&mut Door>,
+    game_state: Res<GameState>,
+    room_status: Res<RoomStatus>,
+) {
+    current_room.enemies_remaining = enemy_query.iter().count() as u32;
+
+    let Ok((mut room, children)) = room_query.get_mut(current_room.entity) else {
+        return;
+    };
+    if room.cleared || !room_status.cleared || room_status.room_number != game_state.room_number {
+        return;
+    }
+    clear_room(&mut room, children, &mut current_room, &mut door_query);
+}
+
+fn clear_room(
+    room: &mut Room,
+    children: &Children,
+    current_room: &mut CurrentRoom,
+    door_query: &mut Query<&mut Door>,
+) {
+    room.cleared = true;
+    current_room.doors_locked = false;
+    
+    // Unlock all doors
+    for child in children.iter() {
+        if let Ok(mut door) = door_query.get_mut(*child) {
+            door.locked = false;
+        }
+    }
+}
+
+fn near_open_door(position: Vec2, door_query: &Query<(&Door, &Transform)>) -> bool {
+    door_query.iter().any(|(door, door_transform)| {
+        !door.locked && position.distance(door_transform.translation.truncate()) < DOOR_INTERACT_RANGE
+    })
+}
+
+/// Only the host moves the party on. Clients using a door ask the host,
+/// which checks the request and then sends everyone the new room.
 fn handle_door_interaction(
     keyboard: Res<ButtonInput<KeyCode>>,
     door_query: Query<(&Door, &Transform)>,
-    player_query: Query<&Transform, With<LocalPlayer>>,
-    current_room: Res<CurrentRoom>,
-    mut game_state: ResMut<GameState>,
+    shrine_query: Query<&GlobalTransform, With<CurseShrine>>,
+    player_query: Query<&Transform, (With<LocalPlayer>, Without<Dead>)>,
+    network_state: Res<NetworkState>,
+    mut room_change: RoomChange,
 ) {
     if !keyboard.just_pressed(KeyCode::KeyE) {
         return;
     }
     
-    let player_transform = player_query.get_single();
-    if player_transform.is_err() {
+    let Ok(player_transform) = player_query.get_single() else {
         return;
-    }
+    };
     let player_pos = player_transform.translation.truncate();
     
-    for (door, door_transform) in door_query.iter() {
-        let door_pos = door_transform.translation.truncate();
-        let distance = player_pos.distance(door_pos);
-        
-        // Check if player is near door
-        if distance < 50.0 && !door.locked {
-            // Initiate room transition
-            game_state.current_state = CurrentGameState::RoomTransition;
-            game_state.room_number += 1;
+    // The press belongs to the shrine
+    if shrine_query.iter().any(|shrine_transform| in_shrine_range(player_pos, shrine_transform)) {
+        return;
+    }
+    if !near_open_door(player_pos, &door_query) {
+        return;
+    }
+
+    if network_state.is_host() {
+        room_change.enter_next_room();
+        return;
+    }
+    let (Some(transport), Some(host_id)) = (room_change.transport.as_mut(), network_state.host_id.as_ref()) else {
+        return;
+    };
+    match protocol::encode(&GameMessage::RequestNextRoom { from_room: room_change.game_state.room_number }) {
+        Ok(payload) => transport.0.send(host_id, &payload),
+        Err(e) => warn!("Failed to encode room request: {}", e),
+    }
+}
+
+/// What the host needs to move the party into the next room
+#[derive(SystemParam)]
+struct RoomChange<'w> {
+    game_state: ResMut<'w, GameState>,
+    next_state: ResMut<'w, NextState<CurrentGameState>>,
+    transport: Option<ResMut<'w, ActiveTransport>>,
+}
+
+impl RoomChange<'_> {
+    /// Move on and tell every peer which room they're in now
+    fn enter_next_room(&mut self) {
+        self.game_state.room_number += 1;
+        self.next_state.set(CurrentGameState::RoomTransition);
+
+        let Some(transport) = self.transport.as_mut() else {
+            return;
+        };
+        match protocol::encode(&GameMessage::EnterRoom { room_number: self.game_state.room_number }) {
+            Ok(payload) => transport.0.broadcast(&payload),
+            Err(e) => warn!("Failed to encode room change: {}", e),
         }
     }
 }
 
+/// Host: a client asked to leave the room. Ignored unless it's about the
+/// room we're in and their player really stands at an open door.
+fn receive_room_requests(
+    mut messages: EventReader<PeerMessageReceived>,
+    door_query: Query<(&Door, &Transform)>,
+    player_query: Query<(&Player, &Transform), Without<Dead>>,
+    mut room_change: RoomChange,
+) {
+    let requested = messages.read().any(|PeerMessageReceived { peer_id, message }| {
+        let GameMessage::RequestNextRoom { from_room } = message else {
+            return false;
+        };
+        *from_room == room_change.game_state.room_number
+            && player_query
+                .iter()
+                .find(|(player, _)| player.id == *peer_id)
+                .is_some_and(|(_, transform)| near_open_door(transform.translation.truncate(), &door_query))
+    });
+    if requested {
+        room_change.enter_next_room();
+    }
+}
+
+/// Client: go wherever the host says the party is
+fn follow_host_room(
+    network_state: Res<NetworkState>,
+    mut messages: EventReader<PeerMessageReceived>,
+    mut game_state: ResMut<GameState>,
+    mut next_state: ResMut<NextState<CurrentGameState>>,
+) {
+    let room_number = messages
+        .read()
+        .filter(|event| network_state.host_id.as_ref() == Some(&event.peer_id))
+        .filter_map(|event| match event.message {
+            GameMessage::EnterRoom { room_number } => Some(room_number),
+            _ => None,
+        })
+        .last();
+    let Some(room_number) = room_number else {
+        return;
+    };
+    if room_number != game_state.room_number {
+        game_state.room_number = room_number;
+        next_state.set(CurrentGameState::RoomTransition);
+    }
+}
+
 fn room_transition(
     mut commands: Commands,
-    mut game_state: ResMut<GameState>,
+    game_state: Res<GameState>,
+    mut next_state: ResMut<NextState<CurrentGameState>>,
     mut room_gen: ResMut<RoomGenerator>,
-    current_room: Res<CurrentRoom>,
+    mut run_rng: ResMut<RunRng>,
     room_query: Query<Entity, With<Room>>,
+    archetypes: Res<EnemyArchetypes>,
 ) {
-    if game_state.current_state != CurrentGameState::RoomTransition {
-        return;
-    }
-    
     // Despawn old room
     for entity in room_query.iter() {
         commands.entity(entity).despawn_recursive();
     }
     
+    // Reseed room streams so every peer derives the same room from the seed
+    run_rng.begin_room(game_state.room_number);
+    
     // Determine next room type
-    let room_type = determine_next_room_type(game_state.room_number);
+    let room_type = determine_next_room_type(game_state.room_number, &mut run_rng);
     
     // Generate new room
     let new_room = generate_room(
         &mut commands,
-        room_type,
-        game_state.room_number,
-        game_state.biome,
+        RoomSpec {
+            room_type,
+            room_number: game_state.room_number,
+            biome: game_state.biome,
+            ascension: game_state.difficulty.ascension(),
+        },
         &mut room_gen,
+        &mut run_rng,
+        &archetypes,
     );
     
     // Update current room resource
@@ -633,19 +1153,23 @@ fn room_transition(
     });
     
     // Return to gameplay
-    game_state.current_state = CurrentGameState::InRun;
+    next_state.set(match room_type {
+        RoomType::Boss => CurrentGameState::BossFight,
+        RoomType::Shop => CurrentGameState::Shop,
+        _ => CurrentGameState::InRun,
+    });
 }
 
-fn determine_next_room_type(room_number: u32) -> RoomType {
-    let mut rng = rand::thread_rng();
+fn determine_next_room_type(room_number: u32, run_rng: &mut RunRng) -> RoomType {
+    let rng = run_rng.stream(RngStream::RoomLayout);
     
     // Boss rooms every 10 rooms
-    if room_number % 10 == 0 {
+    if room_number.is_multiple_of(10) {
         return RoomType::Boss;
     }
     
     // Shop rooms every 5 rooms
-    if room_number % 5 == 0 {
+    if room_number.is_multiple_of(5) {
         return RoomType::Shop;
     }
     
@@ -662,9 +1186,25 @@ fn determine_next_room_type(room_number: u32) -> RoomType {
     }
 }
 
+/// Clearing the final boss room ends the run in victory, ahead of any
+/// reward screen the room would have shown
+fn check_victory(
+    room_query: Query<&Room, Changed<Room>>,
+    game_state: Res<GameState>,
+    mut next_state: ResMut<NextState<CurrentGameState>>,
+) {
+    let final_boss_cleared = room_query
+        .iter()
+        .any(|room| room.cleared && room.room_type == RoomType::Boss);
+    if final_boss_cleared && game_state.room_number >= FINAL_BOSS_ROOM {
+        next_state.set(CurrentGameState::Victory);
+    }
+}
+
 fn spawn_room_rewards(
     mut commands: Commands,
     room_query: Query<(&Room, &Transform), Changed<Room>>,
+    mut next_state: ResMut<NextState<CurrentGameState>>,
 ) {
     for (room, transform) in room_query.iter() {
         if !room.cleared {
@@ -677,73 +1217,285 @@ fn spawn_room_rewards(
             
             match reward {
                 RewardType::Gold(amount) => {
-                    spawn_gold_pickup(&mut commands, transform.translation.truncate() + offset, *amount);
+                    spawn_pickup(&mut commands, PickupType::Gold, transform.translation.truncate() + offset, *amount as f32);
                 }
                 RewardType::Heal(percentage) => {
-                    spawn_health_pickup(&mut commands, transform.translation.truncate() + offset, *percentage);
+                    spawn_pickup(&mut commands, PickupType::Health, transform.translation.truncate() + offset, *percentage);
+                }
+                RewardType::Boon(rarity) => {
+                    commands.insert_resource(BoonSelectionState::new(*rarity));
+                    next_state.set(CurrentGameState::BoonSelection);
                 }
                 _ => {
-                    // Boons and items would trigger selection UI
+                    // Items would trigger selection UI
                 }
             }
         }
     }
 }
 
-fn spawn_gold_pickup(commands: &mut Commands, position: Vec2, amount: u32) {
+pub fn spawn_pickup(commands: &mut Commands, pickup_type: PickupType, position: Vec2, value: f32) -> Entity {
+    let (color, size) = match pickup_type {
+        PickupType::Gold => (Color::srgb(1.0, 0.8, 0.0), 20.0),
+        PickupType::Health => (Color::srgb(0.0, 1.0, 0.0), 25.0),
+        PickupType::Soul => (Color::srgb(0.6, 0.4, 1.0), 20.0),
+        PickupType::PowerUp => (Color::srgb(1.0, 0.3, 0.8), 25.0),
+    };
+
     commands.spawn((
         Pickup {
-            pickup_type: PickupType::Gold,
+            pickup_type,
             auto_collect: true,
-            value: amount as f32,
+            value,
         },
         SpriteBundle {
             sprite: Sprite {
-                color: Color::rgb(1.0, 0.8, 0.0),
-                custom_size: Some(Vec2::new(20.0, 20.0)),
+                color,
+                custom_size: Some(Vec2::new(size, size)),
                 ..default()
             },
             transform: Transform::from_translation(position.extend(0.5)),
             ..default()
         },
-    ));
+    )).id()
 }
 
-fn spawn_health_pickup(commands: &mut Commands, position: Vec2, percentage: f32) {
-    commands.spawn((
-        Pickup {
-            pickup_type: PickupType::Health,
-            auto_collect: true,
-            value: percentage,
-        },
-        SpriteBundle {
-            sprite: Sprite {
-                color: Color::rgb(0.0, 1.0, 0.0),
-                custom_size: Some(Vec2::new(25.0, 25.0)),
-                ..default()
-            },
-            transform: Transform::from_translation(position.extend(0.5)),
-            ..default()
-        },
-    ));
+// Prices from the Tuning Tables
+const SHOP_STOCK: [(PickupType, f32, u32); 3] = [
+    (PickupType::Health, 0.25, 40),
+    (PickupType::Health, 1.0, 120),
+    (PickupType::Soul, 5.0, 60),
+];
+
+/// Lay the wares out across the middle of the shop room
+fn spawn_shop(mut commands: Commands) {
+    for (i, (pickup_type, value, price)) in SHOP_STOCK.iter().enumerate() {
+        let position = Vec3::new((i as f32 - 1.0) * 120.0, 60.0, 0.5);
+
+        commands
+            .spawn((
+                ShopItem {
+                    pickup_type: *pickup_type,
+                    value: *value,
+                    price: *price,
+                },
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::srgb(0.9, 0.75, 0.3),
+                        custom_size: Some(Vec2::new(36.0, 36.0)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(position),
+                    ..default()
+                },
+                StateScoped(CurrentGameState::Shop),
+            ))
+            .with_children(|parent| {
+                parent.spawn(Text2dBundle {
+                    text: Text::from_section(
+                        format!("{}g", price),
+                        TextStyle {
+                            font_size: 18.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    transform: Transform::from_xyz(0.0, -32.0, 0.1),
+                    ..default()
+                });
+            });
+    }
+}
+
+fn buy_shop_items(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut inventory: ResMut<PlayerInventory>,
+    item_query: Query<(Entity, &ShopItem, &Transform)>,
+    player_query: Query<&Transform, With<LocalPlayer>>,
+) {
+    if !keyboard.just_pressed(KeyCode::KeyE) {
+        return;
+    }
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
+
+    for (entity, item, item_transform) in item_query.iter() {
+        let item_pos = item_transform.translation.truncate();
+        if player_pos.distance(item_pos) > 40.0 || inventory.gold < item.price {
+            continue;
+        }
+
+        inventory.gold -= item.price;
+        spawn_pickup(&mut commands, item.pickup_type, item_pos, item.value);
+        commands.entity(entity).despawn_recursive();
+        break;
+    }
 }
 
+/// Let each hazard hit again once its tick comes round; the damage or poison
+/// itself goes through hit detection like any other hitbox
 fn update_room_hazards(
-    hazard_query: Query<(&Hazard, &Transform)>,
-    mut player_query: Query<(&mut Health, &Transform), With<LocalPlayer>>,
-    time: Res<Time>,
+    mut hazard_query: Query<(&mut Hazard, &mut Hitbox)>,
 ) {
-    for (mut health, player_transform) in player_query.iter_mut() {
-        let player_pos = player_transform.translation.truncate();
-        
-        for (hazard, hazard_transform) in hazard_query.iter() {
-            let hazard_pos = hazard_transform.translation.truncate();
-            
-            // Simple overlap check
-            if player_pos.distance(hazard_pos) < 30.0 {
-                // Apply hazard damage
-                health.take_damage(hazard.damage_per_second * time.delta_seconds());
+    for (mut hazard, mut hitbox) in hazard_query.iter_mut() {
+        hazard.tick_timer.tick();
+        if hazard.tick_timer.finished() {
+            hazard.tick_timer.reset();
+            hitbox.hit_entities.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::world::CommandQueue;
+
+    /// Rooms 1 to `rooms` of a run on `seed`, as a peer would build them:
+    /// layout, waves, bosses, shrines and what the loot stream rolls in each.
+    /// `extra_rolls` draws from the run-scoped combat stream between rooms,
+    /// like a peer that saw more hits than another.
+    fn play_out(seed: u64, rooms: u32, extra_rolls: usize) -> Vec<String> {
+        let archetypes = EnemyArchetypes::load();
+        let mut room_gen = RoomGenerator::from_seed(seed);
+        let mut run_rng = RunRng::new(room_gen.seed);
+        let mut world = World::new();
+        let mut log = Vec::new();
+
+        for room_number in 1..=rooms {
+            run_rng.begin_room(room_number);
+            let room_type = determine_next_room_type(room_number, &mut run_rng);
+
+            let mut queue = CommandQueue::default();
+            let mut commands = Commands::new(&mut queue, &world);
+            let spec = RoomSpec {
+                room_type,
+                room_number,
+                biome: BiomeType::Biome2,
+                ascension: 0,
+            };
+            let room_entity = generate_room(&mut commands, spec, &mut room_gen, &mut run_rng, &archetypes);
+            queue.apply(&mut world);
+
+            let room = world.get::<Room>(room_entity).expect("room entity");
+            log.push(format!("{} {:?} {} {:?} {:?}", room_number, room.room_type, room.enemy_count, room.exits, room.rewards));
+            let waves = world.resource::<WaveManager>();
+            log.push(format!("{:?} {:?}", waves.enemies_per_wave, waves.spawn_queue));
+            for (enemy, transform) in world.query::<(&Enemy, &Transform)>().iter(&world) {
+                log.push(format!("{} {:?}", enemy.archetype, transform.translation));
+            }
+            for shrine in world.query::<&CurseShrine>().iter(&world) {
+                log.push(shrine.curse.name.clone());
+            }
+            let loot: Vec<u64> = (0..4).map(|_| run_rng.stream(RngStream::Loot).gen()).collect();
+            log.push(format!("{:?}", loot));
+
+            for _ in 0..extra_rolls {
+                run_rng.stream(RngStream::Combat).gen::<f32>();
             }
+            world.clear_entities();
         }
+        log
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_run() {
+        let first = play_out(0x5EED, 30, 0);
+        assert_eq!(first, play_out(0x5EED, 30, 0));
+        assert_ne!(first, play_out(0x5EED + 1, 30, 0));
+    }
+
+    #[test]
+    fn rooms_ignore_how_much_of_the_run_streams_each_peer_used() {
+        assert_eq!(play_out(42, 30, 0), play_out(42, 30, 17));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn waves_round_the_wave_shares_cumulatively() {
+        // Each wave is the rounded running share minus what's already out
+        let table = [
+            (0, vec![0, 0, 0, 0]),
+            (1, vec![0, 1, 0, 0]),
+            (3, vec![1, 1, 1, 0]),
+            (4, vec![2, 1, 1, 0]),
+            (7, vec![3, 2, 1, 1]),
+            (10, vec![4, 3, 2, 1]),
+            (15, vec![6, 5, 3, 1]),
+        ];
+        for (total, expected) in table {
+            assert_eq!(split_waves(total, RoomType::Combat), expected, "{} enemies", total);
+        }
+    }
+
+    #[test]
+    fn waves_always_add_up_to_the_plan() {
+        for total in 0..=60 {
+            let waves = split_waves(total, RoomType::Combat);
+            assert_eq!(waves.len(), WAVE_SHARES.len());
+            assert_eq!(waves.iter().sum::<u32>(), total, "{} enemies", total);
+        }
+        for room_type in [RoomType::Elite, RoomType::Boss, RoomType::Secret] {
+            assert_eq!(split_waves(9, room_type), vec![9]);
+        }
+    }
+
+    fn planned_waves(total: u32) -> WaveManager {
+        let spawn = |i| EnemySpawnData {
+            archetype: "common_melee".to_string(),
+            position: Vec2::new(i as f32, 0.0),
+            elite_modifier: None,
+        };
+        WaveManager::new((0..total).map(spawn).collect(), split_waves(total, RoomType::Combat))
+    }
+
+    /// Ticks `tick_waves` with `alive` enemies up until a wave lands; returns
+    /// how many ticks that took and the wave
+    fn tick_until_landed(commands: &mut Commands, waves: &mut WaveManager, alive: usize, limit: u32) -> Option<(u32, Vec<EnemySpawnData>)> {
+        let archetypes = EnemyArchetypes::load();
+        (1..=limit).find_map(|ticks| tick_waves(commands, waves, alive, &archetypes).map(|landed| (ticks, landed)))
+    }
+
+    #[test]
+    fn waves_land_after_their_warning_and_on_the_interval() {
+        let warning = FrameTimer::from_seconds(SPAWN_WARNING_SECS).duration();
+        let interval = FrameTimer::from_seconds(WAVE_INTERVAL_SECS).duration();
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let mut waves = planned_waves(10);
+
+        // The first wave is released straight away and lands once warned
+        let (ticks, landed) = tick_until_landed(&mut commands, &mut waves, 0, 1000).expect("first wave");
+        assert_eq!((ticks, landed.len()), (warning + 1, 4));
+
+        // With the first wave untouched, the second waits out the interval
+        let (ticks, landed) = tick_until_landed(&mut commands, &mut waves, 4, 1000).expect("second wave");
+        assert_eq!((ticks, landed.len()), (interval, 3));
+        assert_eq!(waves.current_wave, 2);
+    }
+
+    #[test]
+    fn next_wave_comes_early_once_the_last_is_nearly_dead() {
+        let warning = FrameTimer::from_seconds(SPAWN_WARNING_SECS).duration();
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let mut waves = planned_waves(10);
+        tick_until_landed(&mut commands, &mut waves, 0, 1000).expect("first wave");
+
+        // Two of four left is above WAVE_NEARLY_DEAD; one is at it
+        assert!(tick_until_landed(&mut commands, &mut waves, 2, 10).is_none());
+        assert!(waves.incoming.is_empty());
+        let (ticks, landed) = tick_until_landed(&mut commands, &mut waves, 1, 1000).expect("second wave");
+        assert_eq!((ticks, landed.len()), (warning + 1, 3));
+
+        // Nothing is counted as nearly dead before the first wave is out
+        let mut waves = planned_waves(10);
+        waves.wave_timer = FrameTimer::from_seconds(WAVE_INTERVAL_SECS);
+        assert!(tick_until_landed(&mut commands, &mut waves, 0, 10).is_none());
+        assert_eq!(waves.current_wave, 0);
+    }
+}
@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+use crate::components::*;
+use crate::resources::*;
+use crate::movement::MovementSet;
+
+// ============= Derived Stats =============
+//
+// Boons, curses and elite modifiers never touch `CombatStats` or
+// `MovementStats` directly. They add `StatModifier`s tagged with their source,
+// and `recompute_stats` rebuilds the derived components from `BaseStats`
+// whenever the stack changes:
+//
+//   (base + flat) * (1 + sum of percents) * product of multipliers
+//
+// then applies the soft and hard caps from the Stat Scaling Breakpoints table.
+// Removing a source's modifiers undoes exactly what it added.
+
+/// Stat recomputation; runs before anything that reads the derived stats
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StatsSet;
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, recompute_stats
+            .in_set(StatsSet)
+            .before(MovementSet)
+            .run_if(in_state(PauseState::Running)));
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StatCap {
+    pub soft: f32,
+    pub hard: f32,
+    pub efficiency: f32, // Share of each point past the soft cap that still counts
+    pub relative: bool,  // Caps are multiples of the base value rather than absolute
+}
+
+pub fn stat_cap(stat: StatKind) -> Option<StatCap> {
+    let cap = |soft, hard, efficiency, relative| Some(StatCap {
+        soft,
+        hard,
+        efficiency,
+        relative,
+    });
+
+    // From the Stat Scaling Breakpoints table in Tuning Tables
+    match stat {
+        StatKind::CritChance => cap(0.50, 0.75, 0.50, false),
+        StatKind::CritDamage => cap(2.0, 4.0, 0.25, false),
+        StatKind::AttackSpeed => cap(1.5, 2.0, 0.20, false),
+        StatKind::MoveSpeed => cap(1.5, 2.0, 0.40, true),
+        StatKind::DamageReduction => cap(0.50, 0.75, 0.05, false),
+        _ => None,
+    }
+}
+
+fn apply_cap(stat: StatKind, value: f32, base: f32) -> f32 {
+    let Some(cap) = stat_cap(stat) else {
+        return value;
+    };
+    let scale = if cap.relative { base } else { 1.0 };
+    let (soft, hard) = (cap.soft * scale, cap.hard * scale);
+
+    if value <= soft {
+        return value;
+    }
+    (soft + (value - soft) * cap.efficiency).min(hard)
+}
+
+/// Fold every modifier for `stat` onto `base` and cap the result
+pub fn compute_stat(stat: StatKind, base: f32, modifiers: &StatModifiers) -> f32 {
+    let mut flat = 0.0;
+    let mut percent = 0.0;
+    let mut multiplier = 1.0;
+
+    for modifier in modifiers.iter().filter(|modifier| modifier.stat == stat) {
+        match modifier.op {
+            ModifierOp::Flat => flat += modifier.value,
+            ModifierOp::AddPercent => percent += modifier.value,
+            ModifierOp::Multiply => multiplier *= modifier.value,
+        }
+    }
+
+    let value = (base + flat) * (1.0 + percent) * multiplier;
+    apply_cap(stat, value, base)
+}
+
+const TYPED_DAMAGE: [DamageType; 6] = [
+    DamageType::Physical,
+    DamageType::Fire,
+    DamageType::Ice,
+    DamageType::Lightning,
+    DamageType::Poison,
+    DamageType::Bleed,
+];
+
+fn compute_combat_stats(base: &CombatStats, modifiers: &StatModifiers) -> CombatStats {
+    let mut stats = CombatStats {
+        damage: compute_stat(StatKind::Damage, base.damage, modifiers),
+        crit_chance: compute_stat(StatKind::CritChance, base.crit_chance, modifiers),
+        crit_damage: compute_stat(StatKind::CritDamage, base.crit_damage, modifiers),
+        attack_speed: compute_stat(StatKind::AttackSpeed, base.attack_speed, modifiers),
+        armor: compute_stat(StatKind::Armor, base.armor, modifiers),
+        damage_reduction: compute_stat(StatKind::DamageReduction, base.damage_reduction, modifiers),
+        ..base.clone()
+    };
+
+    for damage_type in TYPED_DAMAGE {
+        if let Some(bonus) = stats.damage_bonus.get_mut(damage_type) {
+            *bonus = compute_stat(StatKind::DamageBonus(damage_type), base.damage_bonus.get(damage_type), modifiers);
+        }
+        if let Some(resistance) = stats.resistances.get_mut(damage_type) {
+            *resistance = compute_stat(StatKind::Resistance(damage_type), base.resistances.get(damage_type), modifiers);
+        }
+    }
+    stats
+}
+
+type StatRecomputeQuery<'w, 's> = Query<'w, 's,
+    (&'static BaseStats, &'static StatModifiers, &'static mut CombatStats, Option<&'static mut MovementStats>, Option<&'static mut Health>),
+    Or<(Changed<StatModifiers>, Changed<BaseStats>)>,
+>;
+
+/// Rebuild derived stats for anything whose modifiers or base changed. Raising
+/// max health heals by the amount gained; lowering it only clamps.
+fn recompute_stats(
+    mut query: StatRecomputeQuery,
+) {
+    for (base, modifiers, mut combat_stats, movement_stats, health) in query.iter_mut() {
+        *combat_stats = compute_combat_stats(&base.combat, modifiers);
+
+        // Status effects turn this into `current_speed`
+        if let Some(mut movement_stats) = movement_stats {
+            movement_stats.base_speed = compute_stat(StatKind::MoveSpeed, base.move_speed, modifiers);
+        }
+
+        if let Some(mut health) = health {
+            let max_health = compute_stat(StatKind::MaxHealth, base.max_health, modifiers);
+            let gained = max_health - health.max;
+            if gained != 0.0 {
+                health.max = max_health;
+                health.current = (health.current + gained.max(0.0)).min(max_health);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32, case: &str) {
+        assert!((actual - expected).abs() < 1e-4, "{}: got {}, expected {}", case, actual, expected);
+    }
+
+    #[test]
+    fn caps_follow_the_stat_scaling_breakpoints() {
+        // (stat, base, uncapped value, capped value)
+        let table = [
+            // Crit Chance: 50% soft, 75% hard, half efficiency past the soft cap
+            (StatKind::CritChance, 0.05, 0.30, 0.30),
+            (StatKind::CritChance, 0.05, 0.50, 0.50),
+            (StatKind::CritChance, 0.05, 0.60, 0.55),
+            (StatKind::CritChance, 0.05, 1.00, 0.75),
+            (StatKind::CritChance, 0.05, 3.00, 0.75),
+            // Crit Damage: 200% soft, 400% hard, quarter efficiency
+            (StatKind::CritDamage, 1.5, 1.8, 1.8),
+            (StatKind::CritDamage, 1.5, 3.0, 2.25),
+            (StatKind::CritDamage, 1.5, 10.0, 4.0),
+            (StatKind::CritDamage, 1.5, 20.0, 4.0),
+            // Attack Speed: 150% soft, 200% hard, a fifth efficiency
+            (StatKind::AttackSpeed, 1.0, 1.2, 1.2),
+            (StatKind::AttackSpeed, 1.0, 2.0, 1.6),
+            (StatKind::AttackSpeed, 1.0, 5.0, 2.0),
+            // Damage Reduction: 50% soft, 75% hard, a twentieth efficiency
+            (StatKind::DamageReduction, 0.0, 0.40, 0.40),
+            (StatKind::DamageReduction, 0.0, 0.90, 0.52),
+            (StatKind::DamageReduction, 0.0, 6.00, 0.75),
+            // Movement Speed caps are 150% and 200% of the base, 40% efficiency
+            (StatKind::MoveSpeed, 300.0, 420.0, 420.0),
+            (StatKind::MoveSpeed, 300.0, 450.0, 450.0),
+            (StatKind::MoveSpeed, 300.0, 600.0, 510.0),
+            (StatKind::MoveSpeed, 300.0, 1000.0, 600.0),
+            (StatKind::MoveSpeed, 200.0, 400.0, 340.0),
+            (StatKind::MoveSpeed, 200.0, 2000.0, 400.0),
+            // Slows are never capped
+            (StatKind::MoveSpeed, 300.0, 150.0, 150.0),
+            // No breakpoints
+            (StatKind::Damage, 10.0, 500.0, 500.0),
+            (StatKind::MaxHealth, 100.0, 1000.0, 1000.0),
+        ];
+
+        for (stat, base, value, expected) in table {
+            assert_close(apply_cap(stat, value, base), expected, &format!("{:?} {} on base {}", stat, value, base));
+        }
+    }
+
+    #[test]
+    fn modifiers_fold_flat_then_percent_then_multiply() {
+        let mut modifiers = StatModifiers::default();
+        modifiers.add(StatModifier::flat(StatKind::Damage, 5.0, ModifierSource::Support));
+        modifiers.add(StatModifier::add_percent(StatKind::Damage, 0.5, ModifierSource::Boon("Sharpened Edge".to_string())));
+        modifiers.add(StatModifier::add_percent(StatKind::Damage, 0.25, ModifierSource::Curse("Marked".to_string())));
+        modifiers.add(StatModifier::multiply(StatKind::Damage, 2.0, ModifierSource::Elite(EliteModifier::Berserker)));
+        modifiers.add(StatModifier::add_percent(StatKind::CritChance, 10.0, ModifierSource::Support));
+
+        // (10 + 5) * (1 + 0.5 + 0.25) * 2
+        assert_close(compute_stat(StatKind::Damage, 10.0, &modifiers), 52.5, "damage");
+        assert_close(compute_stat(StatKind::Armor, 3.0, &modifiers), 3.0, "untouched stat");
+
+        modifiers.remove_source(&ModifierSource::Elite(EliteModifier::Berserker));
+        assert_close(compute_stat(StatKind::Damage, 10.0, &modifiers), 26.25, "after removing a source");
+    }
+
+    #[test]
+    fn modified_move_speed_caps_relative_to_its_base() {
+        let mut modifiers = StatModifiers::default();
+        modifiers.add(StatModifier::add_percent(StatKind::MoveSpeed, 1.0, ModifierSource::Boon("Fleet Foot".to_string())));
+
+        assert_close(compute_stat(StatKind::MoveSpeed, 300.0, &modifiers), 510.0, "base 300");
+        assert_close(compute_stat(StatKind::MoveSpeed, 250.0, &modifiers), 425.0, "base 250");
+    }
+}
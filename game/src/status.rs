@@ -0,0 +1,371 @@
+use bevy::prelude::*;
+use crate::components::*;
+use crate::resources::*;
+use crate::combat::Dead;
+use crate::damage::{DamageInstance, DamageTag, DealDamage};
+use crate::movement::MovementSet;
+use crate::netcode::has_authority;
+use crate::stats::StatsSet;
+
+// ============= Status Effects =============
+//
+// Anything that wants to burn, slow or stun a target sends `ApplyStatus`.
+// Hitboxes, projectiles and support pulses carry an `OnHitStatus` from their
+// move-set or archetype data and send it when they land; poison hazards and
+// exploding elites set theirs in code. The stacking and refresh rules below
+// come from the Status Effect Durations table in tuning-tables.md. Effects
+// tick on the host only, like hit detection. Clients see the results through replicated health, and predict
+// their own player with the speed and stun state from its snapshot.
+
+// DoT and HoT effects deal their per-second value in half-second ticks
+const STATUS_TICK_FRAMES: u32 = 30;
+const STUN_IMMUNITY_SECS: f32 = 2.0;
+
+pub struct StatusPlugin;
+
+impl Plugin for StatusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ApplyStatus>()
+            .add_systems(FixedUpdate, (
+                apply_status_events,
+                tick_status_effects,
+                apply_status_modifiers,
+                apply_move_speed,
+            ).chain()
+                .after(StatsSet)
+                .before(MovementSet)
+                .run_if(in_state(PauseState::Running))
+                .run_if(has_authority));
+    }
+}
+
+/// Put (or stack) an effect on `target`. `value` is per stack: damage or
+/// healing per second, slow fraction, or shield HP.
+#[derive(Event, Debug, Clone)]
+pub struct ApplyStatus {
+    pub target: Entity,
+    pub effect_type: StatusEffectType,
+    pub value: f32,
+}
+
+impl ApplyStatus {
+    /// An attack's on-hit status landing on `target`
+    pub fn on_hit(target: Entity, status: OnHitStatus) -> Self {
+        Self {
+            target,
+            effect_type: status.effect,
+            value: status.value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RefreshRule {
+    RefreshToMax, // Add a stack and restart at the full stacked duration
+    AddDuration,  // Add a stack and extend what's left by the stack duration
+    Replace,      // Throw away the old effect
+    Refresh,      // Add a stack and restart at the base duration
+    Immunity,     // Ignored while active or immune; immunity starts when it ends
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StatusRule {
+    pub base_duration: f32,
+    pub max_stacks: u32,
+    pub stack_duration: f32, // Extra seconds per stack beyond the first
+    pub refresh: RefreshRule,
+}
+
+pub fn status_rule(effect_type: StatusEffectType) -> StatusRule {
+    let rule = |base_duration, max_stacks, stack_duration, refresh| StatusRule {
+        base_duration,
+        max_stacks,
+        stack_duration,
+        refresh,
+    };
+
+    // From Tuning Tables; Shield and Regen aren't listed there
+    match effect_type {
+        StatusEffectType::Burn => rule(3.0, 5, 1.0, RefreshRule::RefreshToMax),
+        StatusEffectType::Poison => rule(5.0, 10, 0.5, RefreshRule::AddDuration),
+        StatusEffectType::Freeze => rule(1.5, 1, 0.0, RefreshRule::Replace),
+        StatusEffectType::Stun => rule(1.0, 1, 0.0, RefreshRule::Immunity),
+        StatusEffectType::Slow => rule(2.0, 3, 0.0, RefreshRule::Refresh),
+        StatusEffectType::Bleed => rule(4.0, 10, 0.0, RefreshRule::Refresh),
+        StatusEffectType::Shield => rule(5.0, 1, 0.0, RefreshRule::Replace),
+        StatusEffectType::Regen => rule(5.0, 1, 0.0, RefreshRule::Refresh),
+    }
+}
+
+/// The damage type a DoT ticks as, or None for effects that don't hurt
+fn damage_over_time_type(effect_type: StatusEffectType) -> Option<DamageType> {
+    match effect_type {
+        StatusEffectType::Burn => Some(DamageType::Fire),
+        StatusEffectType::Poison => Some(DamageType::Poison),
+        StatusEffectType::Bleed => Some(DamageType::Bleed),
+        _ => None,
+    }
+}
+
+/// Fold one application into `effects` following the effect's refresh rule
+pub fn add_status(effects: &mut StatusEffects, effect_type: StatusEffectType, value: f32) {
+    let rule = status_rule(effect_type);
+
+    if rule.refresh == RefreshRule::Immunity && !effects.stun_immunity.finished() {
+        return;
+    }
+
+    let Some(existing) = effects.effects.iter_mut().find(|effect| effect.effect_type == effect_type) else {
+        effects.effects.push(StatusEffect {
+            effect_type,
+            duration: FrameTimer::from_seconds(rule.base_duration),
+            stacks: 1,
+            value,
+            tick_timer: FrameTimer::new(STATUS_TICK_FRAMES),
+        });
+        return;
+    };
+
+    match rule.refresh {
+        RefreshRule::RefreshToMax => {
+            existing.stacks = (existing.stacks + 1).min(rule.max_stacks);
+            let stacked = rule.base_duration + rule.stack_duration * (existing.stacks - 1) as f32;
+            existing.duration = FrameTimer::from_seconds(stacked);
+            existing.value = existing.value.max(value);
+        }
+        RefreshRule::AddDuration => {
+            if existing.stacks < rule.max_stacks {
+                existing.stacks += 1;
+                let remaining = existing.duration.remaining() + frames_from_seconds(rule.stack_duration);
+                existing.duration = FrameTimer::new(remaining);
+            }
+            existing.value = existing.value.max(value);
+        }
+        RefreshRule::Replace => {
+            existing.duration = FrameTimer::from_seconds(rule.base_duration);
+            existing.value = value;
+        }
+        RefreshRule::Refresh => {
+            existing.stacks = (existing.stacks + 1).min(rule.max_stacks);
+            existing.duration.reset();
+            existing.value = existing.value.max(value);
+        }
+        RefreshRule::Immunity => {
+            // Already stunned; stuns never extend
+        }
+    }
+}
+
+fn apply_status_events(
+    mut commands: Commands,
+    mut events: EventReader<ApplyStatus>,
+    mut targets: Query<&mut StatusEffects>,
+) {
+    for event in events.read() {
+        if let Ok(mut effects) = targets.get_mut(event.target) {
+            add_status(&mut effects, event.effect_type, event.value);
+        } else if let Some(mut target) = commands.get_entity(event.target) {
+            let mut effects = StatusEffects::default();
+            add_status(&mut effects, event.effect_type, event.value);
+            target.insert(effects);
+        }
+    }
+}
+
+/// Count effects down, deal DoT/HoT ticks and start stun immunity. DoT ticks
+/// go through the damage pipeline so resistances and shields apply.
+fn tick_status_effects(
+    mut query: Query<(Entity, &mut StatusEffects, &mut Health), Without<Dead>>,
+    mut damage_events: EventWriter<DealDamage>,
+) {
+    for (entity, mut effects, mut health) in query.iter_mut() {
+        effects.stun_immunity.tick();
+
+        let mut healing = 0.0;
+        let mut stun_ended = false;
+
+        for effect in effects.effects.iter_mut() {
+            effect.duration.tick();
+            effect.tick_timer.tick();
+
+            if effect.tick_timer.finished() {
+                effect.tick_timer.reset();
+                let amount = effect.value * effect.stacks as f32 * STATUS_TICK_FRAMES as f32 * FRAME_SECONDS;
+                if let Some(damage_type) = damage_over_time_type(effect.effect_type) {
+                    damage_events.send(DealDamage {
+                        target: entity,
+                        instance: DamageInstance::new(amount, damage_type, None)
+                            .with_tag(DamageTag::DamageOverTime),
+                    });
+                } else if effect.effect_type == StatusEffectType::Regen {
+                    healing += amount;
+                }
+            }
+
+            if effect.duration.finished() && effect.effect_type == StatusEffectType::Stun {
+                stun_ended = true;
+            }
+        }
+        effects.effects.retain(|effect| !effect.duration.finished());
+
+        if stun_ended {
+            effects.stun_immunity = FrameTimer::from_seconds(STUN_IMMUNITY_SECS);
+        }
+
+        if healing > 0.0 {
+            health.heal(healing);
+        }
+    }
+}
+
+type StatusModifierQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static StatusEffects,
+    Option<&'static mut AttackState>,
+    Option<&'static mut Velocity>,
+    Has<Incapacitated>,
+)>;
+
+/// Keep `Incapacitated` in step with Stun/Freeze, interrupting whatever attack
+/// was in progress
+fn apply_status_modifiers(
+    mut commands: Commands,
+    mut query: StatusModifierQuery,
+) {
+    for (entity, effects, attack_state, velocity, was_incapacitated) in query.iter_mut() {
+        let incapacitated = effects.is_incapacitated();
+        if incapacitated && !was_incapacitated {
+            commands.entity(entity).insert(Incapacitated);
+            if let Some(mut attack_state) = attack_state {
+                attack_state.is_attacking = false;
+                attack_state.can_cancel = false;
+            }
+            if let Some(mut velocity) = velocity {
+                velocity.linear = Vec2::ZERO;
+            }
+        } else if !incapacitated && was_incapacitated {
+            commands.entity(entity).remove::<Incapacitated>();
+        }
+    }
+}
+
+/// The speed movement uses: the stats-derived `base_speed` after slows. The
+/// only place `current_speed` is set on the host.
+fn apply_move_speed(
+    mut query: Query<(&mut MovementStats, Option<&StatusEffects>)>,
+) {
+    for (mut stats, effects) in query.iter_mut() {
+        let speed = stats.base_speed * effects.map_or(1.0, StatusEffects::move_speed_multiplier);
+        if stats.current_speed != speed {
+            stats.current_speed = speed;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::archetypes::EnemyArchetypes;
+    use crate::movesets::MoveSets;
+
+    fn frames_left(effects: &StatusEffects, effect_type: StatusEffectType) -> u32 {
+        effects.get(effect_type).map_or(0, |effect| effect.duration.remaining())
+    }
+
+    fn run_for(effects: &mut StatusEffects, frames: u32) {
+        for _ in 0..frames {
+            effects.stun_immunity.tick();
+            for effect in effects.effects.iter_mut() {
+                effect.duration.tick();
+            }
+        }
+    }
+
+    #[test]
+    fn burn_refreshes_to_the_stacked_duration_up_to_five_stacks() {
+        let mut effects = StatusEffects::default();
+        // (stacks, seconds) after each application
+        let table = [(1, 3.0), (2, 4.0), (3, 5.0), (4, 6.0), (5, 7.0), (5, 7.0), (5, 7.0)];
+
+        for (stacks, seconds) in table {
+            run_for(&mut effects, 30);
+            add_status(&mut effects, StatusEffectType::Burn, 4.0);
+            assert_eq!(effects.stacks(StatusEffectType::Burn), stacks);
+            assert_eq!(frames_left(&effects, StatusEffectType::Burn), frames_from_seconds(seconds), "at {} stacks", stacks);
+        }
+
+        // The strongest burn applied so far wins
+        add_status(&mut effects, StatusEffectType::Burn, 2.0);
+        assert_eq!(effects.get(StatusEffectType::Burn).unwrap().value, 4.0);
+    }
+
+    #[test]
+    fn poison_adds_duration_until_ten_stacks() {
+        let mut effects = StatusEffects::default();
+        add_status(&mut effects, StatusEffectType::Poison, 2.0);
+        assert_eq!(frames_left(&effects, StatusEffectType::Poison), frames_from_seconds(5.0));
+
+        run_for(&mut effects, 60);
+        add_status(&mut effects, StatusEffectType::Poison, 2.0);
+        assert_eq!(effects.stacks(StatusEffectType::Poison), 2);
+        assert_eq!(frames_left(&effects, StatusEffectType::Poison), frames_from_seconds(4.5));
+
+        for _ in 2..10 {
+            add_status(&mut effects, StatusEffectType::Poison, 2.0);
+        }
+        assert_eq!(effects.stacks(StatusEffectType::Poison), 10);
+        assert_eq!(frames_left(&effects, StatusEffectType::Poison), frames_from_seconds(8.5));
+
+        // Past the cap neither stacks nor time are added
+        add_status(&mut effects, StatusEffectType::Poison, 2.0);
+        assert_eq!(effects.stacks(StatusEffectType::Poison), 10);
+        assert_eq!(frames_left(&effects, StatusEffectType::Poison), frames_from_seconds(8.5));
+    }
+
+    #[test]
+    fn stuns_are_ignored_during_the_immunity_that_follows_one() {
+        let mut world = World::new();
+        world.init_resource::<Events<DealDamage>>();
+        let mut effects = StatusEffects::default();
+        add_status(&mut effects, StatusEffectType::Stun, 0.0);
+        let target = world.spawn((effects, Health::new(100.0))).id();
+
+        // Stuns never extend
+        run_for(&mut world.get_mut::<StatusEffects>(target).unwrap(), 30);
+        add_status(&mut world.get_mut::<StatusEffects>(target).unwrap(), StatusEffectType::Stun, 0.0);
+        assert_eq!(frames_left(world.get::<StatusEffects>(target).unwrap(), StatusEffectType::Stun), frames_from_seconds(0.5));
+
+        for _ in 0..frames_from_seconds(0.5) {
+            world.run_system_once(tick_status_effects);
+        }
+        let effects = world.get::<StatusEffects>(target).unwrap();
+        assert!(effects.get(StatusEffectType::Stun).is_none());
+        assert_eq!(effects.stun_immunity.remaining(), frames_from_seconds(STUN_IMMUNITY_SECS));
+
+        let mut effects = effects.clone();
+        run_for(&mut effects, frames_from_seconds(STUN_IMMUNITY_SECS) - 1);
+        add_status(&mut effects, StatusEffectType::Stun, 0.0);
+        assert!(!effects.is_incapacitated());
+
+        run_for(&mut effects, 1);
+        add_status(&mut effects, StatusEffectType::Stun, 0.0);
+        assert_eq!(frames_left(&effects, StatusEffectType::Stun), frames_from_seconds(1.0));
+    }
+
+    #[test]
+    fn move_and_archetype_data_carry_on_hit_statuses() {
+        let status = |effect, value| Some(OnHitStatus { effect, value });
+
+        let move_sets = MoveSets::load();
+        let sword = move_sets.get("sword").unwrap();
+        let light_3 = &sword.moves[sword.find("light_3").unwrap()];
+        assert_eq!(light_3.status, status(StatusEffectType::Bleed, 3.0));
+        assert_eq!(sword.moves[sword.find("light_1").unwrap()].status, None);
+
+        let archetypes = EnemyArchetypes::load();
+        let mender = archetypes.get("mender").unwrap();
+        assert_eq!(mender.attacks[0].status, status(StatusEffectType::Shield, 30.0));
+        assert_eq!(archetypes.get("common_melee").unwrap().attacks[0].status, None);
+    }
+}
@@ -1,8 +0,0 @@
-// Systems module for organizing game systems
-pub mod input;
-pub mod physics;
-pub mod rendering;
-
-pub use input::*;
-pub use physics::*;
-pub use rendering::*;
\ No newline at end of file
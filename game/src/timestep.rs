@@ -0,0 +1,101 @@
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use crate::components::*;
+use crate::replication::SnapshotBuffer;
+
+// ============= Fixed Timestep =============
+//
+// Movement, combat and AI run in `FixedUpdate` at `SIMULATION_HZ`, so frame
+// data and replays don't depend on the display. Between ticks each simulated
+// body is drawn at a blend of its last two simulated positions: the true
+// position is put back before every tick and the blend applied again after.
+
+pub struct TimestepPlugin;
+
+impl Plugin for TimestepPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Time::<Fixed>::from_hz(SIMULATION_HZ))
+            .add_systems(FixedFirst, restore_simulated_positions)
+            .add_systems(FixedLast, record_simulated_positions)
+            .add_systems(PostUpdate, (
+                track_simulated_bodies,
+                interpolate_rendered_positions,
+            ).chain().before(TransformSystem::TransformPropagate));
+    }
+}
+
+/// Simulated positions from the last two ticks, and what was last drawn
+#[derive(Component, Debug, Clone)]
+pub struct RenderInterpolation {
+    previous: Vec3,
+    current: Vec3,
+    rendered: Vec3,
+}
+
+impl RenderInterpolation {
+    fn at(translation: Vec3) -> Self {
+        Self {
+            previous: translation,
+            current: translation,
+            rendered: translation,
+        }
+    }
+
+    /// Anything outside the simulation that moved the body (a room change,
+    /// a respawn) wins, and the blend starts over from there
+    fn teleported(&mut self, translation: Vec3) -> bool {
+        if translation == self.rendered {
+            return false;
+        }
+        *self = Self::at(translation);
+        true
+    }
+}
+
+type UntrackedBodyQuery<'w, 's> = Query<'w, 's, (Entity, &'static Transform), (With<Velocity>, Without<RenderInterpolation>)>;
+
+/// Everything with a velocity moves in the simulation. Replicas carry the
+/// component too but are drawn by snapshot interpolation instead.
+fn track_simulated_bodies(
+    mut commands: Commands,
+    query: UntrackedBodyQuery,
+) {
+    for (entity, transform) in query.iter() {
+        commands.entity(entity).insert(RenderInterpolation::at(transform.translation));
+    }
+}
+
+fn restore_simulated_positions(
+    mut query: Query<(&mut Transform, &mut RenderInterpolation), Without<SnapshotBuffer>>,
+) {
+    for (mut transform, mut interpolation) in query.iter_mut() {
+        if !interpolation.teleported(transform.translation) {
+            transform.translation = interpolation.current;
+        }
+        interpolation.previous = interpolation.current;
+    }
+}
+
+fn record_simulated_positions(
+    mut query: Query<(&Transform, &mut RenderInterpolation), Without<SnapshotBuffer>>,
+) {
+    for (transform, mut interpolation) in query.iter_mut() {
+        interpolation.current = transform.translation;
+        interpolation.rendered = transform.translation;
+    }
+}
+
+fn interpolate_rendered_positions(
+    fixed_time: Res<Time<Fixed>>,
+    mut query: Query<(&mut Transform, &mut RenderInterpolation), Without<SnapshotBuffer>>,
+) {
+    let alpha = fixed_time.overstep_fraction();
+
+    for (mut transform, mut interpolation) in query.iter_mut() {
+        if interpolation.teleported(transform.translation) {
+            continue;
+        }
+        transform.translation = interpolation.previous.lerp(interpolation.current, alpha);
+        interpolation.rendered = transform.translation;
+    }
+}
@@ -0,0 +1,533 @@
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use crate::network::SignalingMessage;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::{SocketAddr, UdpSocket};
+
+/// Payload as it came off the wire, before deserialisation
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawPayload {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// Queue shared between producers (JS callbacks, other endpoints) and the
+/// Bevy systems that drain it each frame
+pub struct MessageQueue<T>(Arc<Mutex<VecDeque<T>>>);
+
+impl<T> MessageQueue<T> {
+    pub fn push(&self, item: T) {
+        if let Ok(mut queue) = self.0.lock() {
+            queue.push_back(item);
+        }
+    }
+
+    pub fn drain(&self) -> Vec<T> {
+        match self.0.lock() {
+            Ok(mut queue) => queue.drain(..).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+impl<T> Clone for MessageQueue<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Default for MessageQueue<T> {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(VecDeque::new())))
+    }
+}
+
+// ============= Transport Trait =============
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransportEvent {
+    PeerConnected { peer_id: String },
+    PeerDisconnected { peer_id: String },
+    Message { peer_id: String, payload: RawPayload },
+    Signaling(SignalingMessage),
+}
+
+/// How `NetworkPlugin` reaches other players: WebRTC in the browser, UDP on
+/// desktop, in-process channels for tests
+pub trait Transport: Send + Sync {
+    fn local_id(&self) -> &str;
+    fn peers(&self) -> Vec<String>;
+    fn send(&mut self, peer_id: &str, payload: &RawPayload);
+    fn poll(&mut self) -> Vec<TransportEvent>;
+
+    fn broadcast(&mut self, payload: &RawPayload) {
+        for peer_id in self.peers() {
+            self.send(&peer_id, payload);
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct ActiveTransport(pub Box<dyn Transport>);
+
+// ============= In-Process Channel Transport =============
+
+/// Connects any number of `ChannelTransport` endpoints inside one process
+#[derive(Clone, Default)]
+pub struct ChannelHub {
+    mailboxes: Arc<Mutex<HashMap<String, MessageQueue<TransportEvent>>>>,
+}
+
+impl ChannelHub {
+    /// Join the hub; everyone already in it sees a connect, and vice versa
+    pub fn endpoint(&self, local_id: &str) -> ChannelTransport {
+        let inbox = MessageQueue::default();
+        if let Ok(mut mailboxes) = self.mailboxes.lock() {
+            for (peer_id, mailbox) in mailboxes.iter() {
+                mailbox.push(TransportEvent::PeerConnected { peer_id: local_id.to_string() });
+                inbox.push(TransportEvent::PeerConnected { peer_id: peer_id.clone() });
+            }
+            mailboxes.insert(local_id.to_string(), inbox.clone());
+        }
+
+        ChannelTransport {
+            local_id: local_id.to_string(),
+            hub: self.clone(),
+            inbox,
+        }
+    }
+}
+
+pub struct ChannelTransport {
+    local_id: String,
+    hub: ChannelHub,
+    inbox: MessageQueue<TransportEvent>,
+}
+
+impl Transport for ChannelTransport {
+    fn local_id(&self) -> &str {
+        &self.local_id
+    }
+
+    fn peers(&self) -> Vec<String> {
+        match self.hub.mailboxes.lock() {
+            Ok(mailboxes) => mailboxes
+                .keys()
+                .filter(|peer_id| **peer_id != self.local_id)
+                .cloned()
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn send(&mut self, peer_id: &str, payload: &RawPayload) {
+        if let Ok(mailboxes) = self.hub.mailboxes.lock() {
+            if let Some(mailbox) = mailboxes.get(peer_id) {
+                mailbox.push(TransportEvent::Message {
+                    peer_id: self.local_id.clone(),
+                    payload: payload.clone(),
+                });
+            }
+        }
+    }
+
+    fn poll(&mut self) -> Vec<TransportEvent> {
+        self.inbox.drain()
+    }
+}
+
+impl Drop for ChannelTransport {
+    fn drop(&mut self) {
+        if let Ok(mut mailboxes) = self.hub.mailboxes.lock() {
+            mailboxes.remove(&self.local_id);
+            for mailbox in mailboxes.values() {
+                mailbox.push(TransportEvent::PeerDisconnected { peer_id: self.local_id.clone() });
+            }
+        }
+    }
+}
+
+// ============= Native UDP Transport =============
+
+#[cfg(not(target_arch = "wasm32"))]
+const UDP_HELLO: u8 = 0;
+#[cfg(not(target_arch = "wasm32"))]
+const UDP_TEXT: u8 = 1;
+#[cfg(not(target_arch = "wasm32"))]
+const UDP_BINARY: u8 = 2;
+#[cfg(not(target_arch = "wasm32"))]
+const UDP_BYE: u8 = 3;
+
+#[cfg(not(target_arch = "wasm32"))]
+const UDP_HEARTBEAT_SECS: f32 = 1.0;
+#[cfg(not(target_arch = "wasm32"))]
+const UDP_TIMEOUT_SECS: f32 = 5.0;
+
+/// Desktop transport over plain UDP. Every datagram is
+/// `[kind][sender id length][sender id][body]`; peers discover each other with
+/// hellos sent to the configured addresses and keep alive with heartbeats.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct UdpTransport {
+    local_id: String,
+    socket: UdpSocket,
+    bootstrap: Vec<SocketAddr>,
+    peers: HashMap<String, SocketAddr>,
+    last_seen: HashMap<String, instant::Instant>,
+    last_heartbeat: instant::Instant,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl UdpTransport {
+    pub fn bind(local_id: &str, bind_addr: &str, bootstrap: Vec<SocketAddr>) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(bind_addr)?;
+        socket.set_nonblocking(true)?;
+
+        let mut transport = Self {
+            local_id: local_id.to_string(),
+            socket,
+            bootstrap,
+            peers: HashMap::new(),
+            last_seen: HashMap::new(),
+            last_heartbeat: instant::Instant::now(),
+        };
+        transport.say_hello();
+        Ok(transport)
+    }
+
+    /// `ARPG_BIND=127.0.0.1:7000 ARPG_PEERS=127.0.0.1:7001` runs a second
+    /// native client on the same machine; without `ARPG_BIND` we stay offline
+    pub fn from_env(local_id: &str) -> Option<std::io::Result<Self>> {
+        let bind_addr = std::env::var("ARPG_BIND").ok()?;
+        let bootstrap = std::env::var("ARPG_PEERS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|addr| addr.trim().parse().ok())
+            .collect();
+
+        Some(Self::bind(local_id, &bind_addr, bootstrap))
+    }
+
+    fn frame(&self, kind: u8, body: &[u8]) -> Vec<u8> {
+        let id = self.local_id.as_bytes();
+        let id_len = id.len().min(u8::MAX as usize);
+
+        let mut datagram = Vec::with_capacity(2 + id_len + body.len());
+        datagram.push(kind);
+        datagram.push(id_len as u8);
+        datagram.extend_from_slice(&id[..id_len]);
+        datagram.extend_from_slice(body);
+        datagram
+    }
+
+    fn say_hello(&mut self) {
+        let hello = self.frame(UDP_HELLO, &[]);
+        let known: Vec<SocketAddr> = self.peers.values().copied().collect();
+        for addr in self.bootstrap.iter().chain(known.iter()) {
+            let _ = self.socket.send_to(&hello, addr);
+        }
+        self.last_heartbeat = instant::Instant::now();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Transport for UdpTransport {
+    fn local_id(&self) -> &str {
+        &self.local_id
+    }
+
+    fn peers(&self) -> Vec<String> {
+        self.peers.keys().cloned().collect()
+    }
+
+    fn send(&mut self, peer_id: &str, payload: &RawPayload) {
+        let Some(addr) = self.peers.get(peer_id).copied() else {
+            return;
+        };
+        let datagram = match payload {
+            RawPayload::Text(text) => self.frame(UDP_TEXT, text.as_bytes()),
+            RawPayload::Binary(bytes) => self.frame(UDP_BINARY, bytes),
+        };
+        let _ = self.socket.send_to(&datagram, addr);
+    }
+
+    fn poll(&mut self) -> Vec<TransportEvent> {
+        let mut events = Vec::new();
+        let mut buffer = [0u8; 65_536];
+        let now = instant::Instant::now();
+
+        while let Ok((len, addr)) = self.socket.recv_from(&mut buffer) {
+            let datagram = &buffer[..len];
+            if datagram.len() < 2 || datagram.len() < 2 + datagram[1] as usize {
+                continue; // Truncated or foreign packet
+            }
+            let kind = datagram[0];
+            let id_end = 2 + datagram[1] as usize;
+            let Ok(peer_id) = std::str::from_utf8(&datagram[2..id_end]) else {
+                continue;
+            };
+            if peer_id == self.local_id {
+                continue; // Our own hello echoed back via a bootstrap address
+            }
+            let peer_id = peer_id.to_string();
+            let body = &datagram[id_end..];
+
+            if kind == UDP_BYE {
+                if self.peers.remove(&peer_id).is_some() {
+                    self.last_seen.remove(&peer_id);
+                    events.push(TransportEvent::PeerDisconnected { peer_id });
+                }
+                continue;
+            }
+
+            if self.peers.insert(peer_id.clone(), addr).is_none() {
+                // Answer so the other side learns about us even if we weren't in its bootstrap list
+                let hello = self.frame(UDP_HELLO, &[]);
+                let _ = self.socket.send_to(&hello, addr);
+                events.push(TransportEvent::PeerConnected { peer_id: peer_id.clone() });
+            }
+            self.last_seen.insert(peer_id.clone(), now);
+
+            match kind {
+                UDP_TEXT => {
+                    if let Ok(text) = std::str::from_utf8(body) {
+                        events.push(TransportEvent::Message {
+                            peer_id,
+                            payload: RawPayload::Text(text.to_string()),
+                        });
+                    }
+                }
+                UDP_BINARY => {
+                    events.push(TransportEvent::Message {
+                        peer_id,
+                        payload: RawPayload::Binary(body.to_vec()),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        // Drop peers that went quiet
+        let timed_out: Vec<String> = self
+            .last_seen
+            .iter()
+            .filter(|(_, seen)| now.duration_since(**seen).as_secs_f32() > UDP_TIMEOUT_SECS)
+            .map(|(peer_id, _)| peer_id.clone())
+            .collect();
+        for peer_id in timed_out {
+            self.peers.remove(&peer_id);
+            self.last_seen.remove(&peer_id);
+            events.push(TransportEvent::PeerDisconnected { peer_id });
+        }
+
+        if now.duration_since(self.last_heartbeat).as_secs_f32() > UDP_HEARTBEAT_SECS {
+            self.say_hello();
+        }
+
+        events
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for UdpTransport {
+    fn drop(&mut self) {
+        let bye = self.frame(UDP_BYE, &[]);
+        for addr in self.peers.values() {
+            let _ = self.socket.send_to(&bye, addr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::world::CommandQueue;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+    use crate::archetypes::EnemyArchetypes;
+    use crate::components::{InputFrame, PendingInputs, Player, FRAME_SECONDS, SIMULATION_HZ};
+    use crate::netcode::NetcodePlugin;
+    use crate::network::{GameMessage, NetworkPlugin, NetworkState};
+    use crate::protocol;
+    use crate::replication::ReplicationPlugin;
+    use crate::resources::{GameState, LocalInputHistory, RoomStatus};
+    use crate::room::spawn_player;
+
+    fn messages(events: Vec<TransportEvent>) -> Vec<(String, GameMessage)> {
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                TransportEvent::Message { peer_id, payload } => {
+                    Some((peer_id, protocol::decode(&payload).expect("decodable payload")))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn endpoints_see_each_other_connect() {
+        let hub = ChannelHub::default();
+        let mut host = hub.endpoint("host");
+        let mut client = hub.endpoint("client");
+
+        assert_eq!(host.poll(), vec![TransportEvent::PeerConnected { peer_id: "client".to_string() }]);
+        assert_eq!(client.poll(), vec![TransportEvent::PeerConnected { peer_id: "host".to_string() }]);
+        assert_eq!(host.peers(), vec!["client".to_string()]);
+        assert_eq!(client.peers(), vec!["host".to_string()]);
+        assert!(host.poll().is_empty());
+    }
+
+    #[test]
+    fn encoded_messages_round_trip_between_endpoints() {
+        let hub = ChannelHub::default();
+        let mut host = hub.endpoint("host");
+        let mut client = hub.endpoint("client");
+        host.poll();
+        client.poll();
+
+        let seed = protocol::encode(&GameMessage::RunSeed { seed: 42 }).unwrap();
+        host.send("client", &seed);
+        let joined = protocol::encode(&GameMessage::PlayerJoined {
+            player_id: "client".to_string(),
+            weapon: "sword".to_string(),
+        })
+        .unwrap();
+        client.send("host", &joined);
+
+        let received = messages(client.poll());
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].0, "host");
+        assert!(matches!(received[0].1, GameMessage::RunSeed { seed: 42 }));
+
+        let received = messages(host.poll());
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].0, "client");
+        match &received[0].1 {
+            GameMessage::PlayerJoined { player_id, weapon } => {
+                assert_eq!(player_id, "client");
+                assert_eq!(weapon, "sword");
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn broadcast_reaches_every_other_endpoint() {
+        let hub = ChannelHub::default();
+        let mut host = hub.endpoint("host");
+        let mut a = hub.endpoint("a");
+        let mut b = hub.endpoint("b");
+        host.poll();
+        a.poll();
+        b.poll();
+
+        let payload = RawPayload::Text("hello".to_string());
+        host.broadcast(&payload);
+
+        let expected = vec![TransportEvent::Message { peer_id: "host".to_string(), payload }];
+        assert_eq!(a.poll(), expected);
+        assert_eq!(b.poll(), expected);
+        assert!(host.poll().is_empty());
+    }
+
+    #[test]
+    fn send_to_unknown_peer_is_dropped() {
+        let hub = ChannelHub::default();
+        let mut host = hub.endpoint("host");
+        let mut client = hub.endpoint("client");
+        client.poll();
+
+        host.send("nobody", &RawPayload::Binary(vec![1, 2, 3]));
+        assert!(client.poll().is_empty());
+    }
+
+    #[test]
+    fn dropping_an_endpoint_disconnects_it() {
+        let hub = ChannelHub::default();
+        let mut host = hub.endpoint("host");
+        let client = hub.endpoint("client");
+        host.poll();
+
+        drop(client);
+
+        assert_eq!(host.poll(), vec![TransportEvent::PeerDisconnected { peer_id: "client".to_string() }]);
+        assert!(host.peers().is_empty());
+        host.send("client", &RawPayload::Text("late".to_string()));
+    }
+
+    /// A headless peer on the hub: networking, netcode and replication with
+    /// its own local player, stepping one simulation tick per update
+    fn peer_app(hub: &ChannelHub, player_id: &str) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(FRAME_SECONDS)))
+            .insert_resource(Time::<Fixed>::from_hz(SIMULATION_HZ))
+            .insert_resource(NetworkState {
+                player_id: player_id.to_string(),
+                ..default()
+            })
+            .insert_resource(ActiveTransport(Box::new(hub.endpoint(player_id))))
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<LocalInputHistory>()
+            .init_resource::<GameState>()
+            .init_resource::<RoomStatus>()
+            .init_resource::<EnemyArchetypes>()
+            .add_plugins((NetworkPlugin, NetcodePlugin, ReplicationPlugin));
+
+        let mut queue = CommandQueue::default();
+        spawn_player(&mut Commands::new(&mut queue, app.world()), player_id, Vec2::ZERO, true);
+        queue.apply(app.world_mut());
+        app
+    }
+
+    fn player_entity(app: &mut App, player_id: &str) -> Option<Entity> {
+        let mut players = app.world_mut().query::<(Entity, &Player)>();
+        players
+            .iter(app.world())
+            .find(|(_, player)| player.id == player_id)
+            .map(|(entity, _)| entity)
+    }
+
+    #[test]
+    fn two_apps_elect_a_host_and_exchange_inputs_and_snapshots() {
+        let hub = ChannelHub::default();
+        // Join order doesn't matter; the lowest id hosts
+        let mut client = peer_app(&hub, "player_b");
+        let mut host = peer_app(&hub, "player_a");
+        client.update();
+        host.update();
+
+        for app in [&client, &host] {
+            assert_eq!(app.world().resource::<NetworkState>().host_id.as_deref(), Some("player_a"));
+        }
+        assert!(host.world().resource::<NetworkState>().is_host());
+        assert!(!client.world().resource::<NetworkState>().is_host());
+
+        // Somewhere only the host's copy of the client's player is
+        let remote = player_entity(&mut host, "player_b").expect("host spawned the client's player");
+        host.world_mut().get_mut::<Transform>(remote).unwrap().translation = Vec3::new(123.0, 45.0, 1.0);
+        *host.world_mut().resource_mut::<RoomStatus>() = RoomStatus { room_number: 4, cleared: true };
+
+        for _ in 0..30 {
+            let mut history = client.world_mut().resource_mut::<LocalInputHistory>();
+            let sequence = history.next_sequence();
+            history.record(InputFrame { sequence, actions: Vec::new() });
+            client.update();
+            host.update();
+        }
+
+        // The client's frames are queued on its player on the host, in order
+        let pending = host.world().get::<PendingInputs>(remote).unwrap();
+        let sequences: Vec<u32> = pending.frames.iter().map(|frame| frame.sequence).collect();
+        assert!(!sequences.is_empty());
+        assert_eq!(sequences, (1..=sequences.len() as u32).collect::<Vec<_>>());
+
+        // and the host's snapshots reach the client
+        assert_eq!(*client.world().resource::<RoomStatus>(), RoomStatus { room_number: 4, cleared: true });
+        let local = player_entity(&mut client, "player_b").unwrap();
+        let position = client.world().get::<Transform>(local).unwrap().translation.truncate();
+        assert_eq!(position, Vec2::new(123.0, 45.0));
+    }
+}
@@ -0,0 +1,230 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::components::*;
+use crate::resources::*;
+use crate::movesets::Move;
+use crate::network::{GameMessage, NetworkState, PeerConnected, PeerMessageReceived};
+use crate::projectiles::ProjectileSpec;
+use crate::protocol;
+use crate::transport::ActiveTransport;
+
+// ============= Weapons =============
+//
+// Weapon archetypes live in assets/weapons.json next to the move-sets they
+// use. A weapon supplies the move-set, base damage and attack speed, its range
+// and whether its moves land as melee hitboxes or projectiles. The local
+// player picks one on the main menu from `MetaProgression::unlocked_weapons`
+// and tells its peers in a `PlayerJoined` message, so every peer equips every
+// player with the weapon they picked.
+
+pub const DEFAULT_WEAPON: &str = "sword";
+
+/// Move-set hitboxes are authored for this reach; melee weapons stretch them
+/// to their own range
+pub const MELEE_REFERENCE_RANGE: f32 = 70.0;
+
+const WEAPONS_JSON: &str = include_str!("../assets/weapons.json");
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WeaponDef {
+    pub name: String,
+    pub move_set: String,
+    pub damage: f32,
+    pub attack_speed: f32,
+    pub range: f32,
+    pub attack: WeaponAttack,
+    #[serde(default)]
+    pub projectile_speed: f32,
+}
+
+/// Every weapon by id, parsed from the embedded asset at startup
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Weapons {
+    pub weapons: HashMap<String, WeaponDef>,
+}
+
+impl Weapons {
+    pub fn load() -> Self {
+        match serde_json::from_str(WEAPONS_JSON) {
+            Ok(weapons) => Self { weapons },
+            Err(e) => {
+                error!("Failed to parse assets/weapons.json: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&WeaponDef> {
+        self.weapons.get(id)
+    }
+
+    /// Unlocked weapons that actually exist, in unlock order
+    pub fn unlocked<'a>(&'a self, meta: &'a MetaProgression) -> impl Iterator<Item = (&'a str, &'a WeaponDef)> {
+        meta.unlocked_weapons
+            .iter()
+            .filter_map(|id| self.weapons.get_key_value(id.as_str()))
+            .map(|(id, weapon)| (id.as_str(), weapon))
+    }
+}
+
+/// The weapon each remote player picked, by player id
+#[derive(Resource, Debug, Default)]
+pub struct PeerWeapons(HashMap<String, String>);
+
+pub struct WeaponPlugin;
+
+impl Plugin for WeaponPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Weapons::load())
+            .init_resource::<PeerWeapons>()
+            .add_systems(Update, (
+                select_weapon.run_if(in_state(CurrentGameState::MainMenu)),
+                announce_weapon,
+                receive_peer_weapons,
+                equip_weapons,
+            ).chain());
+    }
+}
+
+/// Number keys pick from the unlocked weapons on the main menu
+fn select_weapon(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    meta: Res<MetaProgression>,
+    weapons: Res<Weapons>,
+    mut inventory: ResMut<PlayerInventory>,
+) {
+    const KEYS: [KeyCode; 9] = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+
+    let Some(index) = KEYS.iter().position(|key| keyboard.just_pressed(*key)) else {
+        return;
+    };
+    if let Some((id, _)) = weapons.unlocked(&meta).nth(index) {
+        if inventory.weapon != id {
+            inventory.weapon = id.to_string();
+        }
+    }
+}
+
+/// Tell peers our pick when they connect and whenever it changes
+fn announce_weapon(
+    network_state: Res<NetworkState>,
+    inventory: Res<PlayerInventory>,
+    transport: Option<ResMut<ActiveTransport>>,
+    mut connected: EventReader<PeerConnected>,
+) {
+    let Some(mut transport) = transport else {
+        connected.clear();
+        return;
+    };
+    let message = GameMessage::PlayerJoined {
+        player_id: network_state.player_id.clone(),
+        weapon: inventory.weapon.clone(),
+    };
+    let payload = match protocol::encode(&message) {
+        Ok(payload) => payload,
+        Err(e) => {
+            warn!("Failed to encode weapon pick: {}", e);
+            return;
+        }
+    };
+
+    if inventory.is_changed() {
+        connected.clear();
+        transport.0.broadcast(&payload);
+        return;
+    }
+    for PeerConnected { peer_id } in connected.read() {
+        transport.0.send(peer_id, &payload);
+    }
+}
+
+fn receive_peer_weapons(
+    mut peer_weapons: ResMut<PeerWeapons>,
+    mut messages: EventReader<PeerMessageReceived>,
+) {
+    for PeerMessageReceived { peer_id, message } in messages.read() {
+        if let GameMessage::PlayerJoined { player_id, weapon } = message {
+            // Peers only speak for themselves
+            if player_id == peer_id && peer_weapons.0.get(player_id) != Some(weapon) {
+                peer_weapons.0.insert(player_id.clone(), weapon.clone());
+            }
+        }
+    }
+}
+
+/// Give every player the weapon they picked: the local player the one from
+/// the menu, remote players the one they announced, or the default until
+/// they have
+fn equip_weapons(
+    mut commands: Commands,
+    mut query: Query<(Entity, &Player, &mut BaseStats, &mut AttackState, Option<&Weapon>)>,
+    weapons: Res<Weapons>,
+    inventory: Res<PlayerInventory>,
+    peer_weapons: Res<PeerWeapons>,
+) {
+    for (entity, player, mut base_stats, mut attack_state, equipped) in query.iter_mut() {
+        let id = if player.is_local {
+            inventory.weapon.as_str()
+        } else {
+            peer_weapons.0.get(&player.id).map_or(DEFAULT_WEAPON, String::as_str)
+        };
+        if equipped.is_some_and(|weapon| weapon.id == id) {
+            continue;
+        }
+        let Some(weapon) = weapons.get(id) else {
+            warn!("Unknown weapon '{}'", id);
+            continue;
+        };
+
+        base_stats.combat.damage = weapon.damage;
+        base_stats.combat.attack_speed = weapon.attack_speed;
+        attack_state.move_set = weapon.move_set.clone();
+
+        commands.entity(entity).insert(Weapon {
+            id: id.to_string(),
+            range: weapon.range,
+            attack: weapon.attack,
+            projectile_speed: weapon.projectile_speed,
+        });
+    }
+}
+
+/// What one of this weapon's shots looks like for `current`, flying until it
+/// has covered the weapon's range
+pub fn weapon_projectile(weapon: &Weapon, damage: f32, current: &Move) -> ProjectileSpec {
+    ProjectileSpec {
+        piercing: current.projectile.piercing,
+        homing: current.projectile.homing.map(|turn_rate| Homing {
+            turn_rate,
+            range: weapon.range,
+        }),
+        status: current.status,
+        ..player_projectile(damage, weapon.projectile_speed, weapon.range)
+    }
+}
+
+/// A plain player shot; replicas of any player projectile look like this
+pub fn player_projectile(damage: f32, speed: f32, range: f32) -> ProjectileSpec {
+    ProjectileSpec {
+        damage,
+        damage_type: DamageType::Physical,
+        speed,
+        range,
+        piercing: 0,
+        size: Vec2::new(16.0, 6.0),
+        color: Color::srgb(0.6, 0.9, 1.0),
+        homing: None,
+        status: None,
+    }
+}
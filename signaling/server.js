@@ -1,6 +1,5 @@
 import { WebSocketServer } from 'ws';
 import { createServer } from 'http';
-import { randomBytes } from 'crypto';
 import dotenv from 'dotenv';
 
 dotenv.config();
@@ -30,16 +29,19 @@ const server = createServer((req, res) => {
 // Create WebSocket server
 const wss = new WebSocketServer({ server });
 
-// Store rooms and clients
+// Store rooms and clients (keyed by player_id)
 const rooms = new Map();
 const clients = new Map();
 // Reverse mapping for efficient WebSocket to Client lookup
 const wsToClient = new WeakMap();
 
+// Messages use the game's SignalingMessage format: a "type" tag naming the
+// variant (Join, Leave, Offer, Answer, IceCandidate, RoomUpdate) with
+// snake_case fields. Clients are known by the player_id they join with.
+
 class Room {
-  constructor(id, hostId) {
+  constructor(id) {
     this.id = id;
-    this.hostId = hostId;
     this.players = new Set();
     this.createdAt = Date.now();
   }
@@ -54,10 +56,6 @@ class Room {
 
   removePlayer(playerId) {
     this.players.delete(playerId);
-    if (playerId === this.hostId && this.players.size > 0) {
-      // Migrate host to another player
-      this.hostId = Array.from(this.players)[0];
-    }
   }
 
   isEmpty() {
@@ -66,18 +64,16 @@ class Room {
 
   toJSON() {
     return {
-      id: this.id,
-      hostId: this.hostId,
-      players: Array.from(this.players),
-      playerCount: this.players.size
+      type: 'RoomUpdate',
+      players: Array.from(this.players)
     };
   }
 }
 
 class Client {
-  constructor(ws, id) {
+  constructor(ws) {
     this.ws = ws;
-    this.id = id;
+    this.id = null; // Set by Join
     this.roomId = null;
     this.isAlive = true;
   }
@@ -88,7 +84,8 @@ class Client {
     }
   }
 
-  joinRoom(roomId) {
+  joinRoom(playerId, roomId) {
+    this.id = playerId;
     this.roomId = roomId;
   }
 
@@ -99,18 +96,10 @@ class Client {
 
 // Handle WebSocket connections
 wss.on('connection', (ws) => {
-  const clientId = generateId();
-  const client = new Client(ws, clientId);
-  clients.set(clientId, client);
+  const client = new Client(ws);
   wsToClient.set(ws, client);
 
-  console.log(`Client connected: ${clientId}`);
-
-  // Send welcome message
-  client.send({
-    type: 'welcome',
-    id: clientId
-  });
+  console.log('Client connected');
 
   // Set up ping/pong for connection health
   ws.on('pong', () => {
@@ -123,10 +112,6 @@ wss.on('connection', (ws) => {
       handleMessage(client, message);
     } catch (error) {
       console.error('Failed to parse message:', error);
-      client.send({
-        type: 'error',
-        message: 'Invalid message format'
-      });
     }
   });
 
@@ -135,177 +120,126 @@ wss.on('connection', (ws) => {
   });
 
   ws.on('error', (error) => {
-    console.error(`WebSocket error for client ${clientId}:`, error);
+    console.error(`WebSocket error for client ${client.id}:`, error);
   });
 });
 
 function handleMessage(client, message) {
   switch (message.type) {
-    case 'create-room':
-      handleCreateRoom(client, message);
+    case 'Join':
+      handleJoin(client, message);
       break;
     
-    case 'join-room':
-      handleJoinRoom(client, message);
+    case 'Leave':
+      handleLeave(client);
       break;
     
-    case 'leave-room':
-      handleLeaveRoom(client);
-      break;
-    
-    case 'offer':
-    case 'answer':
-    case 'ice-candidate':
+    case 'Offer':
+    case 'Answer':
+    case 'IceCandidate':
       handleSignaling(client, message);
       break;
     
-    case 'get-room-info':
-      handleGetRoomInfo(client, message);
-      break;
-    
     default:
-      client.send({
-        type: 'error',
-        message: `Unknown message type: ${message.type}`
-      });
+      console.warn(`Unknown message type from ${client.id}: ${message.type}`);
   }
 }
 
-function handleCreateRoom(client, message) {
-  const roomId = message.roomId || generateRoomId();
+function handleJoin(client, message) {
+  const { room_id: roomId, player_id: playerId } = message;
   
-  if (rooms.has(roomId)) {
-    client.send({
-      type: 'error',
-      message: 'Room already exists'
-    });
+  if (typeof roomId !== 'string' || !roomId || typeof playerId !== 'string' || !playerId) {
+    console.warn('Join without room_id or player_id');
     return;
   }
 
-  const room = new Room(roomId, client.id);
-  room.addPlayer(client.id);
-  rooms.set(roomId, room);
-  client.joinRoom(roomId);
-
-  client.send({
-    type: 'room-created',
-    roomId: roomId,
-    room: room.toJSON()
-  });
+  const existing = clients.get(playerId);
+  if (existing && existing !== client) {
+    console.warn(`Player id ${playerId} is already connected`);
+    return;
+  }
 
-  console.log(`Room created: ${roomId} by ${client.id}`);
-}
+  // Switching rooms counts as leaving the old one
+  if (client.roomId && client.roomId !== roomId) {
+    handleLeave(client);
+  }
 
-function handleJoinRoom(client, message) {
-  const { roomId } = message;
-  
   if (!rooms.has(roomId)) {
-    client.send({
-      type: 'room-not-found',
-      roomId: roomId
-    });
-    return;
+    rooms.set(roomId, new Room(roomId));
+    console.log(`Room created: ${roomId}`);
   }
-
   const room = rooms.get(roomId);
   
-  if (!room.addPlayer(client.id)) {
-    client.send({
-      type: 'room-full',
-      roomId: roomId
-    });
+  if (!room.players.has(playerId) && !room.addPlayer(playerId)) {
+    console.warn(`Room ${roomId} is full, rejecting ${playerId}`);
+    if (room.isEmpty()) {
+      rooms.delete(roomId);
+    }
     return;
   }
 
-  client.joinRoom(roomId);
-
-  // Notify the joiner
-  client.send({
-    type: 'room-joined',
-    roomId: roomId,
-    room: room.toJSON()
-  });
+  client.joinRoom(playerId, roomId);
+  clients.set(playerId, client);
 
-  // Notify existing players
-  broadcastToRoom(roomId, {
-    type: 'peer-joined',
-    peerId: client.id
-  }, client.id);
+  // Everyone, the joiner included, gets the new roster and negotiates from it
+  broadcastToRoom(roomId, room.toJSON());
 
-  console.log(`Client ${client.id} joined room ${roomId}`);
+  console.log(`Client ${playerId} joined room ${roomId}`);
 }
 
-function handleLeaveRoom(client) {
+function handleLeave(client) {
   if (!client.roomId) return;
 
-  const room = rooms.get(client.roomId);
+  const roomId = client.roomId;
+  const room = rooms.get(roomId);
   if (room) {
     room.removePlayer(client.id);
     
     if (room.isEmpty()) {
-      rooms.delete(client.roomId);
-      console.log(`Room ${client.roomId} deleted (empty)`);
+      rooms.delete(roomId);
+      console.log(`Room ${roomId} deleted (empty)`);
     } else {
       // Notify remaining players
-      broadcastToRoom(client.roomId, {
-        type: 'peer-left',
-        peerId: client.id
-      }, client.id);
+      broadcastToRoom(roomId, {
+        type: 'Leave',
+        player_id: client.id
+      });
+      broadcastToRoom(roomId, room.toJSON());
     }
   }
 
-  console.log(`Client ${client.id} left room ${client.roomId}`);
+  console.log(`Client ${client.id} left room ${roomId}`);
   client.leaveRoom();
 }
 
 function handleSignaling(client, message) {
   const { to } = message;
+  const targetClient = clients.get(to);
   
-  if (!to || !clients.has(to)) {
-    client.send({
-      type: 'error',
-      message: 'Target peer not found'
-    });
+  if (!client.roomId || !targetClient || targetClient.roomId !== client.roomId) {
+    console.warn(`Dropping ${message.type} from ${client.id}: target ${to} not in room`);
     return;
   }
 
-  const targetClient = clients.get(to);
-  
-  // Forward the signaling message
+  // Forward the signaling message; the sender can't claim someone else's id
   targetClient.send({
     ...message,
     from: client.id
   });
 }
 
-function handleGetRoomInfo(client, message) {
-  const { roomId } = message;
-  
-  if (!rooms.has(roomId)) {
-    client.send({
-      type: 'room-not-found',
-      roomId: roomId
-    });
-    return;
-  }
-
-  const room = rooms.get(roomId);
-  client.send({
-    type: 'room-info',
-    room: room.toJSON()
-  });
-}
-
 function handleDisconnect(client) {
   console.log(`Client disconnected: ${client.id}`);
   
   // Leave room if in one
   if (client.roomId) {
-    handleLeaveRoom(client);
+    handleLeave(client);
   }
   
   // Remove from clients map
-  clients.delete(client.id);
+  if (client.id && clients.get(client.id) === client) {
+    clients.delete(client.id);
+  }
 }
 
 function broadcastToRoom(roomId, message, excludeId = null) {
@@ -322,20 +256,6 @@ function broadcastToRoom(roomId, message, excludeId = null) {
   });
 }
 
-function generateId() {
-  // Use crypto-secure random generation
-  const timestamp = Date.now().toString(36);
-  const random = randomBytes(8).toString('hex');
-  return timestamp + random;
-}
-
-function generateRoomId() {
-  // Generate crypto-secure 6-character room code
-  const bytes = randomBytes(4);
-  const hex = bytes.toString('hex').toUpperCase();
-  return hex.slice(0, 6);
-}
-
 // Heartbeat to detect disconnected clients
 const heartbeatInterval = setInterval(() => {
   wss.clients.forEach((ws) => {